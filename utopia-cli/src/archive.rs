@@ -0,0 +1,213 @@
+use sevenz_rust::{Password, SevenZReader};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use tracing::info;
+use utopia_winit::SystemType;
+use zip::ZipArchive;
+
+// Larger than any ROM for a supported system (the biggest N64 ROMs are 64 MiB). Sizes given in
+// archive headers are not trusted beyond this.
+const MAX_ROM_SIZE: u64 = 256 * 1024 * 1024;
+
+pub struct Rom {
+    pub path: PathBuf,
+    pub data: Vec<u8>,
+}
+
+pub fn load(path: &Path, entry: Option<&str>) -> Result<Rom, Box<dyn Error>> {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "zip" => load_zip(path, entry),
+        "7z" => load_7z(path, entry),
+        _ => Ok(Rom {
+            path: path.to_path_buf(),
+            data: fs::read(path)?,
+        }),
+    }
+}
+
+fn load_zip(path: &Path, entry: Option<&str>) -> Result<Rom, Box<dyn Error>> {
+    let mut archive = ZipArchive::new(File::open(path)?)?;
+
+    // Entries are checked in archive order, so the same one is picked every time
+    for index in 0..archive.len() {
+        let file = archive.by_index(index)?;
+
+        if file.is_dir() || !is_match(file.name(), entry) {
+            continue;
+        }
+
+        let name = file.name().to_owned();
+
+        info!("Loading '{}' from '{}'", name, path.display());
+
+        let size = file.size();
+        let data = read_entry(file, size, MAX_ROM_SIZE)?;
+
+        return Ok(Rom {
+            path: PathBuf::from(name),
+            data,
+        });
+    }
+
+    Err(no_entry_error(path, entry))
+}
+
+fn load_7z(path: &Path, entry: Option<&str>) -> Result<Rom, Box<dyn Error>> {
+    let mut archive = SevenZReader::open(path, Password::empty())?;
+    let mut rom = None;
+
+    archive.for_each_entries(|archive_entry, reader| {
+        let name = archive_entry.name();
+
+        // Returning false only stops iteration within the current block, so entries in later
+        // blocks still need to be skipped once a ROM has been found
+        if rom.is_some() || archive_entry.is_directory() || !is_match(name, entry) {
+            // Entries in a solid block have to be read through in order
+            io::copy(reader, &mut io::sink())?;
+            return Ok(true);
+        }
+
+        info!("Loading '{}' from '{}'", name, path.display());
+
+        let data = read_entry(reader, archive_entry.size(), MAX_ROM_SIZE)?;
+
+        rom = Some(Rom {
+            path: PathBuf::from(name),
+            data,
+        });
+
+        Ok(false)
+    })?;
+
+    rom.ok_or_else(|| no_entry_error(path, entry))
+}
+
+// Reads no more than 'limit' bytes, whatever size the archive claims the entry is
+fn read_entry(reader: impl Read, size: u64, limit: u64) -> io::Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(size.min(limit) as usize);
+    reader.take(limit + 1).read_to_end(&mut data)?;
+
+    if data.len() as u64 > limit {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Archive entry is larger than {} bytes", limit),
+        ));
+    }
+
+    Ok(data)
+}
+
+fn is_match(name: &str, entry: Option<&str>) -> bool {
+    match entry {
+        Some(entry) => name == entry,
+        None => SystemType::try_from(Path::new(name)).is_ok(),
+    }
+}
+
+fn no_entry_error(path: &Path, entry: Option<&str>) -> Box<dyn Error> {
+    match entry {
+        Some(entry) => format!("Entry '{}' not found in '{}'", entry, path.display()).into(),
+        None => format!("No ROM files found in '{}'", path.display()).into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sevenz_rust::{SevenZArchiveEntry, SevenZWriter};
+    use std::io::Write;
+    use zip::write::{FileOptions, ZipWriter};
+
+    fn create_zip(name: &str, entries: &[(&str, &[u8])]) -> PathBuf {
+        let path = std::env::temp_dir().join(name);
+        let mut writer = ZipWriter::new(File::create(&path).unwrap());
+
+        for (entry_name, data) in entries {
+            writer
+                .start_file(*entry_name, FileOptions::default())
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+
+        writer.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn zip_loads_first_rom_in_archive_order() {
+        let path = create_zip(
+            "utopia-archive-test.zip",
+            &[
+                ("readme.txt", b"text"),
+                ("zelda.gb", b"first"),
+                ("mario.nes", b"second"),
+            ],
+        );
+
+        for _ in 0..8 {
+            let rom = load(&path, None).unwrap();
+            assert_eq!(rom.path, PathBuf::from("zelda.gb"));
+            assert_eq!(rom.data, b"first");
+        }
+
+        let rom = load(&path, Some("mario.nes")).unwrap();
+        assert_eq!(rom.data, b"second");
+
+        assert!(load(&path, Some("missing.sfc")).is_err());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    fn create_7z(name: &str, entries: &[(&str, &[u8])]) -> PathBuf {
+        let path = std::env::temp_dir().join(name);
+        let mut writer = SevenZWriter::create(&path).unwrap();
+
+        for (entry_name, data) in entries {
+            let mut entry = SevenZArchiveEntry::new();
+            entry.name = entry_name.to_string();
+            entry.has_stream = true;
+            writer.push_archive_entry(entry, Some(*data)).unwrap();
+        }
+
+        writer.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn seven_zip_loads_first_rom_in_archive_order() {
+        let path = create_7z(
+            "utopia-archive-test.7z",
+            &[
+                ("readme.txt", b"text"),
+                ("zelda.gb", b"first"),
+                ("mario.nes", b"second"),
+            ],
+        );
+
+        let rom = load(&path, None).unwrap();
+        assert_eq!(rom.path, PathBuf::from("zelda.gb"));
+        assert_eq!(rom.data, b"first");
+
+        let rom = load(&path, Some("mario.nes")).unwrap();
+        assert_eq!(rom.data, b"second");
+
+        assert!(load(&path, Some("missing.sfc")).is_err());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn entry_size_is_bounded() {
+        // Claimed sizes are only a hint
+        assert_eq!(read_entry(&b"rom"[..], u64::MAX, 8).unwrap(), b"rom");
+        assert_eq!(read_entry(&[0; 8][..], 0, 8).unwrap(), [0; 8]);
+        assert!(read_entry(&[0; 9][..], 9, 8).is_err());
+    }
+}
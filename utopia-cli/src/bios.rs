@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 use tracing::warn;
@@ -5,18 +6,22 @@ use tracing::warn;
 #[derive(Debug)]
 pub struct BiosLoader {
     base_path: PathBuf,
+    paths: HashMap<String, PathBuf>,
 }
 
 impl BiosLoader {
-    pub fn new(base_path: PathBuf) -> Self {
-        Self { base_path }
+    pub fn new(base_path: PathBuf, paths: HashMap<String, PathBuf>) -> Self {
+        Self { base_path, paths }
     }
 }
 
 impl utopia_winit::BiosLoader for BiosLoader {
     fn load(&self, name: &str) -> Result<Vec<u8>, utopia_winit::Error> {
-        let file_name = format!("{}.bin", name);
-        let path = self.base_path.with_file_name(file_name);
+        let path = self
+            .paths
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| self.base_path.with_file_name(format!("{}.bin", name)));
 
         let result = fs::read(&path).map_err(|err| {
             warn!("Failed to load BIOS file '{}': {}", path.display(), err);
@@ -0,0 +1,256 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item, Table, TableLike};
+use tracing::{debug, info};
+use utopia_winit::{
+    AudioFilter, FastForwardAudio, GbColorCorrection, InputConfig, InputStore, NtscFilter, RomInfo,
+    Scaler, Sync, SystemType,
+};
+
+const CONFIG_DIR: &str = "utopia";
+const CONFIG_FILE: &str = "config.toml";
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub sync: Option<Sync>,
+    pub full_screen: Option<bool>,
+    pub skip_boot: Option<bool>,
+    pub save_dir: Option<PathBuf>,
+    pub bios: HashMap<String, PathBuf>,
+    pub scale: Option<u32>,
+    pub audio_latency: Option<u32>,
+    pub fast_forward_speed: Option<f64>,
+    pub fast_forward_audio: Option<FastForwardAudio>,
+    pub audio_filter: Option<AudioFilter>,
+    pub ntsc_filter: Option<NtscFilter>,
+    pub nes_palette: Option<String>,
+    pub gb_color_correction: Option<GbColorCorrection>,
+    pub gb_frame_blend: Option<bool>,
+    pub gb_palette: Option<String>,
+    pub scaler: Option<Scaler>,
+    pub capture_dir: Option<PathBuf>,
+    pub input: InputConfig,
+}
+
+impl Settings {
+    fn merge(&mut self, other: &Settings) {
+        self.sync = other.sync.or(self.sync);
+        self.full_screen = other.full_screen.or(self.full_screen);
+        self.skip_boot = other.skip_boot.or(self.skip_boot);
+        self.save_dir = other.save_dir.clone().or(self.save_dir.take());
+        self.bios.extend(other.bios.clone());
+        self.scale = other.scale.or(self.scale);
+        self.audio_latency = other.audio_latency.or(self.audio_latency);
+        self.fast_forward_speed = other.fast_forward_speed.or(self.fast_forward_speed);
+        self.fast_forward_audio = other.fast_forward_audio.or(self.fast_forward_audio);
+        self.audio_filter = other.audio_filter.or(self.audio_filter);
+        self.ntsc_filter = other.ntsc_filter.or(self.ntsc_filter);
+        self.nes_palette = other.nes_palette.clone().or(self.nes_palette.take());
+        self.gb_color_correction = other.gb_color_correction.or(self.gb_color_correction);
+        self.gb_frame_blend = other.gb_frame_blend.or(self.gb_frame_blend);
+        self.gb_palette = other.gb_palette.clone().or(self.gb_palette.take());
+        self.scaler = other.scaler.or(self.scaler);
+        self.capture_dir = other.capture_dir.clone().or(self.capture_dir.take());
+        self.input.merge(&other.input);
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.scale == Some(0) {
+            return Err("'scale' must be at least 1".into());
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    #[serde(flatten)]
+    pub global: Settings,
+    pub system: HashMap<String, Settings>,
+    pub game: HashMap<String, Settings>,
+}
+
+impl Config {
+    pub fn load(path: Option<&Path>) -> Result<Self, Box<dyn Error>> {
+        let path = match path {
+            Some(path) => path.to_path_buf(),
+            None => {
+                let Some(path) = default_path().filter(|path| path.is_file()) else {
+                    return Ok(Self::default());
+                };
+
+                path
+            }
+        };
+
+        debug!("Loading config from '{}'", path.display());
+
+        let config = Self::parse(&fs::read_to_string(&path)?)
+            .map_err(|err| format!("Failed to parse '{}': {}", path.display(), err))?;
+
+        Ok(config)
+    }
+
+    fn parse(source: &str) -> Result<Self, String> {
+        let config: Self = toml::from_str(source).map_err(|err| err.to_string())?;
+
+        config.global.validate()?;
+
+        for (key, settings) in config.system.iter().chain(&config.game) {
+            settings
+                .validate()
+                .map_err(|err| format!("{} (in '{}')", err, key))?;
+        }
+
+        Ok(config)
+    }
+
+    pub fn resolve(&self, system_type: SystemType, rom_info: Option<&RomInfo>) -> Settings {
+        let mut settings = self.global.clone();
+
+        if let Some(system_settings) = self.system.get(system_key(system_type)) {
+            settings.merge(system_settings);
+        }
+
+        if let Some(rom_info) = rom_info {
+            let crc32 = format!("{:08x}", rom_info.crc32);
+
+            // Games can be keyed by either their CRC32 or SHA-1 (as shown by 'utopia info')
+            let game_settings = self.game.iter().find(|(key, _)| {
+                key.eq_ignore_ascii_case(&crc32) || key.eq_ignore_ascii_case(&rom_info.sha1)
+            });
+
+            if let Some((_, game_settings)) = game_settings {
+                settings.merge(game_settings);
+            }
+        }
+
+        settings
+    }
+}
+
+// Writes bindings chosen in the window back into the '[input]' table of the config file
+#[derive(Debug)]
+pub struct InputFile {
+    path: PathBuf,
+}
+
+impl InputFile {
+    pub fn new(path: Option<&Path>) -> Option<Self> {
+        let path = path.map(Path::to_path_buf).or_else(default_path)?;
+        Some(Self { path })
+    }
+}
+
+impl InputStore for InputFile {
+    fn save(&self, changes: &InputConfig) -> Result<(), Box<dyn Error>> {
+        let mut document: DocumentMut = if self.path.is_file() {
+            fs::read_to_string(&self.path)?.parse()?
+        } else {
+            DocumentMut::new()
+        };
+
+        let input = document
+            .entry("input")
+            .or_insert_with(|| Item::Table(Table::new()))
+            .as_table_mut()
+            .ok_or("'input' is not a table")?;
+
+        merge_table(input, toml_edit::ser::to_document(changes)?.as_table());
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(&self.path, document.to_string())?;
+
+        info!("Saved input bindings to '{}'", self.path.display());
+
+        Ok(())
+    }
+}
+
+fn merge_table(target: &mut dyn TableLike, source: &dyn TableLike) {
+    for (key, item) in source.iter() {
+        let Some(source) = item.as_table_like() else {
+            target.insert(key, item.clone());
+            continue;
+        };
+
+        if !has_values(source) {
+            continue;
+        }
+
+        match target.get_mut(key).and_then(Item::as_table_like_mut) {
+            Some(existing) => merge_table(existing, source),
+            None => {
+                let mut table = Table::new();
+                table.set_implicit(true);
+                merge_table(&mut table, source);
+                target.insert(key, Item::Table(table));
+            }
+        }
+    }
+}
+
+fn has_values(table: &dyn TableLike) -> bool {
+    table
+        .iter()
+        .any(|(_, item)| item.as_table_like().is_none_or(has_values))
+}
+
+fn default_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(CONFIG_DIR).join(CONFIG_FILE))
+}
+
+fn system_key(system_type: SystemType) -> &'static str {
+    match system_type {
+        SystemType::GameBoy => "gb",
+        SystemType::GameBoyAdvance => "gba",
+        SystemType::Nes => "nes",
+        SystemType::Nintendo64 => "n64",
+        SystemType::SegaMasterSystem => "sms",
+        SystemType::Snes => "snes",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn later_settings_override_earlier_ones() {
+        let config = Config::parse(
+            r#"
+            full_screen = true
+            skip_boot = true
+            scale = 2
+
+            [system.nes]
+            full_screen = false
+            "#,
+        )
+        .unwrap();
+
+        let settings = config.resolve(SystemType::Nes, None);
+        assert_eq!(settings.full_screen, Some(false));
+        assert_eq!(settings.skip_boot, Some(true));
+        assert_eq!(settings.scale, Some(2));
+
+        let settings = config.resolve(SystemType::GameBoy, None);
+        assert_eq!(settings.full_screen, Some(true));
+    }
+
+    #[test]
+    fn zero_scale_is_rejected() {
+        assert!(Config::parse("scale = 0").is_err());
+        assert!(Config::parse("[system.snes]\nscale = 0").is_err());
+        assert!(Config::parse("scale = 1").is_ok());
+    }
+}
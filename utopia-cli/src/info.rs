@@ -0,0 +1,56 @@
+use super::archive;
+use std::error::Error;
+use std::path::Path;
+use utopia_winit::{
+    AudioFilter, DefaultBiosLoader, DefaultMemoryMapper, GbColorCorrection, GbPalette, NesPalette,
+    NtscFilter, RomInfo, SystemOptions, SystemType,
+};
+
+pub fn print(rom_path: &Path, entry: Option<&str>) -> Result<(), Box<dyn Error>> {
+    let rom = archive::load(rom_path, entry)?;
+    let system_type = SystemType::detect(&rom.data, Some(&rom.path))?;
+    let info = read(system_type, &rom.data)?;
+
+    let mut fields = vec![
+        ("System", info.system_type.to_string()),
+        ("Name", info.name.unwrap_or("Unknown".into())),
+        ("CRC32", format!("{:08X}", info.crc32)),
+        ("SHA-1", info.sha1.to_uppercase()),
+    ];
+
+    fields.extend(info.fields);
+
+    let width = fields
+        .iter()
+        .map(|(label, _)| label.len())
+        .max()
+        .unwrap_or(0);
+
+    for (label, value) in fields {
+        println!(
+            "{:width$}  {}",
+            format!("{}:", label),
+            value,
+            width = width + 1
+        );
+    }
+
+    Ok(())
+}
+
+pub fn read(system_type: SystemType, rom_data: &[u8]) -> Result<RomInfo, Box<dyn Error>> {
+    let system = utopia_winit::create(SystemOptions {
+        system_type,
+        bios_loader: &DefaultBiosLoader,
+        memory_mapper: &DefaultMemoryMapper,
+        skip_boot: true,
+        audio_filter: AudioFilter::default(),
+        ntsc_filter: NtscFilter::default(),
+        nes_palette: NesPalette::default(),
+        gb_color_correction: GbColorCorrection::default(),
+        gb_frame_blend: false,
+        gb_palette: GbPalette::default(),
+    })?;
+
+    Ok(system.rom_info(rom_data)?)
+}
@@ -0,0 +1,136 @@
+use super::archive;
+use super::bios::BiosLoader;
+use super::config::{Config, InputFile};
+use super::info;
+use super::mmap::MemoryMapper;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::info;
+use utopia_winit::{
+    apply_patch, GbPalette, InputStore, NesPalette, ResetOptions, RomLoader, Sync, SystemType,
+};
+
+const PATCH_EXTENSIONS: [&str; 3] = ["ips", "bps", "ups"];
+
+// Command line options apply to every ROM loaded during the session, not just the first one
+#[derive(Debug)]
+pub struct Loader {
+    pub config: Config,
+    pub config_path: Option<PathBuf>,
+    pub bios_path: Option<PathBuf>,
+    pub skip_boot: Option<bool>,
+    pub full_screen: Option<bool>,
+    pub sync: Option<Sync>,
+}
+
+impl Loader {
+    pub fn load_rom(
+        &self,
+        rom_path: &Path,
+        entry: Option<&str>,
+        patches: Vec<PathBuf>,
+    ) -> Result<ResetOptions<MemoryMapper>, Box<dyn Error>> {
+        let mut rom = archive::load(rom_path, entry)?;
+
+        let patches = if patches.is_empty() {
+            PATCH_EXTENSIONS
+                .iter()
+                .map(|extension| rom_path.with_extension(extension))
+                .filter(|path| path.is_file())
+                .collect()
+        } else {
+            patches
+        };
+
+        for path in patches {
+            info!("Applying patch '{}'", path.display());
+            rom.data = apply_patch(rom.data, &fs::read(&path)?)?;
+        }
+
+        let system_type = SystemType::detect(&rom.data, Some(&rom.path))?;
+        let rom_info = info::read(system_type, &rom.data).ok();
+        let settings = self.config.resolve(system_type, rom_info.as_ref());
+
+        // BIOS paths in the config only apply if no path is given on the command line
+        let bios_loader = match &self.bios_path {
+            Some(bios_path) => BiosLoader::new(bios_path.clone(), HashMap::new()),
+            None => BiosLoader::new(rom_path.to_path_buf(), settings.bios),
+        };
+
+        let save_path = match settings.save_dir {
+            Some(save_dir) => {
+                fs::create_dir_all(&save_dir)?;
+                save_dir.join(rom_path.file_name().ok_or("Invalid ROM path")?)
+            }
+            None => rom_path.to_path_buf(),
+        }
+        .with_extension("sav");
+
+        Ok(ResetOptions {
+            bios_loader: Arc::new(bios_loader),
+            memory_mapper: MemoryMapper::new(save_path),
+            rom_path: rom.path,
+            rom_data: rom.data,
+            patches: Vec::new(),
+            skip_boot: self.skip_boot.or(settings.skip_boot).unwrap_or(false),
+            full_screen: self.full_screen.or(settings.full_screen).unwrap_or(false),
+            sync: self.sync.or(settings.sync),
+            scale: settings.scale,
+            audio_latency: settings.audio_latency,
+            fast_forward_speed: settings.fast_forward_speed,
+            fast_forward_audio: settings.fast_forward_audio,
+            audio_filter: settings.audio_filter,
+            ntsc_filter: settings.ntsc_filter,
+            nes_palette: settings
+                .nes_palette
+                .as_deref()
+                .map(load_palette)
+                .transpose()?,
+            gb_color_correction: settings.gb_color_correction,
+            gb_frame_blend: settings.gb_frame_blend,
+            gb_palette: settings
+                .gb_palette
+                .as_deref()
+                .map(load_gb_palette)
+                .transpose()?,
+            scaler: settings.scaler,
+            capture_dir: settings.capture_dir,
+            input: settings.input,
+            input_store: InputFile::new(self.config_path.as_deref())
+                .map(|input_file| Arc::new(input_file) as Arc<dyn InputStore>),
+            rom_loader: None,
+            achievements: None,
+            cheats: None,
+        })
+    }
+}
+
+impl RomLoader<MemoryMapper> for Loader {
+    fn load(&self, path: &Path) -> Result<ResetOptions<MemoryMapper>, Box<dyn Error>> {
+        self.load_rom(path, None, Vec::new())
+    }
+}
+
+// Palettes can be given either by PPU name or as the path to a .pal file
+fn load_palette(name: &str) -> Result<NesPalette, Box<dyn Error>> {
+    if let Some(palette) = NesPalette::from_name(name) {
+        return Ok(palette);
+    }
+
+    let data =
+        fs::read(name).map_err(|err| format!("Failed to load palette '{}': {}", name, err))?;
+    Ok(NesPalette::from_pal(data)?)
+}
+
+fn load_gb_palette(name: &str) -> Result<GbPalette, Box<dyn Error>> {
+    if let Some(palette) = GbPalette::from_name(name) {
+        return Ok(palette);
+    }
+
+    let data =
+        fs::read(name).map_err(|err| format!("Failed to load palette '{}': {}", name, err))?;
+    Ok(GbPalette::from_pal(data)?)
+}
@@ -1,61 +1,222 @@
-use bios::BiosLoader;
 use clap::builder::PossibleValue;
-use clap::{Parser, ValueEnum};
-use mmap::MemoryMapper;
+use clap::{Parser, Subcommand, ValueEnum};
+use config::Config;
+use loader::Loader;
+use render::RenderOptions;
 use std::error::Error;
+use std::fs;
 use std::path::PathBuf;
-use std::rc::Rc;
-use utopia_winit::{App, ResetOptions, Sync};
+use std::sync::Arc;
+use utopia_winit::{AchievementSet, App, Sync};
 
+mod archive;
 mod bios;
+mod config;
+mod info;
+mod loader;
 mod log;
 mod mmap;
+mod render;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 struct SyncArg(Sync);
 
 #[derive(Parser, Debug)]
-#[command(author, version)]
+#[command(
+    author,
+    version,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
-    rom_path: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
 
-    #[arg(short, long)]
+    #[arg(required = true)]
+    rom_path: Option<PathBuf>,
+
+    /// Name of the ROM file to load from a .zip or .7z archive
+    #[arg(long)]
+    entry: Option<String>,
+
+    #[arg(short, long, overrides_with = "no_full_screen")]
     full_screen: bool,
 
+    /// Start in a window, even if the config file enables full-screen mode
+    #[arg(long, overrides_with = "full_screen")]
+    no_full_screen: bool,
+
     #[arg(short, long)]
     bios_path: Option<PathBuf>,
 
-    #[arg(short, long)]
+    #[arg(short, long, overrides_with = "no_skip_boot")]
     skip_boot: bool,
 
+    /// Run the boot ROM, even if the config file skips it
+    #[arg(long, overrides_with = "skip_boot")]
+    no_skip_boot: bool,
+
     #[arg(value_enum, long)]
     sync: Option<SyncArg>,
+
+    #[arg(long)]
+    achievements: Option<PathBuf>,
+
+    /// Path to the config file (defaults to 'utopia/config.toml' in the user config directory)
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// IPS, BPS or UPS patch to apply (may be given multiple times)
+    #[arg(short, long)]
+    patch: Vec<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Print information about a ROM without running it
+    Info {
+        rom_path: PathBuf,
+
+        /// Name of the ROM file to load from a .zip or .7z archive
+        #[arg(long)]
+        entry: Option<String>,
+    },
+
+    /// Run a ROM without a window, writing its audio to WAV files
+    Render {
+        rom_path: PathBuf,
+
+        /// Name of the ROM file to load from a .zip or .7z archive
+        #[arg(long)]
+        entry: Option<String>,
+
+        #[arg(short, long)]
+        bios_path: Option<PathBuf>,
+
+        /// Path to the config file (defaults to 'utopia/config.toml' in the user config directory)
+        #[arg(long)]
+        config: Option<PathBuf>,
+
+        /// IPS, BPS or UPS patch to apply (may be given multiple times)
+        #[arg(short, long)]
+        patch: Vec<PathBuf>,
+
+        /// Length of audio to render, in seconds
+        #[arg(long, default_value_t = 60.0)]
+        seconds: f64,
+
+        /// Directory to write the WAV files to (defaults to the capture directory)
+        #[arg(short, long)]
+        output_dir: Option<PathBuf>,
+
+        /// Also write each sound channel to its own WAV file
+        #[arg(long)]
+        stems: bool,
+
+        /// Sound channel to leave out of the main mix (may be given multiple times)
+        #[arg(long)]
+        mute: Vec<String>,
+
+        /// Sound channel to play on its own in the main mix
+        #[arg(long)]
+        solo: Option<String>,
+
+        /// Print the names of the system's sound channels, then exit
+        #[arg(long)]
+        list_channels: bool,
+
+        /// Also save the last frame as a PNG, using the configured scaler
+        #[arg(long)]
+        screenshot: bool,
+    },
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
 
+    match args.command {
+        Some(Command::Info { rom_path, entry }) => {
+            return info::print(&rom_path, entry.as_deref());
+        }
+        Some(Command::Render {
+            rom_path,
+            entry,
+            bios_path,
+            config,
+            patch,
+            seconds,
+            output_dir,
+            stems,
+            mute,
+            solo,
+            list_channels,
+            screenshot,
+        }) => {
+            let _log = log::init()?;
+
+            let loader = Loader {
+                config: Config::load(config.as_deref())?,
+                config_path: config,
+                bios_path,
+                skip_boot: None,
+                full_screen: None,
+                sync: None,
+            };
+
+            let render_options = RenderOptions {
+                seconds,
+                output_dir,
+                stems,
+                mute,
+                solo,
+                list_channels,
+                screenshot,
+            };
+
+            return render::render(&loader, &rom_path, entry.as_deref(), patch, render_options);
+        }
+        None => (),
+    }
+
+    let rom_path = args.rom_path.ok_or("No ROM path given")?;
+
     let _log = log::init()?;
 
-    let rom_data = std::fs::read(&args.rom_path)?;
+    let loader = Arc::new(Loader {
+        config: Config::load(args.config.as_deref())?,
+        config_path: args.config,
+        bios_path: args.bios_path,
+        skip_boot: flag(args.skip_boot, args.no_skip_boot),
+        full_screen: flag(args.full_screen, args.no_full_screen),
+        sync: args.sync.map(|sync| sync.0),
+    });
+
+    let mut options = loader.load_rom(&rom_path, args.entry.as_deref(), args.patch)?;
+
+    options.achievements = args
+        .achievements
+        .map(|path| fs::read_to_string(path).map(|json| AchievementSet::from_json(&json)))
+        .transpose()?
+        .transpose()?;
+
+    options.rom_loader = Some(loader);
 
     let mut app = App::new();
 
-    app.reset(ResetOptions {
-        bios_loader: Rc::new(BiosLoader::new(
-            args.bios_path.unwrap_or(args.rom_path.clone()),
-        )),
-        memory_mapper: MemoryMapper::new(args.rom_path.clone()),
-        rom_path: args.rom_path.clone(),
-        rom_data,
-        skip_boot: args.skip_boot,
-        full_screen: args.full_screen,
-        sync: args.sync.map(|sync| sync.0),
-    })?;
+    app.reset(options)?;
 
     Ok(())
 }
 
+// Flags left off the command line defer to the config file
+fn flag(enabled: bool, disabled: bool) -> Option<bool> {
+    match (enabled, disabled) {
+        (true, _) => Some(true),
+        (_, true) => Some(false),
+        _ => None,
+    }
+}
+
 impl ValueEnum for SyncArg {
     fn value_variants<'a>() -> &'a [Self] {
         &[Self(Sync::None), Self(Sync::Video), Self(Sync::Audio)]
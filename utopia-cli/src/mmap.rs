@@ -1,20 +1,22 @@
 use memmap2::{MmapMut, MmapOptions};
 use std::fs::OpenOptions;
+use std::ops::{Deref, DerefMut};
 use std::path::PathBuf;
+use tracing::warn;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct MemoryMapper {
-    rom_path: PathBuf,
+    save_path: PathBuf,
 }
 
 impl MemoryMapper {
-    pub fn new(rom_path: PathBuf) -> Self {
-        Self { rom_path }
+    pub fn new(save_path: PathBuf) -> Self {
+        Self { save_path }
     }
 }
 
 impl utopia_winit::MemoryMapper for MemoryMapper {
-    type Mapped = MmapMut;
+    type Mapped = SaveData;
 
     fn open(&self, len: usize, battery_backed: bool) -> Result<Self::Mapped, utopia_winit::Error> {
         let result = if battery_backed {
@@ -22,13 +24,39 @@ impl utopia_winit::MemoryMapper for MemoryMapper {
                 .read(true)
                 .write(true)
                 .create(true)
-                .open(self.rom_path.with_extension("sav"))
+                .open(&self.save_path)
                 .and_then(|file| file.set_len(len as u64).map(|_| file))
                 .and_then(|file| unsafe { MmapOptions::new().map_mut(&file) })
         } else {
             MmapOptions::new().len(len).map_anon()
         };
 
-        result.map_err(|err| err.to_string().into())
+        result.map(SaveData).map_err(|err| err.to_string().into())
+    }
+}
+
+// Makes sure save data has been written out by the time the game is unloaded (e.g. when another
+// ROM is loaded in its place)
+pub struct SaveData(MmapMut);
+
+impl Deref for SaveData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl DerefMut for SaveData {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+impl Drop for SaveData {
+    fn drop(&mut self) {
+        if let Err(err) = self.0.flush() {
+            warn!("Failed to flush save data: {}", err);
+        }
     }
 }
@@ -0,0 +1,303 @@
+use super::loader::Loader;
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use utopia_winit::{AudioQueue, FrameScaler, InstanceOptions, SystemOptions, SystemType};
+
+type Writer = WavWriter<BufWriter<File>>;
+
+#[derive(Debug)]
+pub struct RenderOptions {
+    pub seconds: f64,
+    pub output_dir: Option<PathBuf>,
+    pub stems: bool,
+    pub mute: Vec<String>,
+    pub solo: Option<String>,
+    pub list_channels: bool,
+    pub screenshot: bool,
+}
+
+// Runs a ROM with no window and no input, writing its audio to WAV files (and optionally its
+// last frame to a PNG)
+pub fn render(
+    loader: &Loader,
+    rom_path: &Path,
+    entry: Option<&str>,
+    patches: Vec<PathBuf>,
+    render_options: RenderOptions,
+) -> Result<(), Box<dyn Error>> {
+    let options = loader.load_rom(rom_path, entry, patches)?;
+    let system_type = SystemType::detect(&options.rom_data, Some(&options.rom_path))?;
+
+    let system = utopia_winit::create(SystemOptions {
+        system_type,
+        bios_loader: options.bios_loader.as_ref(),
+        memory_mapper: &options.memory_mapper,
+        skip_boot: options.skip_boot,
+        audio_filter: options.audio_filter.unwrap_or_default(),
+        ntsc_filter: options.ntsc_filter.unwrap_or_default(),
+        nes_palette: options.nes_palette.clone().unwrap_or_default(),
+        gb_color_correction: options.gb_color_correction.unwrap_or_default(),
+        gb_frame_blend: options.gb_frame_blend.unwrap_or_default(),
+        gb_palette: options.gb_palette.clone().unwrap_or_default(),
+    })?;
+
+    let mut instance = system.create_instance(InstanceOptions {
+        rom_data: options.rom_data,
+        wgpu_context: None,
+        output_resolution: system.default_output_resolution(),
+    })?;
+
+    if instance.audio_queue().is_none() {
+        return Err(format!("Audio rendering is not supported for {}", system_type).into());
+    }
+
+    let channels = instance.audio_channels().to_vec();
+
+    if render_options.list_channels {
+        for name in channels {
+            println!("{}", name);
+        }
+
+        return Ok(());
+    }
+
+    let find_channel = |name: &str| {
+        channels
+            .iter()
+            .position(|channel| channel.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                format!(
+                    "No sound channel named '{}' (expected one of: {})",
+                    name,
+                    channels.join(", ")
+                )
+            })
+    };
+
+    for name in &render_options.mute {
+        instance.set_audio_channel_muted(find_channel(name)?, true);
+    }
+
+    if let Some(name) = &render_options.solo {
+        instance.solo_audio_channel(find_channel(name)?);
+    }
+
+    let output_dir = render_options
+        .output_dir
+        .or(options.capture_dir)
+        .unwrap_or_else(|| rom_path.parent().map(Path::to_path_buf).unwrap_or_default());
+
+    fs::create_dir_all(&output_dir)?;
+
+    let name = options
+        .rom_path
+        .file_stem()
+        .map_or("utopia".into(), |stem| stem.to_string_lossy().into_owned());
+
+    let sample_rate = instance.sample_rate();
+    let mut paths = vec![output_dir.join(format!("{}.wav", name))];
+
+    if render_options.stems {
+        if channels.is_empty() {
+            return Err(format!("Stems are not supported for {}", system_type).into());
+        }
+
+        instance.set_stems_enabled(true);
+
+        paths.extend(channels.iter().map(|channel| {
+            let channel = channel.to_lowercase().replace(' ', "-");
+            output_dir.join(format!("{}.{}.wav", name, channel))
+        }));
+    }
+
+    let mut writers = paths
+        .iter()
+        .map(|path| create_writer(path, sample_rate))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let frames = (render_options.seconds * system_type.frame_rate()).round() as u64;
+
+    // Every channel is rendered in the same pass, so the stems line up exactly with the mix
+    for _ in 0..frames {
+        instance.run_frame(&[]);
+
+        if let Some(queue) = instance.audio_queue() {
+            write_queue(&mut writers[0], queue)?;
+        }
+
+        if let Some(queues) = instance.stem_queues() {
+            for (writer, queue) in writers[1..].iter_mut().zip(queues) {
+                write_queue(writer, queue)?;
+            }
+        }
+    }
+
+    for writer in writers {
+        writer.finalize()?;
+    }
+
+    if render_options.screenshot {
+        let (pixels, size) = instance.pixels().ok_or("No frame available to capture")?;
+
+        let mut scaler = FrameScaler::new(options.scaler.unwrap_or_default());
+        let mut output = Vec::new();
+        scaler.apply(pixels, size, &mut output);
+
+        let path = output_dir.join(format!("{}.png", name));
+        utopia_winit::save_screenshot(&path, &output, scaler.output_size(size))?;
+        paths.push(path);
+    }
+
+    for path in paths {
+        println!("{}", path.display());
+    }
+
+    Ok(())
+}
+
+// Samples are written as floats, so nothing is lost to clipping or quantisation
+fn create_writer(path: &Path, sample_rate: u64) -> Result<Writer, Box<dyn Error>> {
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: sample_rate.try_into()?,
+        bits_per_sample: 32,
+        sample_format: SampleFormat::Float,
+    };
+
+    Ok(WavWriter::create(path, spec)?)
+}
+
+fn write_queue(writer: &mut Writer, queue: &mut AudioQueue) -> Result<(), Box<dyn Error>> {
+    for (left, right) in queue.drain(..) {
+        writer.write_sample(left)?;
+        writer.write_sample(right)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use utopia_winit::Scaler;
+
+    // Writes a ROM that does nothing but loop
+    fn write_rom(name: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("utopia-{}-{}", name, std::process::id()));
+        _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut rom_data = b"NES\x1a\x01\x01\x00\x00".to_vec();
+        rom_data.resize(16, 0);
+        let mut prg_rom = vec![0xea; 16384];
+        prg_rom[..3].copy_from_slice(&[0x4c, 0x00, 0x80]);
+        prg_rom[0x3ffa..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x00, 0x80]);
+        rom_data.extend(prg_rom);
+        rom_data.resize(rom_data.len() + 8192, 0);
+
+        let rom_path = dir.join("game.nes");
+        fs::write(&rom_path, rom_data).unwrap();
+
+        (dir, rom_path)
+    }
+
+    #[test]
+    fn stems_write_one_file_per_channel() {
+        let (dir, rom_path) = write_rom("render");
+
+        let loader = Loader {
+            config: Config::default(),
+            config_path: None,
+            bios_path: None,
+            skip_boot: None,
+            full_screen: None,
+            sync: None,
+        };
+
+        let render_options = RenderOptions {
+            seconds: 0.5,
+            output_dir: Some(dir.join("out")),
+            stems: true,
+            mute: vec!["noise".into()],
+            solo: None,
+            list_channels: false,
+            screenshot: false,
+        };
+
+        render(&loader, &rom_path, None, Vec::new(), render_options).unwrap();
+
+        let mut files: Vec<String> = fs::read_dir(dir.join("out"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+
+        files.sort();
+
+        assert_eq!(
+            files,
+            [
+                "game.dmc.wav",
+                "game.noise.wav",
+                "game.pulse-1.wav",
+                "game.pulse-2.wav",
+                "game.triangle.wav",
+                "game.wav",
+            ]
+        );
+
+        // The stems line up exactly with the mix
+        let len = hound::WavReader::open(dir.join("out/game.wav"))
+            .unwrap()
+            .len();
+
+        assert!(len > 0);
+
+        for file in &files {
+            let wav = hound::WavReader::open(dir.join("out").join(file)).unwrap();
+            assert_eq!(wav.len(), len, "{}", file);
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn screenshot_uses_configured_scaler() {
+        let (dir, rom_path) = write_rom("render-screenshot");
+
+        let mut config = Config::default();
+        config.global.scaler = Some(Scaler::Scale2x);
+
+        let loader = Loader {
+            config,
+            config_path: None,
+            bios_path: None,
+            skip_boot: None,
+            full_screen: None,
+            sync: None,
+        };
+
+        let render_options = RenderOptions {
+            seconds: 0.1,
+            output_dir: Some(dir.join("out")),
+            stems: false,
+            mute: Vec::new(),
+            solo: None,
+            list_channels: false,
+            screenshot: true,
+        };
+
+        render(&loader, &rom_path, None, Vec::new(), render_options).unwrap();
+
+        // Width and height from the PNG header
+        let png = fs::read(dir.join("out/game.png")).unwrap();
+        let width = u32::from_be_bytes(png[16..20].try_into().unwrap());
+        let height = u32::from_be_bytes(png[20..24].try_into().unwrap());
+        assert_eq!((width, height), (512, 448));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
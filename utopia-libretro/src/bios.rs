@@ -0,0 +1,27 @@
+use std::fs;
+use std::path::PathBuf;
+use tracing::warn;
+
+#[derive(Debug)]
+pub struct BiosLoader {
+    system_dir: PathBuf,
+}
+
+impl BiosLoader {
+    pub fn new(system_dir: PathBuf) -> Self {
+        Self { system_dir }
+    }
+}
+
+impl utopia::BiosLoader for BiosLoader {
+    fn load(&self, name: &str) -> Result<Vec<u8>, utopia::Error> {
+        let path = self.system_dir.join(format!("{}.bin", name));
+
+        let result = fs::read(&path).map_err(|err| {
+            warn!("Failed to load BIOS file '{}': {}", path.display(), err);
+            err
+        });
+
+        result.map_err(|err| err.to_string().into())
+    }
+}
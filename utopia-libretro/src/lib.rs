@@ -0,0 +1,549 @@
+// The safety requirements for the exported functions are those of the libretro API itself
+#![allow(clippy::missing_safety_doc)]
+
+use bios::BiosLoader;
+use ffi::*;
+use mmap::{MemoryMapper, SaveRam};
+use std::cell::{Cell, RefCell};
+use std::error::Error;
+use std::ffi::{c_char, c_uint, c_void, CStr};
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::slice;
+use std::sync::Arc;
+use tracing::error;
+use utopia::{
+    AudioFilter, GbColorCorrection, GbPalette, Instance, InstanceOptions, JoypadState, NesPalette,
+    NtscFilter, Size, SystemOptions, SystemType, WgpuContext,
+};
+
+mod bios;
+mod ffi;
+mod mmap;
+
+const MIN_MAX_SIZE: Size = Size::new(640, 480);
+
+// Maps libretro joypad IDs (B, Y, Select, Start, Up, Down, Left, Right, A, X, L, R, L2, R2, L3,
+// R3) to indices in JoypadState::buttons
+const BUTTON_MAP: [usize; 16] = [0, 2, 8, 9, 12, 13, 14, 15, 1, 3, 4, 5, 6, 7, 10, 11];
+
+#[derive(Copy, Clone, Default)]
+struct Callbacks {
+    environment: Option<RetroEnvironment>,
+    video_refresh: Option<RetroVideoRefresh>,
+    audio_sample_batch: Option<RetroAudioSampleBatch>,
+    input_poll: Option<RetroInputPoll>,
+    input_state: Option<RetroInputState>,
+}
+
+struct Game {
+    rom_path: PathBuf,
+    rom_data: Vec<u8>,
+    system_type: SystemType,
+    resolution: Size,
+    instance: Box<dyn Instance>,
+    save_ram: SaveRam,
+    joypad_state: JoypadState,
+    frame_buffer: Vec<u32>,
+    audio_buffer: Vec<i16>,
+}
+
+thread_local! {
+    static CALLBACKS: Cell<Callbacks> = Cell::default();
+    static GAME: RefCell<Option<Game>> = const { RefCell::new(None) };
+}
+
+impl Game {
+    fn load(rom_path: PathBuf, rom_data: Vec<u8>) -> Result<Self, Box<dyn Error>> {
+        let system_type = SystemType::detect(&rom_data, Some(&rom_path))?;
+
+        let rom_dir = rom_path.parent().unwrap_or(Path::new(".")).to_path_buf();
+
+        let system_dir = environment_path(RETRO_ENVIRONMENT_GET_SYSTEM_DIRECTORY)
+            .unwrap_or_else(|| rom_dir.clone());
+
+        let save_dir = environment_path(RETRO_ENVIRONMENT_GET_SAVE_DIRECTORY).unwrap_or(rom_dir);
+
+        let save_path = save_dir
+            .join(rom_path.file_name().unwrap_or_default())
+            .with_extension("sav");
+
+        let bios_loader = BiosLoader::new(system_dir);
+        let memory_mapper = MemoryMapper::new(save_path);
+
+        let system = utopia::create(SystemOptions {
+            system_type,
+            bios_loader: &bios_loader,
+            memory_mapper: &memory_mapper,
+            skip_boot: false,
+            audio_filter: AudioFilter::default(),
+            ntsc_filter: NtscFilter::default(),
+            nes_palette: NesPalette::default(),
+            gb_color_correction: GbColorCorrection::default(),
+            gb_frame_blend: false,
+            gb_palette: GbPalette::default(),
+        })?;
+
+        let resolution = system.default_output_resolution();
+
+        // Only the N64 renders via the GPU. Other systems hand us their pixels directly.
+        let wgpu_context = if system_type == SystemType::Nintendo64 {
+            Some(create_wgpu_context()?)
+        } else {
+            None
+        };
+
+        let instance = system.create_instance(InstanceOptions {
+            rom_data: rom_data.clone(),
+            wgpu_context,
+            output_resolution: resolution,
+        })?;
+
+        Ok(Self {
+            rom_path,
+            rom_data,
+            system_type,
+            resolution,
+            instance,
+            save_ram: memory_mapper.save_ram(),
+            joypad_state: JoypadState::default(),
+            frame_buffer: Vec::new(),
+            audio_buffer: Vec::new(),
+        })
+    }
+
+    fn run_frame(&mut self, callbacks: Callbacks) {
+        if let Some(input_poll) = callbacks.input_poll {
+            unsafe { input_poll() };
+        }
+
+        if let Some(input_state) = callbacks.input_state {
+            self.update_joypad(input_state);
+        }
+
+        // Only the first port is forwarded from the frontend for now
+        self.instance.run_frame(&[Some(self.joypad_state.clone())]);
+
+        if let Some(video_refresh) = callbacks.video_refresh {
+            self.refresh_video(video_refresh);
+        }
+
+        if let Some(audio_sample_batch) = callbacks.audio_sample_batch {
+            self.queue_audio(audio_sample_batch);
+        }
+    }
+
+    fn update_joypad(&mut self, input_state: RetroInputState) {
+        let JoypadState { buttons, axes } = &mut self.joypad_state;
+
+        for (id, index) in BUTTON_MAP.into_iter().enumerate() {
+            buttons[index] = unsafe { input_state(0, RETRO_DEVICE_JOYPAD, 0, id as c_uint) } != 0;
+        }
+
+        let analog = |index, id| axis(unsafe { input_state(0, RETRO_DEVICE_ANALOG, index, id) });
+
+        let left = RETRO_DEVICE_INDEX_ANALOG_LEFT;
+        let right = RETRO_DEVICE_INDEX_ANALOG_RIGHT;
+
+        // Libretro Y axes are positive downwards, whereas ours are positive upwards
+        axes[0] = analog(left, RETRO_DEVICE_ID_ANALOG_X);
+        axes[1] = analog(left, RETRO_DEVICE_ID_ANALOG_Y).saturating_neg();
+        axes[2] = analog(right, RETRO_DEVICE_ID_ANALOG_X);
+        axes[3] = analog(right, RETRO_DEVICE_ID_ANALOG_Y).saturating_neg();
+    }
+
+    fn refresh_video(&mut self, video_refresh: RetroVideoRefresh) {
+        let Some((pixels, size)) = self.instance.pixels() else {
+            // Passing NULL tells the frontend to duplicate the previous frame
+            unsafe {
+                video_refresh(
+                    ptr::null(),
+                    self.resolution.width,
+                    self.resolution.height,
+                    0,
+                )
+            };
+            return;
+        };
+
+        self.frame_buffer.clear();
+
+        self.frame_buffer.extend(
+            pixels.chunks_exact(4).map(|pixel| {
+                ((pixel[0] as u32) << 16) | ((pixel[1] as u32) << 8) | (pixel[2] as u32)
+            }),
+        );
+
+        unsafe {
+            video_refresh(
+                self.frame_buffer.as_ptr() as *const c_void,
+                size.width,
+                size.height,
+                size.width as usize * 4,
+            )
+        };
+    }
+
+    fn queue_audio(&mut self, audio_sample_batch: RetroAudioSampleBatch) {
+        let Some(queue) = self.instance.audio_queue() else {
+            return;
+        };
+
+        self.audio_buffer.clear();
+
+        for (left, right) in queue.drain(..) {
+            self.audio_buffer.push(sample(left));
+            self.audio_buffer.push(sample(right));
+        }
+
+        let mut remaining = &self.audio_buffer[..];
+
+        while !remaining.is_empty() {
+            let frames = unsafe { audio_sample_batch(remaining.as_ptr(), remaining.len() / 2) };
+
+            if frames == 0 {
+                break;
+            }
+
+            remaining = &remaining[(frames * 2).min(remaining.len())..];
+        }
+    }
+}
+
+fn callbacks() -> Callbacks {
+    CALLBACKS.with(|callbacks| callbacks.get())
+}
+
+fn update_callbacks(update: impl FnOnce(&mut Callbacks)) {
+    CALLBACKS.with(|callbacks| {
+        let mut value = callbacks.get();
+        update(&mut value);
+        callbacks.set(value);
+    })
+}
+
+fn environment_path(cmd: c_uint) -> Option<PathBuf> {
+    let environment = callbacks().environment?;
+    let mut dir: *const c_char = ptr::null();
+
+    let result = unsafe { environment(cmd, &mut dir as *mut *const c_char as *mut c_void) };
+
+    if !result || dir.is_null() {
+        return None;
+    }
+
+    let dir = unsafe { CStr::from_ptr(dir) };
+
+    Some(PathBuf::from(dir.to_string_lossy().into_owned()))
+}
+
+fn create_wgpu_context() -> Result<WgpuContext, Box<dyn Error>> {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+
+    let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+        power_preference: wgpu::PowerPreference::default(),
+        compatible_surface: None,
+        force_fallback_adapter: false,
+    }))
+    .ok_or("No suitable graphics adapter found")?;
+
+    let (device, queue) = pollster::block_on(adapter.request_device(
+        &wgpu::DeviceDescriptor {
+            features: wgpu::Features::empty(),
+            limits: wgpu::Limits::default(),
+            label: None,
+        },
+        None,
+    ))?;
+
+    Ok(WgpuContext {
+        device: Arc::new(device),
+        queue: Arc::new(queue),
+        output_format: wgpu::TextureFormat::Rgba8UnormSrgb,
+    })
+}
+
+fn axis(value: i16) -> i32 {
+    (value as i32) << 16
+}
+
+fn sample(value: f32) -> i16 {
+    (value.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+}
+
+#[no_mangle]
+pub extern "C" fn retro_api_version() -> c_uint {
+    RETRO_API_VERSION
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_environment(environment: RetroEnvironment) {
+    update_callbacks(|callbacks| callbacks.environment = Some(environment));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_video_refresh(video_refresh: RetroVideoRefresh) {
+    update_callbacks(|callbacks| callbacks.video_refresh = Some(video_refresh));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample(_audio_sample: RetroAudioSample) {
+    // Audio is always sent in batches
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_audio_sample_batch(audio_sample_batch: RetroAudioSampleBatch) {
+    update_callbacks(|callbacks| callbacks.audio_sample_batch = Some(audio_sample_batch));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_poll(input_poll: RetroInputPoll) {
+    update_callbacks(|callbacks| callbacks.input_poll = Some(input_poll));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_input_state(input_state: RetroInputState) {
+    update_callbacks(|callbacks| callbacks.input_state = Some(input_state));
+}
+
+#[no_mangle]
+pub extern "C" fn retro_init() {}
+
+#[no_mangle]
+pub extern "C" fn retro_deinit() {
+    GAME.with(|game| *game.borrow_mut() = None);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_info(info: *mut RetroSystemInfo) {
+    let Some(info) = info.as_mut() else {
+        return;
+    };
+
+    *info = RetroSystemInfo {
+        library_name: concat!("Utopia", "\0").as_ptr() as *const c_char,
+        library_version: concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr() as *const c_char,
+        valid_extensions: concat!("gb|gbc|gba|n64|v64|z64|nes|sfc|smc|sms", "\0").as_ptr()
+            as *const c_char,
+        need_fullpath: false,
+        block_extract: false,
+    };
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_get_system_av_info(info: *mut RetroSystemAvInfo) {
+    let Some(info) = info.as_mut() else {
+        return;
+    };
+
+    GAME.with(|game| {
+        let game = game.borrow();
+
+        let Some(game) = game.as_ref() else {
+            return;
+        };
+
+        let Size { width, height } = game.resolution;
+
+        *info = RetroSystemAvInfo {
+            geometry: RetroGameGeometry {
+                base_width: width,
+                base_height: height,
+                max_width: width.max(MIN_MAX_SIZE.width),
+                max_height: height.max(MIN_MAX_SIZE.height),
+                aspect_ratio: 0.0,
+            },
+            timing: RetroSystemTiming {
+                fps: game.system_type.frame_rate(),
+                sample_rate: game.instance.sample_rate() as f64,
+            },
+        };
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn retro_set_controller_port_device(_port: c_uint, _device: c_uint) {}
+
+#[no_mangle]
+pub extern "C" fn retro_reset() {
+    GAME.with(|game| {
+        let mut game = game.borrow_mut();
+
+        let Some(Game {
+            rom_path, rom_data, ..
+        }) = game.take()
+        else {
+            return;
+        };
+
+        // The previous instance has been dropped at this point, so any save data will have
+        // been flushed before it gets mapped again
+        match Game::load(rom_path, rom_data) {
+            Ok(new_game) => *game = Some(new_game),
+            Err(err) => error!("Failed to reset game: {}", err),
+        }
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn retro_run() {
+    let callbacks = callbacks();
+
+    GAME.with(|game| {
+        if let Some(game) = game.borrow_mut().as_mut() {
+            game.run_frame(callbacks);
+        }
+    });
+}
+
+// The size of a state never changes once a game is loaded, so the easiest way to find it is to
+// take one. Systems without save state support report a size of zero.
+#[no_mangle]
+pub extern "C" fn retro_serialize_size() -> usize {
+    save_state().map_or(0, |state| state.len())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_serialize(data: *mut c_void, size: usize) -> bool {
+    let Some(state) = save_state() else {
+        return false;
+    };
+
+    if data.is_null() || size < state.len() {
+        error!("Save state buffer is too small");
+        return false;
+    }
+
+    let buffer = slice::from_raw_parts_mut(data as *mut u8, size);
+    let (head, tail) = buffer.split_at_mut(state.len());
+    head.copy_from_slice(&state);
+    tail.fill(0);
+    true
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_unserialize(data: *const c_void, size: usize) -> bool {
+    if data.is_null() {
+        return false;
+    }
+
+    let state = slice::from_raw_parts(data as *const u8, size);
+
+    GAME.with(|game| {
+        let mut game = game.borrow_mut();
+
+        let Some(game) = game.as_mut() else {
+            return false;
+        };
+
+        match game.instance.load_state(state) {
+            Ok(()) => true,
+            Err(err) => {
+                error!("Failed to load save state: {}", err);
+                false
+            }
+        }
+    })
+}
+
+fn save_state() -> Option<Vec<u8>> {
+    GAME.with(|game| {
+        game.borrow_mut()
+            .as_mut()
+            .and_then(|game| game.instance.save_state())
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_reset() {}
+
+#[no_mangle]
+pub extern "C" fn retro_cheat_set(_index: c_uint, _enabled: bool, _code: *const c_char) {}
+
+#[no_mangle]
+pub unsafe extern "C" fn retro_load_game(info: *const RetroGameInfo) -> bool {
+    let Some(info) = info.as_ref() else {
+        return false;
+    };
+
+    if info.path.is_null() {
+        error!("A ROM path is required in order to determine the system type");
+        return false;
+    }
+
+    let rom_path = PathBuf::from(CStr::from_ptr(info.path).to_string_lossy().into_owned());
+
+    let rom_data = if info.data.is_null() {
+        match std::fs::read(&rom_path) {
+            Ok(rom_data) => rom_data,
+            Err(err) => {
+                error!("Failed to read '{}': {}", rom_path.display(), err);
+                return false;
+            }
+        }
+    } else {
+        slice::from_raw_parts(info.data as *const u8, info.size).to_vec()
+    };
+
+    if let Some(environment) = callbacks().environment {
+        let mut pixel_format = RETRO_PIXEL_FORMAT_XRGB8888;
+
+        let result = environment(
+            RETRO_ENVIRONMENT_SET_PIXEL_FORMAT,
+            &mut pixel_format as *mut c_uint as *mut c_void,
+        );
+
+        if !result {
+            error!("Frontend does not support XRGB8888 pixel format");
+            return false;
+        }
+    }
+
+    match Game::load(rom_path, rom_data) {
+        Ok(game) => {
+            GAME.with(|cell| *cell.borrow_mut() = Some(game));
+            true
+        }
+        Err(err) => {
+            error!("Failed to load game: {}", err);
+            false
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn retro_load_game_special(
+    _game_type: c_uint,
+    _info: *const RetroGameInfo,
+    _num_info: usize,
+) -> bool {
+    false
+}
+
+#[no_mangle]
+pub extern "C" fn retro_unload_game() {
+    GAME.with(|game| *game.borrow_mut() = None);
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_region() -> c_uint {
+    RETRO_REGION_NTSC
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_data(id: c_uint) -> *mut c_void {
+    save_ram(id).map_or(ptr::null_mut(), |(data, _)| data as *mut c_void)
+}
+
+#[no_mangle]
+pub extern "C" fn retro_get_memory_size(id: c_uint) -> usize {
+    save_ram(id).map_or(0, |(_, len)| len)
+}
+
+fn save_ram(id: c_uint) -> Option<(*mut u8, usize)> {
+    if id != RETRO_MEMORY_SAVE_RAM {
+        return None;
+    }
+
+    GAME.with(|game| game.borrow().as_ref().and_then(|game| game.save_ram.get()))
+}
@@ -0,0 +1,55 @@
+use memmap2::{MmapMut, MmapOptions};
+use std::cell::Cell;
+use std::fs::OpenOptions;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+pub type SaveRam = Rc<Cell<Option<(*mut u8, usize)>>>;
+
+#[derive(Debug)]
+pub struct MemoryMapper {
+    save_path: PathBuf,
+    save_ram: SaveRam,
+}
+
+impl MemoryMapper {
+    pub fn new(save_path: PathBuf) -> Self {
+        Self {
+            save_path,
+            save_ram: Default::default(),
+        }
+    }
+
+    pub fn save_ram(&self) -> SaveRam {
+        self.save_ram.clone()
+    }
+}
+
+impl utopia::MemoryMapper for MemoryMapper {
+    type Mapped = MmapMut;
+
+    fn open(&self, len: usize, battery_backed: bool) -> Result<Self::Mapped, utopia::Error> {
+        let result = if battery_backed {
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&self.save_path)
+                .and_then(|file| file.set_len(len as u64).map(|_| file))
+                .and_then(|file| unsafe { MmapOptions::new().map_mut(&file) })
+        } else {
+            MmapOptions::new().len(len).map_anon()
+        };
+
+        let mut mapped = result.map_err(|err| utopia::Error(err.to_string()))?;
+
+        // The mapping stays at the same address for as long as the instance owns it, so the
+        // frontend can be given direct access to it via 'retro_get_memory_data'
+        if battery_backed {
+            self.save_ram.set(Some((mapped.as_mut_ptr(), mapped.len())));
+        }
+
+        Ok(mapped)
+    }
+}
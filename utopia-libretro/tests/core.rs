@@ -0,0 +1,219 @@
+// Minimal libretro frontend that loads the built core as a shared library and runs a ROM headlessly
+
+use libloading::{Library, Symbol};
+use std::error::Error;
+use std::ffi::{c_char, c_uint, c_void, CStr, CString};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+const FRAMES: usize = 60;
+
+#[repr(C)]
+struct RetroSystemInfo {
+    library_name: *const c_char,
+    library_version: *const c_char,
+    valid_extensions: *const c_char,
+    need_fullpath: bool,
+    block_extract: bool,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct RetroSystemAvInfo {
+    base_width: c_uint,
+    base_height: c_uint,
+    max_width: c_uint,
+    max_height: c_uint,
+    aspect_ratio: f32,
+    fps: f64,
+    sample_rate: f64,
+}
+
+#[repr(C)]
+struct RetroGameInfo {
+    path: *const c_char,
+    data: *const c_void,
+    size: usize,
+    meta: *const c_char,
+}
+
+static VIDEO_FRAMES: AtomicUsize = AtomicUsize::new(0);
+static AUDIO_FRAMES: AtomicUsize = AtomicUsize::new(0);
+static LAST_WIDTH: AtomicUsize = AtomicUsize::new(0);
+static LAST_HEIGHT: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" fn environment(cmd: c_uint, _data: *mut c_void) -> bool {
+    // Accept the pixel format (RETRO_ENVIRONMENT_SET_PIXEL_FORMAT), but leave the directories
+    // for the core to work out
+    cmd == 10
+}
+
+extern "C" fn video_refresh(data: *const c_void, width: c_uint, height: c_uint, _pitch: usize) {
+    if !data.is_null() {
+        VIDEO_FRAMES.fetch_add(1, Ordering::Relaxed);
+        LAST_WIDTH.store(width as usize, Ordering::Relaxed);
+        LAST_HEIGHT.store(height as usize, Ordering::Relaxed);
+    }
+}
+
+extern "C" fn audio_sample(_left: i16, _right: i16) {}
+
+extern "C" fn audio_sample_batch(_data: *const i16, frames: usize) -> usize {
+    AUDIO_FRAMES.fetch_add(frames, Ordering::Relaxed);
+    frames
+}
+
+extern "C" fn input_poll() {}
+
+extern "C" fn input_state(_port: c_uint, _device: c_uint, _index: c_uint, _id: c_uint) -> i16 {
+    0
+}
+
+// Cargo places the core next to the directory containing the test executable
+fn core_path() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    let target_dir = exe.parent().unwrap().parent().unwrap();
+    target_dir.join(libloading::library_filename("utopia_libretro"))
+}
+
+// An NROM cartridge whose reset vector points at an infinite loop
+fn nes_rom() -> Vec<u8> {
+    let mut rom = b"NES\x1a\x01\x01\x00\x00".to_vec();
+    rom.resize(16, 0);
+
+    let mut prg_rom = vec![0xea; 0x4000];
+    prg_rom[0..3].copy_from_slice(&[0x4c, 0x00, 0x80]);
+    prg_rom[0x3ffa..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x00, 0x80]);
+
+    rom.extend(prg_rom);
+    rom.extend(vec![0; 0x2000]);
+    rom
+}
+
+#[test]
+fn runs_nes_rom() -> Result<(), Box<dyn Error>> {
+    let rom_data = nes_rom();
+    let rom_path = std::env::temp_dir().join("utopia-libretro-test.nes");
+    std::fs::write(&rom_path, &rom_data)?;
+    let rom_path = CString::new(rom_path.to_string_lossy().into_owned())?;
+
+    unsafe {
+        let core = Library::new(core_path())?;
+
+        let api_version: Symbol<extern "C" fn() -> c_uint> = core.get(b"retro_api_version")?;
+        assert_eq!(api_version(), 1);
+
+        let get_system_info: Symbol<unsafe extern "C" fn(*mut RetroSystemInfo)> =
+            core.get(b"retro_get_system_info")?;
+
+        let mut system_info = RetroSystemInfo {
+            library_name: std::ptr::null(),
+            library_version: std::ptr::null(),
+            valid_extensions: std::ptr::null(),
+            need_fullpath: false,
+            block_extract: false,
+        };
+
+        get_system_info(&mut system_info);
+
+        assert_eq!(
+            CStr::from_ptr(system_info.library_name).to_string_lossy(),
+            "Utopia"
+        );
+
+        let extensions = CStr::from_ptr(system_info.valid_extensions).to_string_lossy();
+        assert!(extensions.split('|').any(|extension| extension == "nes"));
+
+        core.get::<extern "C" fn(extern "C" fn(c_uint, *mut c_void) -> bool)>(
+            b"retro_set_environment",
+        )?(environment);
+
+        core.get::<extern "C" fn(extern "C" fn(*const c_void, c_uint, c_uint, usize))>(
+            b"retro_set_video_refresh",
+        )?(video_refresh);
+
+        core.get::<extern "C" fn(extern "C" fn(i16, i16))>(b"retro_set_audio_sample")?(
+            audio_sample,
+        );
+
+        core.get::<extern "C" fn(extern "C" fn(*const i16, usize) -> usize)>(
+            b"retro_set_audio_sample_batch",
+        )?(audio_sample_batch);
+
+        core.get::<extern "C" fn(extern "C" fn())>(b"retro_set_input_poll")?(input_poll);
+
+        core.get::<extern "C" fn(extern "C" fn(c_uint, c_uint, c_uint, c_uint) -> i16)>(
+            b"retro_set_input_state",
+        )?(input_state);
+
+        core.get::<extern "C" fn()>(b"retro_init")?();
+
+        let game_info = RetroGameInfo {
+            path: rom_path.as_ptr(),
+            data: rom_data.as_ptr() as *const c_void,
+            size: rom_data.len(),
+            meta: std::ptr::null(),
+        };
+
+        let load_game: Symbol<unsafe extern "C" fn(*const RetroGameInfo) -> bool> =
+            core.get(b"retro_load_game")?;
+
+        assert!(load_game(&game_info), "Failed to load game");
+
+        let mut av_info = RetroSystemAvInfo::default();
+
+        core.get::<unsafe extern "C" fn(*mut RetroSystemAvInfo)>(b"retro_get_system_av_info")?(
+            &mut av_info,
+        );
+
+        assert_eq!((av_info.base_width, av_info.base_height), (256, 224));
+        assert!(av_info.fps > 60.0 && av_info.fps < 60.1);
+
+        let run: Symbol<extern "C" fn()> = core.get(b"retro_run")?;
+
+        for _ in 0..FRAMES {
+            run();
+        }
+
+        let memory_size: Symbol<extern "C" fn(c_uint) -> usize> =
+            core.get(b"retro_get_memory_size")?;
+
+        assert_eq!(VIDEO_FRAMES.load(Ordering::Relaxed), FRAMES);
+        assert_eq!(LAST_WIDTH.load(Ordering::Relaxed), 256);
+        assert_eq!(LAST_HEIGHT.load(Ordering::Relaxed), 224);
+        assert!(AUDIO_FRAMES.load(Ordering::Relaxed) > 0);
+        assert_eq!(memory_size(0), 0);
+
+        let serialize_size: Symbol<extern "C" fn() -> usize> = core.get(b"retro_serialize_size")?;
+
+        let serialize: Symbol<unsafe extern "C" fn(*mut c_void, usize) -> bool> =
+            core.get(b"retro_serialize")?;
+
+        let unserialize: Symbol<unsafe extern "C" fn(*const c_void, usize) -> bool> =
+            core.get(b"retro_unserialize")?;
+
+        let size = serialize_size();
+        assert!(size > 0);
+
+        let mut saved = vec![0u8; size];
+        assert!(serialize(saved.as_mut_ptr() as *mut c_void, size));
+        assert!(!serialize(saved.as_mut_ptr() as *mut c_void, size - 1));
+
+        // Running on and then loading the state should take the game back to where it was
+        run();
+        let mut current = vec![0u8; size];
+        assert!(serialize(current.as_mut_ptr() as *mut c_void, size));
+        assert_ne!(current, saved);
+
+        assert!(unserialize(saved.as_ptr() as *const c_void, size));
+        assert!(serialize(current.as_mut_ptr() as *mut c_void, size));
+        assert_eq!(current, saved);
+
+        assert!(!unserialize(saved.as_ptr() as *const c_void, size - 1));
+
+        core.get::<extern "C" fn()>(b"retro_unload_game")?();
+        core.get::<extern "C" fn()>(b"retro_deinit")?();
+    }
+
+    Ok(())
+}
@@ -1,5 +1,6 @@
-use std::rc::Rc;
-use utopia_winit::{App, DefaultMemoryMapper, ResetOptions};
+use js_sys::{Array, Uint8Array};
+use std::sync::Arc;
+use utopia_winit::{apply_patch, App, DefaultMemoryMapper, InputConfig, ResetOptions};
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsError;
 use web_sys::HtmlCanvasElement;
@@ -36,18 +37,48 @@ impl Utopia {
         rom_path: &str,
         rom_data: Vec<u8>,
         bios_data: Option<Vec<u8>>,
+        patches: Option<Array>,
     ) -> Result<(), JsError> {
         let _ = canvas;
 
+        let mut rom_data = rom_data;
+
+        for patch in patches.iter().flat_map(|patches| patches.iter()) {
+            let patch_data: Uint8Array = patch
+                .dyn_into()
+                .map_err(|_| JsError::new("Patches must be Uint8Arrays"))?;
+
+            rom_data = apply_patch(rom_data, &patch_data.to_vec())
+                .map_err(|err| JsError::new(&err.to_string()))?;
+        }
+
         self.app
             .reset(ResetOptions {
-                bios_loader: Rc::new(BiosLoader(bios_data)),
+                bios_loader: Arc::new(BiosLoader(bios_data)),
                 memory_mapper: DefaultMemoryMapper,
                 rom_path: rom_path.into(),
                 rom_data,
+                patches: Vec::new(),
                 skip_boot: true,
                 full_screen: false,
                 sync: None,
+                scale: None,
+                audio_latency: None,
+                fast_forward_speed: None,
+                fast_forward_audio: None,
+                audio_filter: None,
+                ntsc_filter: None,
+                nes_palette: None,
+                gb_color_correction: None,
+                gb_frame_blend: None,
+                gb_palette: None,
+                scaler: None,
+                capture_dir: None,
+                input: InputConfig::default(),
+                input_store: None,
+                rom_loader: None,
+                achievements: None,
+                cheats: None,
                 #[cfg(target_arch = "wasm32")]
                 canvas,
             })
@@ -1,10 +1,16 @@
+use super::stretch::TimeStretch;
+use super::FastForwardAudio;
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::{
-    BufferSize, OutputCallbackInfo, PlayStreamError, Sample, SampleRate, Stream, StreamConfig,
+    BufferSize, Device, FromSample, OutputCallbackInfo, PlayStreamError, SampleFormat, SizedSample,
+    Stream, StreamConfig, SupportedStreamConfig,
+};
+use rtrb::{Consumer, Producer, RingBuffer};
+use rubato::{
+    Resampler, SincFixedIn, SincInterpolationParameters, SincInterpolationType, WindowFunction,
 };
 use std::error::Error;
-use std::sync::{Arc, Mutex};
-use tracing::warn;
+use tracing::{info, warn};
 use utopia::AudioQueue;
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -13,90 +19,393 @@ use std::time::{Duration, Instant};
 #[cfg(target_arch = "wasm32")]
 use web_time::{Duration, Instant};
 
+// Number of frames passed to the resampler at a time
+const CHUNK_SIZE: usize = 128;
+
+// Dynamic rate control never changes the pitch by more than this
+const MAX_RATE_ADJUSTMENT: f64 = 0.005;
+
+// Amount of audio to keep queued up (in milliseconds) when no latency has been configured
+const DEFAULT_TARGET_LATENCY: u64 = 50;
+
+// Rather than holding the last sample when the buffer runs dry (which can leave a DC offset),
+// the output fades back towards silence
+const UNDERRUN_DECAY: f32 = 0.995;
+
+// An output device, along with the format it would prefer to be given
+pub struct AudioOutput {
+    device: Device,
+    config: SupportedStreamConfig,
+}
+
 pub struct AudioController {
-    stream: Stream,
-    send_queue: Arc<Mutex<AudioQueue>>,
+    stream: Option<Stream>,
+}
+
+pub struct AudioSender {
+    output: Option<ResampledOutput>,
     total_samples: u64,
     sample_rate: u64,
     start_time: Instant,
     sync_time: Instant,
+    speed: f64,
+    fast_forward_audio: FastForwardAudio,
+    time_stretch: TimeStretch,
+    stretched: Vec<(f32, f32)>,
 }
 
-impl AudioController {
-    pub fn new(sample_rate: u64) -> Result<Self, Box<dyn Error>> {
+struct ResampledOutput {
+    producer: Producer<(f32, f32)>,
+    resampler: SincFixedIn<f32>,
+    input: [Vec<f32>; 2],
+    output: Vec<Vec<f32>>,
+    capacity: usize,
+    target_fill: usize,
+}
+
+impl AudioOutput {
+    // Returns 'None' if there is no usable output device
+    pub fn open() -> Option<Self> {
         let host = cpal::default_host();
-        let device = host.default_output_device().unwrap();
 
-        let config = StreamConfig {
-            channels: 2,
-            sample_rate: SampleRate(sample_rate.try_into()?),
-            buffer_size: BufferSize::Default,
+        let Some(device) = host.default_output_device() else {
+            warn!("No audio output device found");
+            return None;
         };
 
-        let send_queue = Arc::new(Mutex::new(AudioQueue::new()));
-        let receive_queue = send_queue.clone();
-
-        let stream = device.build_output_stream(
-            &config,
-            move |output: &mut [f32], _: &OutputCallbackInfo| {
-                let mut input = receive_queue.lock().unwrap();
-                let mut prev_sample = (Sample::EQUILIBRIUM, Sample::EQUILIBRIUM);
-
-                for sample_out in output.chunks_exact_mut(2) {
-                    if let Some(sample_in) = input.pop_front() {
-                        sample_out[0] = sample_in.0;
-                        sample_out[1] = sample_in.1;
-                        prev_sample = sample_in;
-                    } else {
-                        sample_out[0] = prev_sample.0;
-                        sample_out[1] = prev_sample.1;
-                    }
-                }
-            },
-            move |err| warn!("{}", err),
-            None,
-        )?;
+        match device.default_output_config() {
+            Ok(config) => Some(Self { device, config }),
+            Err(err) => {
+                warn!("Failed to query audio output device: {}", err);
+                None
+            }
+        }
+    }
+}
 
+impl AudioController {
+    pub fn new(
+        output: Option<AudioOutput>,
+        sample_rate: u64,
+        latency: Option<u32>,
+        fast_forward_audio: FastForwardAudio,
+    ) -> (Self, AudioSender) {
         let start_time = Instant::now();
 
-        Ok(Self {
-            stream,
-            send_queue,
+        let mut sender = AudioSender {
+            output: None,
             total_samples: 0,
             sample_rate,
-            start_time: Instant::now(),
-            sync_time: calculate_sync_time(start_time, 0, sample_rate),
-        })
+            start_time,
+            sync_time: calculate_sync_time(start_time, 0, sample_rate, 1.0),
+            speed: 1.0,
+            fast_forward_audio,
+            time_stretch: TimeStretch::new(),
+            stretched: Vec::new(),
+        };
+
+        // Without an output stream, samples are still counted (so timing is unaffected) but
+        // otherwise discarded
+        let stream = output.and_then(|output| match open_stream(output, sample_rate, latency) {
+            Ok((stream, resampled_output)) => {
+                sender.output = Some(resampled_output);
+                Some(stream)
+            }
+            Err(err) => {
+                warn!("Failed to open audio output: {}", err);
+                None
+            }
+        });
+
+        (Self { stream }, sender)
     }
 
+    pub fn resume(&mut self) -> Result<(), PlayStreamError> {
+        match &self.stream {
+            Some(stream) => stream.play(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl AudioSender {
     pub fn sync_time(&self) -> Instant {
         self.sync_time
     }
 
     pub fn resync(&mut self) {
-        self.send_queue.lock().unwrap().clear();
         self.total_samples = 0;
         self.start_time = Instant::now();
-        self.sync_time = calculate_sync_time(self.start_time, self.total_samples, self.sample_rate);
+        self.update_sync_time();
     }
 
-    pub fn resume(&mut self) -> Result<(), PlayStreamError> {
-        self.stream.play()?;
+    pub fn set_speed(&mut self, speed: f64) {
+        self.speed = speed;
+        self.time_stretch = TimeStretch::new();
         self.resync();
-        Ok(())
     }
 
     pub fn queue_samples(&mut self, source_queue: &mut AudioQueue) {
         self.total_samples += source_queue.len() as u64;
 
-        self.send_queue.lock().unwrap().append(source_queue);
+        if let Some(output) = &mut self.output {
+            if self.speed == 1.0 {
+                output.push(source_queue.iter().copied());
+            } else if self.speed < 1.0 || self.fast_forward_audio == FastForwardAudio::Stretch {
+                // Audio is time-stretched to match the speed of the emulator, keeping its pitch
+                self.time_stretch.process(
+                    self.speed,
+                    source_queue.iter().copied(),
+                    &mut self.stretched,
+                );
+
+                output.push(self.stretched.drain(..));
+            }
+        }
+
         source_queue.clear();
 
-        self.sync_time = calculate_sync_time(self.start_time, self.total_samples, self.sample_rate);
+        self.update_sync_time();
+    }
+
+    fn update_sync_time(&mut self) {
+        self.sync_time = calculate_sync_time(
+            self.start_time,
+            self.total_samples,
+            self.sample_rate,
+            self.speed,
+        );
+    }
+}
+
+impl ResampledOutput {
+    fn new(
+        producer: Producer<(f32, f32)>,
+        capacity: usize,
+        input_rate: u64,
+        output_rate: u64,
+        target_fill: usize,
+    ) -> Result<Self, Box<dyn Error>> {
+        let parameters = SincInterpolationParameters {
+            sinc_len: 128,
+            f_cutoff: 0.95,
+            oversampling_factor: 128,
+            interpolation: SincInterpolationType::Cubic,
+            window: WindowFunction::BlackmanHarris2,
+        };
+
+        let resampler = SincFixedIn::new(
+            output_rate as f64 / input_rate as f64,
+            1.0 + MAX_RATE_ADJUSTMENT * 2.0,
+            parameters,
+            CHUNK_SIZE,
+            2,
+        )?;
+
+        Ok(Self {
+            producer,
+            output: resampler.output_buffer_allocate(true),
+            resampler,
+            input: [Vec::new(), Vec::new()],
+            capacity,
+            target_fill: target_fill.clamp(1, capacity),
+        })
+    }
+
+    fn push(&mut self, samples: impl Iterator<Item = (f32, f32)>) {
+        for (left, right) in samples {
+            self.input[0].push(left);
+            self.input[1].push(right);
+        }
+
+        while self.input[0].len() >= CHUNK_SIZE {
+            self.adjust_rate();
+
+            let result = self.resampler.process_into_buffer(
+                &[&self.input[0][..CHUNK_SIZE], &self.input[1][..CHUNK_SIZE]],
+                &mut self.output,
+                None,
+            );
+
+            for channel in &mut self.input {
+                channel.drain(..CHUNK_SIZE);
+            }
+
+            let output_len = match result {
+                Ok((_, output_len)) => output_len,
+                Err(err) => {
+                    warn!("Audio resampling failed: {}", err);
+                    continue;
+                }
+            };
+
+            for index in 0..output_len {
+                let sample = (self.output[0][index], self.output[1][index]);
+
+                if self.producer.push(sample).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    // Nudges the resampling ratio so that the amount of queued audio stays close to the target.
+    // This stops the emulator and output device clocks from slowly drifting apart.
+    fn adjust_rate(&mut self) {
+        let fill = self.capacity - self.producer.slots();
+        let error = (self.target_fill as f64 - fill as f64) / self.target_fill as f64;
+        let adjustment = error.clamp(-1.0, 1.0) * MAX_RATE_ADJUSTMENT;
+
+        if let Err(err) = self
+            .resampler
+            .set_resample_ratio_relative(1.0 + adjustment, true)
+        {
+            warn!("Failed to adjust audio rate: {}", err);
+        }
     }
 }
 
-fn calculate_sync_time(start_time: Instant, total_samples: u64, sample_rate: u64) -> Instant {
-    let expected_duration = (total_samples * 1000) / sample_rate;
-    start_time + Duration::from_millis(expected_duration)
+fn open_stream(
+    output: AudioOutput,
+    sample_rate: u64,
+    latency: Option<u32>,
+) -> Result<(Stream, ResampledOutput), Box<dyn Error>> {
+    let AudioOutput { device, config } = output;
+
+    let output_rate = config.sample_rate().0 as u64;
+
+    let stream_config = StreamConfig {
+        channels: config.channels(),
+        sample_rate: config.sample_rate(),
+        // Latency is given in milliseconds
+        buffer_size: latency.map_or(BufferSize::Default, |latency| {
+            BufferSize::Fixed((output_rate * latency as u64 / 1000) as u32)
+        }),
+    };
+
+    // One second of audio is far more than should ever be buffered
+    let capacity = output_rate as usize;
+    let (producer, consumer) = RingBuffer::new(capacity);
+
+    let stream = match config.sample_format() {
+        SampleFormat::I8 => build_stream::<i8>(&device, &stream_config, consumer),
+        SampleFormat::I16 => build_stream::<i16>(&device, &stream_config, consumer),
+        SampleFormat::I32 => build_stream::<i32>(&device, &stream_config, consumer),
+        SampleFormat::U8 => build_stream::<u8>(&device, &stream_config, consumer),
+        SampleFormat::U16 => build_stream::<u16>(&device, &stream_config, consumer),
+        SampleFormat::U32 => build_stream::<u32>(&device, &stream_config, consumer),
+        SampleFormat::F32 => build_stream::<f32>(&device, &stream_config, consumer),
+        SampleFormat::F64 => build_stream::<f64>(&device, &stream_config, consumer),
+        sample_format => return Err(format!("Unsupported sample format: {}", sample_format).into()),
+    }?;
+
+    info!(
+        "Audio output: {} Hz, {} channel(s), {} (resampled from {} Hz)",
+        output_rate,
+        config.channels(),
+        config.sample_format(),
+        sample_rate
+    );
+
+    let target_latency = latency.map_or(DEFAULT_TARGET_LATENCY, |latency| latency as u64 * 2);
+
+    let resampled_output = ResampledOutput::new(
+        producer,
+        capacity,
+        sample_rate,
+        output_rate,
+        (output_rate * target_latency / 1000) as usize,
+    )?;
+
+    Ok((stream, resampled_output))
+}
+
+fn build_stream<T: SizedSample + FromSample<f32>>(
+    device: &Device,
+    config: &StreamConfig,
+    mut consumer: Consumer<(f32, f32)>,
+) -> Result<Stream, cpal::BuildStreamError> {
+    let channels = config.channels as usize;
+    let mut prev_sample = (0.0, 0.0);
+
+    device.build_output_stream(
+        config,
+        move |output: &mut [T], _: &OutputCallbackInfo| {
+            for frame in output.chunks_exact_mut(channels) {
+                prev_sample = consumer.pop().unwrap_or((
+                    prev_sample.0 * UNDERRUN_DECAY,
+                    prev_sample.1 * UNDERRUN_DECAY,
+                ));
+
+                let (left, right) = prev_sample;
+
+                // Mono devices get a mix of both channels. Any channels beyond the first two
+                // are left silent.
+                if channels == 1 {
+                    frame[0] = T::from_sample((left + right) / 2.0);
+                } else {
+                    frame[0] = T::from_sample(left);
+                    frame[1] = T::from_sample(right);
+
+                    for sample in &mut frame[2..] {
+                        *sample = T::EQUILIBRIUM;
+                    }
+                }
+            }
+        },
+        move |err| warn!("{}", err),
+        None,
+    )
+}
+
+fn calculate_sync_time(
+    start_time: Instant,
+    total_samples: u64,
+    sample_rate: u64,
+    speed: f64,
+) -> Instant {
+    let expected_duration = total_samples as f64 / (sample_rate as f64 * speed);
+    start_time + Duration::from_secs_f64(expected_duration)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resample(fill: usize, target_fill: usize) -> usize {
+        let (mut producer, mut consumer) = RingBuffer::new(96000);
+
+        for _ in 0..fill {
+            producer.push((0.0, 0.0)).unwrap();
+        }
+
+        let mut output = ResampledOutput::new(producer, 96000, 32000, 48000, target_fill).unwrap();
+
+        output.push((0..32000).map(|index| {
+            let sample = (index as f32 * 0.05).sin();
+            (sample, sample)
+        }));
+
+        let mut len = 0;
+
+        while consumer.pop().is_ok() {
+            len += 1;
+        }
+
+        len - fill
+    }
+
+    #[test]
+    fn rate_control() {
+        // Allow for the samples still held inside the resampler
+        let in_range = |len: usize, min: f64, max: f64| {
+            len as f64 >= 48000.0 * min - 256.0 && len as f64 <= 48000.0 * max
+        };
+
+        // Output is slightly stretched when running low, and slightly squashed when running high
+        let low = resample(0, 48000);
+        let high = resample(96000 - 48000, 1);
+        assert!(in_range(low, 1.004, 1.005), "{}", low);
+        assert!(in_range(high, 0.995, 0.996), "{}", high);
+    }
 }
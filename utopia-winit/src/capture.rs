@@ -0,0 +1,257 @@
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use tracing::info;
+use utopia::{AudioQueue, Size};
+
+// Finds the first '<stem>-NNN' name for which none of the given extensions are taken yet
+pub fn next_path(dir: &Path, stem: &str, extensions: &[&str]) -> Result<PathBuf, Box<dyn Error>> {
+    fs::create_dir_all(dir)?;
+
+    for index in 1..=9999 {
+        let path = dir.join(format!("{}-{:03}", stem, index));
+
+        if extensions
+            .iter()
+            .all(|extension| !add_extension(&path, extension).exists())
+        {
+            return Ok(path);
+        }
+    }
+
+    Err(format!("No free capture file names left in '{}'", dir.display()).into())
+}
+
+// Unlike 'Path::with_extension', this leaves any dots that are already in the name alone
+pub fn add_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    path.into()
+}
+
+pub fn save_screenshot(path: &Path, pixels: &[u8], size: Size) -> Result<(), Box<dyn Error>> {
+    let mut encoder =
+        png::Encoder::new(BufWriter::new(File::create(path)?), size.width, size.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    writer.finish()?;
+
+    info!("Screenshot saved to '{}'", path.display());
+
+    Ok(())
+}
+
+// Writes emulated frames to a y4m video and emulated audio to a WAV file. As y4m has no way to
+// change resolution part way through, a new video file is started whenever the frame size changes.
+pub struct Recording {
+    path: PathBuf,
+    frame_rate: f64,
+    video: Option<(BufWriter<File>, Size)>,
+    segment: u32,
+    audio: WavWriter<BufWriter<File>>,
+    planes: [Vec<u8>; 3],
+}
+
+impl Recording {
+    pub fn new(path: PathBuf, frame_rate: f64, sample_rate: u64) -> Result<Self, Box<dyn Error>> {
+        let spec = WavSpec {
+            channels: 2,
+            sample_rate: sample_rate.try_into()?,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+
+        let audio = WavWriter::create(add_extension(&path, "wav"), spec)?;
+
+        info!("Recording to '{}'", path.display());
+
+        Ok(Self {
+            path,
+            frame_rate,
+            video: None,
+            segment: 0,
+            audio,
+            planes: Default::default(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write_frame(&mut self, pixels: &[u8], size: Size) -> Result<(), Box<dyn Error>> {
+        if self
+            .video
+            .as_ref()
+            .is_none_or(|(_, video_size)| *video_size != size)
+        {
+            self.start_segment(size)?;
+        }
+
+        let (writer, _) = self.video.as_mut().unwrap();
+
+        for plane in &mut self.planes {
+            plane.clear();
+        }
+
+        // BT.601, limited range
+        for pixel in pixels.chunks_exact(4) {
+            let (r, g, b) = (pixel[0] as i32, pixel[1] as i32, pixel[2] as i32);
+            let y = 16 + ((66 * r + 129 * g + 25 * b + 128) >> 8);
+            let u = 128 + ((-38 * r - 74 * g + 112 * b + 128) >> 8);
+            let v = 128 + ((112 * r - 94 * g - 18 * b + 128) >> 8);
+            self.planes[0].push(y as u8);
+            self.planes[1].push(u as u8);
+            self.planes[2].push(v as u8);
+        }
+
+        writer.write_all(b"FRAME\n")?;
+
+        for plane in &self.planes {
+            writer.write_all(plane)?;
+        }
+
+        Ok(())
+    }
+
+    // Does not consume the queue, as the samples still need to be played
+    pub fn write_audio(&mut self, queue: &AudioQueue) -> Result<(), Box<dyn Error>> {
+        for &(left, right) in queue {
+            self.audio.write_sample(to_i16(left))?;
+            self.audio.write_sample(to_i16(right))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), Box<dyn Error>> {
+        if let Some((mut writer, _)) = self.video.take() {
+            writer.flush()?;
+        }
+
+        self.audio.finalize()?;
+
+        info!("Recording saved to '{}'", self.path.display());
+
+        Ok(())
+    }
+
+    fn start_segment(&mut self, size: Size) -> Result<(), Box<dyn Error>> {
+        if let Some((mut writer, _)) = self.video.take() {
+            writer.flush()?;
+        }
+
+        self.segment += 1;
+
+        let path = if self.segment == 1 {
+            add_extension(&self.path, "y4m")
+        } else {
+            add_extension(&self.path, &format!("{}.y4m", self.segment))
+        };
+
+        if self.segment > 1 {
+            info!(
+                "Resolution changed to {}x{}, continuing in '{}'",
+                size.width,
+                size.height,
+                path.display()
+            );
+        }
+
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writeln!(
+            writer,
+            "YUV4MPEG2 W{} H{} F{}:1000 Ip A1:1 C444",
+            size.width,
+            size.height,
+            (self.frame_rate * 1000.0).round() as u32
+        )?;
+
+        self.video = Some((writer, size));
+
+        Ok(())
+    }
+}
+
+fn to_i16(sample: f32) -> i16 {
+    (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("utopia-{}-{}", name, std::process::id()));
+        _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn next_path_skips_taken_names() {
+        let dir = temp_dir("next-path");
+
+        let path = next_path(&dir, "game.v1", &["png"]).unwrap();
+        assert_eq!(path, dir.join("game.v1-001"));
+        assert_eq!(add_extension(&path, "png"), dir.join("game.v1-001.png"));
+
+        fs::write(dir.join("game.v1-001.png"), []).unwrap();
+        fs::write(dir.join("game.v1-002.wav"), []).unwrap();
+
+        // A name is only free if it's free for every extension
+        assert_eq!(
+            next_path(&dir, "game.v1", &["png"]).unwrap(),
+            dir.join("game.v1-002")
+        );
+
+        assert_eq!(
+            next_path(&dir, "game.v1", &["png", "wav"]).unwrap(),
+            dir.join("game.v1-003")
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn resolution_change_starts_new_segment() {
+        let dir = temp_dir("recording");
+        let path = next_path(&dir, "game", &["y4m", "wav"]).unwrap();
+        let mut recording = Recording::new(path.clone(), 60.0, 32000).unwrap();
+
+        let small = Size::new(2, 1);
+        let large = Size::new(2, 2);
+
+        recording.write_frame(&[255; 8], small).unwrap();
+        recording.write_frame(&[0; 8], small).unwrap();
+        recording.write_frame(&[0; 16], large).unwrap();
+        recording.write_audio(&[(0.5, -0.5)].into()).unwrap();
+        recording.finish().unwrap();
+
+        let first = fs::read(add_extension(&path, "y4m")).unwrap();
+        let header = b"YUV4MPEG2 W2 H1 F60000:1000 Ip A1:1 C444\n";
+        assert!(first.starts_with(header));
+        assert_eq!(first.len(), header.len() + 2 * (6 + 2 * 3));
+
+        // White and black in limited range
+        assert_eq!(first[header.len()..][..8], *b"FRAME\n\xeb\xeb");
+        assert_eq!(first[(header.len() + 12)..][..8], *b"FRAME\n\x10\x10");
+
+        let second = fs::read(add_extension(&path, "2.y4m")).unwrap();
+        let header = b"YUV4MPEG2 W2 H2 F60000:1000 Ip A1:1 C444\n";
+        assert!(second.starts_with(header));
+        assert_eq!(second.len(), header.len() + 6 + 4 * 3);
+
+        let wav = hound::WavReader::open(add_extension(&path, "wav")).unwrap();
+        assert_eq!(wav.spec().sample_rate, 32000);
+        assert_eq!(wav.len(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
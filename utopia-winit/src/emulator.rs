@@ -0,0 +1,432 @@
+use super::audio::AudioSender;
+use super::capture::{self, Recording};
+use super::input::PortState;
+use super::macros::Macros;
+use super::turbo::Turbo;
+use super::Sync;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+use triple_buffer::{Input, Output, TripleBuffer};
+use utopia::{AchievementEvent, CheatSet, FrameScaler, Instance, Scaler, Size};
+
+#[cfg(not(target_arch = "wasm32"))]
+use rtrb::{Consumer, Producer, PushError, RingBuffer};
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread::{self, JoinHandle};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
+// Commands are only sent in response to user input (plus one tick per frame), so the emulator
+// thread should never fall this far behind
+#[cfg(not(target_arch = "wasm32"))]
+const COMMAND_QUEUE_SIZE: usize = 64;
+
+#[derive(Clone, Default)]
+pub struct Frame {
+    pub pixels: Vec<u8>,
+    pub size: Size,
+}
+
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+pub enum Command {
+    Input(Vec<Option<PortState>>),
+    RecordMacro,
+    PlayMacro,
+    StartRecording(Box<Recording>),
+    StopRecording,
+    Screenshot(PathBuf),
+    TogglePause,
+    FrameAdvance,
+    SetSpeed(f64),
+    LoadCheats(CheatSet),
+    Tick,
+    Exit,
+}
+
+pub struct Runner {
+    instance: Box<dyn Instance>,
+    audio: AudioSender,
+    scaler: FrameScaler,
+    frames: Input<Frame>,
+    ports: Vec<Option<PortState>>,
+    turbo: Turbo,
+    macros: Macros,
+    recording: Option<Recording>,
+    paused: bool,
+    frame_advance: bool,
+    ticked: bool,
+    speed: f64,
+    frame_credit: f64,
+}
+
+impl Runner {
+    pub fn new(
+        instance: Box<dyn Instance>,
+        audio: AudioSender,
+        scaler: Scaler,
+        turbo_frames: u32,
+    ) -> (Self, Output<Frame>) {
+        let (frames, output) = TripleBuffer::default().split();
+
+        let runner = Self {
+            instance,
+            audio,
+            scaler: FrameScaler::new(scaler),
+            frames,
+            ports: vec![Some(PortState::default())],
+            turbo: Turbo::new(turbo_frames),
+            macros: Macros::new(),
+            recording: None,
+            paused: false,
+            frame_advance: false,
+            ticked: false,
+            speed: 1.0,
+            frame_credit: 0.0,
+        };
+
+        (runner, output)
+    }
+
+    pub fn sync_time(&self) -> Instant {
+        self.audio.sync_time()
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn resync(&mut self) {
+        self.audio.resync();
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn handle_command(&mut self, command: Command) -> bool {
+        match command {
+            Command::Input(ports) => self.ports = ports,
+            Command::RecordMacro => self.macros.toggle_recording(),
+            Command::PlayMacro => self.macros.play(),
+            Command::StartRecording(recording) => {
+                self.stop_recording();
+                self.recording = Some(*recording);
+            }
+            Command::StopRecording => self.stop_recording(),
+            Command::Screenshot(path) => {
+                if let Err(err) = self.save_screenshot(&path) {
+                    warn!("Failed to save screenshot: {}", err);
+                }
+            }
+            Command::TogglePause => {
+                self.paused = !self.paused;
+                info!("{}", if self.paused { "Paused" } else { "Resumed" });
+
+                // Don't try to catch up on the time spent paused
+                if !self.paused {
+                    self.audio.resync();
+                }
+            }
+            Command::FrameAdvance => {
+                if self.paused {
+                    self.frame_advance = true;
+                }
+            }
+            Command::SetSpeed(speed) => {
+                self.speed = speed;
+                self.frame_credit = 0.0;
+                self.audio.set_speed(speed);
+            }
+            Command::LoadCheats(set) => {
+                if let Err(err) = self.instance.load_cheats(set) {
+                    warn!("Failed to load cheats: {}", err);
+                }
+            }
+            Command::Tick => self.ticked = true,
+            Command::Exit => return false,
+        }
+
+        true
+    }
+
+    // Returns the number of frames to run for this tick (or wake-up, when not using video sync)
+    pub fn take_frames(&mut self, sync: Sync) -> u32 {
+        self.ticked = false;
+
+        if self.paused {
+            return std::mem::take(&mut self.frame_advance) as u32;
+        }
+
+        if sync != Sync::Video {
+            return 1;
+        }
+
+        // With video sync, fast-forward and slow motion are handled by running more or fewer
+        // frames per tick
+        self.frame_credit += self.speed;
+        let frames = self.frame_credit.floor();
+        self.frame_credit -= frames;
+        frames as u32
+    }
+
+    pub fn run_frame(&mut self) {
+        let mut ports = self.turbo.apply(&self.ports);
+        self.macros.apply(&mut ports);
+        self.instance.run_frame(&ports);
+
+        if let Some((pixels, size)) = self.instance.pixels() {
+            // Scaling happens here rather than on the UI thread, as it can be expensive
+            let frame = self.frames.input_buffer();
+            self.scaler.apply(pixels, size, &mut frame.pixels);
+            frame.size = self.scaler.output_size(size);
+            self.frames.publish();
+
+            if let Some(recording) = &mut self.recording {
+                if let Err(err) = recording.write_frame(pixels, size) {
+                    warn!("Failed to record video: {}", err);
+                    self.recording = None;
+                }
+            }
+        }
+
+        if let Some(queue) = self.instance.audio_queue() {
+            if let Some(recording) = &mut self.recording {
+                if let Err(err) = recording.write_audio(queue) {
+                    warn!("Failed to record audio: {}", err);
+                    self.recording = None;
+                }
+            }
+
+            self.audio.queue_samples(queue);
+        }
+
+        if let Some(events) = self.instance.achievement_events() {
+            for event in events.drain(..) {
+                match event {
+                    AchievementEvent::Unlocked { title, points, .. } => {
+                        info!("Achievement Unlocked: {} ({} points)", title, points);
+                    }
+                }
+            }
+        }
+    }
+
+    // Screenshots are taken at the native resolution, before any scaling or filtering
+    fn save_screenshot(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let (pixels, size) = self
+            .instance
+            .pixels()
+            .ok_or("No frame available to capture")?;
+
+        capture::save_screenshot(path, pixels, size)
+    }
+
+    fn stop_recording(&mut self) {
+        if let Some(recording) = self.recording.take() {
+            if let Err(err) = recording.finish() {
+                warn!("Failed to save recording: {}", err);
+            }
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn run(mut self, sync: Sync, mut receiver: Consumer<Command>, on_frame: impl Fn()) -> Self {
+        // Don't try to catch up on any time spent stopped
+        self.audio.resync();
+
+        loop {
+            // In video sync mode, frames are only run when the UI thread asks for one
+            let ready = if self.paused {
+                self.frame_advance
+            } else {
+                sync != Sync::Video || self.ticked
+            };
+
+            // The UI thread unparks this thread whenever it sends a command
+            let command = match receiver.pop() {
+                Ok(command) => Some(command),
+                Err(_) if receiver.is_abandoned() => return self,
+                Err(_) if ready => None,
+                Err(_) => {
+                    thread::park();
+                    continue;
+                }
+            };
+
+            if let Some(command) = command {
+                if !self.handle_command(command) {
+                    return self;
+                }
+
+                continue;
+            }
+
+            let video_tick = sync == Sync::Video && self.ticked;
+            let frames = self.take_frames(sync);
+
+            for _ in 0..frames {
+                self.run_frame();
+            }
+
+            // Every tick needs a redraw, even if no frames were run, or the ticks will stop coming
+            if frames > 0 || video_tick {
+                on_frame();
+            }
+
+            if sync == Sync::Audio && !self.paused {
+                let sync_time = self.sync_time();
+                let now = Instant::now();
+
+                if sync_time > now {
+                    thread::sleep(sync_time - now);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Runner {
+    fn drop(&mut self) {
+        self.stop_recording();
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+type OnFrame = Box<dyn Fn() + Send>;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Emulator {
+    sender: Producer<Command>,
+    thread: Option<JoinHandle<(Runner, OnFrame)>>,
+    // Held while the thread is stopped, so that it can be started again
+    stopped: Option<(Runner, OnFrame)>,
+    sync: Sync,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Emulator {
+    pub fn spawn(runner: Runner, sync: Sync, on_frame: impl Fn() + Send + 'static) -> Self {
+        let mut emulator = Self {
+            sender: RingBuffer::new(1).0,
+            thread: None,
+            stopped: Some((runner, Box::new(on_frame))),
+            sync,
+        };
+
+        emulator.resume();
+        emulator
+    }
+
+    pub fn send(&mut self, mut command: Command) {
+        // The queue only fills up if the thread has stalled, so give it a chance to catch up
+        while let Err(PushError::Full(rejected)) = self.sender.push(command) {
+            // If the thread has already exited, there's nothing to do
+            if self.sender.is_abandoned() {
+                return;
+            }
+
+            self.wake();
+            thread::yield_now();
+            command = rejected;
+        }
+
+        self.wake();
+    }
+
+    fn wake(&self) {
+        if let Some(thread) = &self.thread {
+            thread.thread().unpark();
+        }
+    }
+
+    // Stops the thread, but keeps the instance around (with its save data still open)
+    pub fn stop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            self.send(Command::Exit);
+            self.stopped = thread.join().ok();
+        }
+    }
+
+    pub fn resume(&mut self) {
+        let Some((runner, on_frame)) = self.stopped.take() else {
+            return;
+        };
+
+        let (sender, receiver) = RingBuffer::new(COMMAND_QUEUE_SIZE);
+        let sync = self.sync;
+
+        let thread = thread::Builder::new()
+            .name("emulator".into())
+            .spawn(move || (runner.run(sync, receiver, &on_frame), on_frame))
+            .expect("Failed to spawn emulator thread");
+
+        self.sender = sender;
+        self.thread = Some(thread);
+
+        if sync == Sync::Video {
+            self.send(Command::Tick);
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for Emulator {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::audio::AudioController;
+    use super::super::turbo::DEFAULT_TURBO_FRAMES;
+    use super::super::FastForwardAudio;
+    use super::*;
+    use std::fs::{self, File};
+    use utopia::JoypadState;
+
+    struct TestInstance {
+        pixels: Vec<u8>,
+    }
+
+    impl Instance for TestInstance {
+        fn run_frame(&mut self, _ports: &[Option<JoypadState>]) {}
+
+        fn present(&self, _canvas: &wgpu::Texture) {}
+
+        fn pixels(&self) -> Option<(&[u8], Size)> {
+            Some((&self.pixels, Size::new(3, 2)))
+        }
+    }
+
+    #[test]
+    fn screenshots_are_native_resolution() {
+        let instance = TestInstance {
+            pixels: vec![255; 3 * 2 * 4],
+        };
+
+        let (_audio, sender) = AudioController::new(None, 44100, None, FastForwardAudio::Mute);
+        let (mut runner, mut frames) = Runner::new(
+            Box::new(instance),
+            sender,
+            Scaler::Scale2x,
+            DEFAULT_TURBO_FRAMES,
+        );
+
+        runner.run_frame();
+        frames.update();
+        assert_eq!(frames.output_buffer().size, Size::new(6, 4));
+
+        let path =
+            std::env::temp_dir().join(format!("utopia-screenshot-{}.png", std::process::id()));
+        assert!(runner.handle_command(Command::Screenshot(path.clone())));
+
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let info = decoder.read_info().unwrap().info().clone();
+        assert_eq!((info.width, info.height), (3, 2));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,60 +1,259 @@
-use gilrs::{Axis, Button, Error, EventType, Gilrs};
-use utopia::JoypadState;
+use super::input::{self, GamepadConfig, GamepadInput, InputConfig, PortState};
+use gilrs::{Axis, Button, Error, EventType, GamepadId, Gilrs};
+use std::collections::{HashMap, HashSet};
+use tracing::{info, warn};
+use utopia::MAX_PORTS;
+
+const DEFAULT_BINDINGS: [(GamepadInput, usize); 14] = [
+    (GamepadInput::Button(Button::South), 0),
+    (GamepadInput::Button(Button::East), 1),
+    // North and west appear swapped for me, though this may just be an Xbox controller issue?
+    (GamepadInput::Button(Button::North), 2),
+    (GamepadInput::Button(Button::West), 3),
+    (GamepadInput::Button(Button::LeftTrigger), 4),
+    (GamepadInput::Button(Button::RightTrigger), 5),
+    (GamepadInput::Axis(Axis::LeftZ, true), 6),
+    (GamepadInput::Axis(Axis::RightZ, true), 7),
+    (GamepadInput::Button(Button::Select), 8),
+    (GamepadInput::Button(Button::Start), 9),
+    (GamepadInput::Button(Button::DPadUp), 12),
+    (GamepadInput::Button(Button::DPadDown), 13),
+    (GamepadInput::Button(Button::DPadLeft), 14),
+    (GamepadInput::Button(Button::DPadRight), 15),
+];
+
+struct Device {
+    uuid: String,
+    name: String,
+    port: usize,
+    config: GamepadConfig,
+    bindings: HashMap<GamepadInput, Vec<usize>>,
+    held: HashSet<GamepadInput>,
+    axes: [i32; 4],
+}
+
+impl Device {
+    fn new(uuid: String, name: String, port: usize, config: &InputConfig) -> Self {
+        let mut device = Self {
+            uuid,
+            name,
+            port,
+            config: GamepadConfig::default(),
+            bindings: HashMap::new(),
+            held: HashSet::new(),
+            axes: [0; 4],
+        };
+
+        device.set_config(config);
+        device
+    }
+
+    fn set_config(&mut self, config: &InputConfig) {
+        self.config = config.gamepad_config(&self.uuid);
+        self.bindings.clear();
+
+        for (input, button) in DEFAULT_BINDINGS {
+            // Buttons mentioned in the config lose all of their default inputs
+            if !self
+                .config
+                .buttons
+                .contains_key(input::BUTTON_NAMES[button])
+            {
+                self.bindings.entry(input).or_default().push(button);
+            }
+        }
+
+        for (name, inputs) in &self.config.buttons {
+            let Some(button) = input::button_index(name) else {
+                warn!("Unknown button name in gamepad bindings: '{}'", name);
+                continue;
+            };
+
+            for input in inputs {
+                self.bindings.entry(*input).or_default().push(button);
+            }
+        }
+    }
+
+    // Returns true if the input has just become active
+    fn update(&mut self, input: GamepadInput, active: bool) -> bool {
+        if active {
+            self.held.insert(input)
+        } else {
+            self.held.remove(&input);
+            false
+        }
+    }
+
+    fn update_axis(&mut self, axis: Axis, value: f32, pressed: &mut Vec<GamepadInput>) {
+        match axis {
+            Axis::LeftStickX => self.axes[0] = (value * i32::MAX as f32) as i32,
+            Axis::LeftStickY => self.axes[1] = (value * i32::MAX as f32) as i32,
+            Axis::RightStickX => self.axes[2] = (value * i32::MAX as f32) as i32,
+            Axis::RightStickY => self.axes[3] = (value * i32::MAX as f32) as i32,
+            _ => (),
+        }
+
+        let threshold = self.config.threshold(axis);
+
+        for positive in [true, false] {
+            let input = GamepadInput::Axis(axis, positive);
+            let active = GamepadInput::axis_value(axis, positive, value) >= threshold;
+
+            if self.update(input, active) {
+                pressed.push(input);
+            }
+        }
+    }
+}
 
 pub struct Gamepad {
     gilrs: Gilrs,
+    config: InputConfig,
+    devices: HashMap<GamepadId, Device>,
 }
 
 impl Gamepad {
-    pub fn new() -> Result<Self, Error> {
-        Ok(Self {
+    pub fn new(config: &InputConfig) -> Result<Self, Error> {
+        let mut gamepad = Self {
             gilrs: Gilrs::new()?,
-        })
+            config: config.clone(),
+            devices: HashMap::new(),
+        };
+
+        // Gamepads that are already plugged in don't generate 'Connected' events
+        let ids: Vec<GamepadId> = gamepad.gilrs.gamepads().map(|(id, _)| id).collect();
+
+        for id in ids {
+            gamepad.connect(id);
+        }
+
+        Ok(gamepad)
     }
 
-    pub fn handle_events(&mut self, joypad_state: &mut JoypadState) {
+    pub fn set_bindings(&mut self, config: &InputConfig) {
+        self.config = config.clone();
+
+        for device in self.devices.values_mut() {
+            device.set_config(config);
+        }
+    }
+
+    pub fn uuid(&self, id: GamepadId) -> Option<&str> {
+        self.devices.get(&id).map(|device| device.uuid.as_str())
+    }
+
+    // Returns the port and name of each connected gamepad
+    pub fn assignments(&self) -> Vec<(usize, &str)> {
+        let mut assignments: Vec<_> = self
+            .devices
+            .values()
+            .map(|device| (device.port, device.name.as_str()))
+            .collect();
+
+        assignments.sort();
+        assignments
+    }
+
+    pub fn next_port(&mut self, id: GamepadId) {
+        if let Some(device) = self.devices.get_mut(&id) {
+            device.port = (device.port + 1) % MAX_PORTS;
+            info!(
+                "Gamepad '{}' moved to port {}",
+                device.name,
+                device.port + 1
+            );
+        }
+    }
+
+    // Returns every input that was newly pressed, along with the ID of its gamepad
+    pub fn handle_events(&mut self) -> Vec<(GamepadId, GamepadInput)> {
+        let mut pressed = Vec::new();
+
         while let Some(event) = self.gilrs.next_event() {
             match event.event {
-                EventType::ButtonPressed(button, ..) => update_button(joypad_state, button, true),
-                EventType::ButtonReleased(button, ..) => update_button(joypad_state, button, false),
-                EventType::AxisChanged(axis, value, ..) => update_axis(joypad_state, axis, value),
+                EventType::Connected => {
+                    self.connect(event.id);
+                    continue;
+                }
+                EventType::Disconnected => {
+                    if let Some(device) = self.devices.remove(&event.id) {
+                        info!(
+                            "Gamepad '{}' disconnected from port {}",
+                            device.name,
+                            device.port + 1
+                        );
+                    }
+
+                    continue;
+                }
                 _ => (),
             }
+
+            let Some(device) = self.connect(event.id) else {
+                continue;
+            };
+
+            let mut device_pressed = Vec::new();
+
+            match event.event {
+                EventType::ButtonPressed(button, ..) if button != Button::Unknown => {
+                    let input = GamepadInput::Button(button);
+
+                    if device.update(input, true) {
+                        device_pressed.push(input);
+                    }
+                }
+                EventType::ButtonReleased(button, ..) => {
+                    device.update(GamepadInput::Button(button), false);
+                }
+                EventType::AxisChanged(axis, value, ..) if axis != Axis::Unknown => {
+                    device.update_axis(axis, value, &mut device_pressed);
+                }
+                _ => (),
+            }
+
+            pressed.extend(device_pressed.into_iter().map(|input| (event.id, input)));
         }
+
+        pressed
     }
-}
 
-fn update_button(joypad_state: &mut JoypadState, button: Button, pressed: bool) {
-    let JoypadState { buttons, .. } = joypad_state;
-
-    match button {
-        Button::South => buttons[0] = pressed,
-        Button::East => buttons[1] = pressed,
-        // North and west appear swapped for me, though this may just be an Xbox controller issue?
-        Button::North => buttons[2] = pressed,
-        Button::West => buttons[3] = pressed,
-        Button::LeftTrigger => buttons[4] = pressed,
-        Button::RightTrigger => buttons[5] = pressed,
-        Button::Select => buttons[8] = pressed,
-        Button::Start => buttons[9] = pressed,
-        Button::DPadUp => buttons[12] = pressed,
-        Button::DPadDown => buttons[13] = pressed,
-        Button::DPadLeft => buttons[14] = pressed,
-        Button::DPadRight => buttons[15] = pressed,
-        _ => (),
+    pub fn update_ports(&self, ports: &mut [Option<PortState>]) {
+        for device in self.devices.values() {
+            let port = ports[device.port].get_or_insert_with(Default::default);
+
+            // A button stays down for as long as any of its inputs are active
+            for input in &device.held {
+                for &button in device.bindings.get(input).into_iter().flatten() {
+                    port.press(button);
+                }
+            }
+
+            if device.axes != [0; 4] {
+                port.joypad.axes = device.axes;
+            }
+        }
     }
-}
 
-fn update_axis(joypad_state: &mut JoypadState, axis: Axis, value: f32) {
-    let JoypadState { axes, buttons } = joypad_state;
-
-    match axis {
-        Axis::LeftStickX => axes[0] = (value * i32::MAX as f32) as i32,
-        Axis::LeftStickY => axes[1] = (value * i32::MAX as f32) as i32,
-        Axis::RightStickX => axes[2] = (value * i32::MAX as f32) as i32,
-        Axis::RightStickY => axes[3] = (value * i32::MAX as f32) as i32,
-        Axis::LeftZ => buttons[6] = value >= -0.75,
-        Axis::RightZ => buttons[7] = value >= -0.75,
-        _ => (),
+    fn connect(&mut self, id: GamepadId) -> Option<&mut Device> {
+        if !self.devices.contains_key(&id) {
+            let gamepad = self.gilrs.connected_gamepad(id)?;
+
+            // New gamepads take the lowest port that isn't already in use
+            let port = (0..MAX_PORTS)
+                .find(|port| self.devices.values().all(|device| device.port != *port))
+                .unwrap_or(0);
+
+            let uuid = input::uuid_string(gamepad.uuid());
+            let name = gamepad.name().to_owned();
+
+            info!("Gamepad '{}' connected to port {}", name, port + 1);
+
+            self.devices
+                .insert(id, Device::new(uuid, name, port, &self.config));
+        }
+
+        self.devices.get_mut(&id)
     }
 }
@@ -0,0 +1,309 @@
+use gilrs::{Axis, Button};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use utopia::{JoypadState, JOYPAD_BUTTON_COUNT};
+use winit::keyboard::KeyCode;
+
+// Names for each entry in JoypadState.buttons, following the standard gamepad layout, followed by
+// turbo versions of the face buttons
+pub const BUTTON_NAMES: [&str; 21] = [
+    "a", "b", "x", "y", "l1", "r1", "l2", "r2", "select", "start", "l3", "r3", "up", "down",
+    "left", "right", "home", "turbo_a", "turbo_b", "turbo_x", "turbo_y",
+];
+
+pub const BUTTON_COUNT: usize = BUTTON_NAMES.len();
+
+// The JoypadState.buttons index pressed by each turbo button
+pub const TURBO_BUTTONS: [usize; 4] = [0, 1, 2, 3];
+
+const _: () = assert!(BUTTON_COUNT == JOYPAD_BUTTON_COUNT + TURBO_BUTTONS.len());
+
+const GAMEPAD_BUTTONS: [(&str, Button); 19] = [
+    ("South", Button::South),
+    ("East", Button::East),
+    ("North", Button::North),
+    ("West", Button::West),
+    ("C", Button::C),
+    ("Z", Button::Z),
+    ("LeftTrigger", Button::LeftTrigger),
+    ("LeftTrigger2", Button::LeftTrigger2),
+    ("RightTrigger", Button::RightTrigger),
+    ("RightTrigger2", Button::RightTrigger2),
+    ("Select", Button::Select),
+    ("Start", Button::Start),
+    ("Mode", Button::Mode),
+    ("LeftThumb", Button::LeftThumb),
+    ("RightThumb", Button::RightThumb),
+    ("DPadUp", Button::DPadUp),
+    ("DPadDown", Button::DPadDown),
+    ("DPadLeft", Button::DPadLeft),
+    ("DPadRight", Button::DPadRight),
+];
+
+const GAMEPAD_AXES: [(&str, Axis); 8] = [
+    ("LeftStickX", Axis::LeftStickX),
+    ("LeftStickY", Axis::LeftStickY),
+    ("LeftZ", Axis::LeftZ),
+    ("RightStickX", Axis::RightStickX),
+    ("RightStickY", Axis::RightStickY),
+    ("RightZ", Axis::RightZ),
+    ("DPadX", Axis::DPadX),
+    ("DPadY", Axis::DPadY),
+];
+
+const DEFAULT_STICK_THRESHOLD: f32 = 0.5;
+
+// Equivalent to a raw trigger value of -0.75
+const DEFAULT_TRIGGER_THRESHOLD: f32 = 0.125;
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct InputConfig {
+    pub keyboard: BTreeMap<String, Vec<KeyCode>>,
+    // Numbered from 1, to match player numbers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keyboard_port: Option<usize>,
+    pub gamepad: GamepadConfig,
+    // Keyed by gamepad UUID, as a 32 digit hex string
+    pub profiles: BTreeMap<String, GamepadConfig>,
+    // Number of emulated frames that turbo buttons spend pressed, then released
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub turbo_frames: Option<u32>,
+}
+
+impl InputConfig {
+    pub fn merge(&mut self, other: &InputConfig) {
+        self.keyboard.extend(
+            other
+                .keyboard
+                .iter()
+                .map(|(name, keys)| (name.clone(), keys.clone())),
+        );
+
+        self.keyboard_port = other.keyboard_port.or(self.keyboard_port);
+        self.turbo_frames = other.turbo_frames.or(self.turbo_frames);
+        self.gamepad.merge(&other.gamepad);
+
+        for (uuid, profile) in &other.profiles {
+            self.profiles
+                .entry(uuid.to_ascii_lowercase())
+                .or_default()
+                .merge(profile);
+        }
+    }
+
+    pub fn gamepad_config(&self, uuid: &str) -> GamepadConfig {
+        let mut config = self.gamepad.clone();
+
+        let profile = self
+            .profiles
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(uuid));
+
+        if let Some((_, profile)) = profile {
+            config.merge(profile);
+        }
+
+        config
+    }
+}
+
+// Input for a single controller port, before turbo is applied
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PortState {
+    pub joypad: JoypadState,
+    pub turbo: [bool; TURBO_BUTTONS.len()],
+}
+
+impl PortState {
+    pub fn press(&mut self, button: usize) {
+        if button < JOYPAD_BUTTON_COUNT {
+            self.joypad.buttons[button] = true;
+        } else {
+            self.turbo[button - JOYPAD_BUTTON_COUNT] = true;
+        }
+    }
+
+    pub fn pressed(&self, button: usize) -> bool {
+        if button < JOYPAD_BUTTON_COUNT {
+            self.joypad.buttons[button]
+        } else {
+            self.turbo[button - JOYPAD_BUTTON_COUNT]
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GamepadConfig {
+    pub buttons: BTreeMap<String, Vec<GamepadInput>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stick_threshold: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trigger_threshold: Option<f32>,
+}
+
+impl GamepadConfig {
+    pub fn merge(&mut self, other: &GamepadConfig) {
+        self.buttons.extend(
+            other
+                .buttons
+                .iter()
+                .map(|(name, inputs)| (name.clone(), inputs.clone())),
+        );
+
+        self.stick_threshold = other.stick_threshold.or(self.stick_threshold);
+        self.trigger_threshold = other.trigger_threshold.or(self.trigger_threshold);
+    }
+
+    pub fn threshold(&self, axis: Axis) -> f32 {
+        if is_trigger(axis) {
+            self.trigger_threshold.unwrap_or(DEFAULT_TRIGGER_THRESHOLD)
+        } else {
+            self.stick_threshold.unwrap_or(DEFAULT_STICK_THRESHOLD)
+        }
+    }
+}
+
+// A physical button, or one direction of an analog axis (e.g. '+LeftStickX')
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum GamepadInput {
+    Button(Button),
+    Axis(Axis, bool),
+}
+
+impl GamepadInput {
+    // Maps a raw axis value onto 0.0 - 1.0 in the direction of this input
+    pub fn axis_value(axis: Axis, positive: bool, value: f32) -> f32 {
+        if is_trigger(axis) {
+            // Triggers rest at -1.0, so they only have one useful direction
+            if positive {
+                (value + 1.0) / 2.0
+            } else {
+                0.0
+            }
+        } else if positive {
+            value
+        } else {
+            -value
+        }
+    }
+}
+
+impl FromStr for GamepadInput {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (positive, name) = match value.as_bytes().first() {
+            Some(b'+') => (Some(true), &value[1..]),
+            Some(b'-') => (Some(false), &value[1..]),
+            _ => (None, value),
+        };
+
+        let input = if let Some(positive) = positive {
+            GAMEPAD_AXES
+                .iter()
+                .find(|(axis_name, _)| *axis_name == name)
+                .map(|(_, axis)| GamepadInput::Axis(*axis, positive))
+        } else {
+            GAMEPAD_BUTTONS
+                .iter()
+                .find(|(button_name, _)| *button_name == name)
+                .map(|(_, button)| GamepadInput::Button(*button))
+        };
+
+        input.ok_or_else(|| format!("Unknown gamepad input: '{}'", value))
+    }
+}
+
+impl TryFrom<String> for GamepadInput {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<GamepadInput> for String {
+    fn from(value: GamepadInput) -> Self {
+        value.to_string()
+    }
+}
+
+impl fmt::Display for GamepadInput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GamepadInput::Button(button) => {
+                let name = GAMEPAD_BUTTONS
+                    .iter()
+                    .find(|(_, value)| *value == button)
+                    .map_or("Unknown", |(name, _)| name);
+
+                write!(f, "{}", name)
+            }
+            GamepadInput::Axis(axis, positive) => {
+                let name = GAMEPAD_AXES
+                    .iter()
+                    .find(|(_, value)| *value == axis)
+                    .map_or("Unknown", |(name, _)| name);
+
+                write!(f, "{}{}", if positive { '+' } else { '-' }, name)
+            }
+        }
+    }
+}
+
+pub fn button_index(name: &str) -> Option<usize> {
+    BUTTON_NAMES.iter().position(|button| *button == name)
+}
+
+pub fn uuid_string(uuid: [u8; 16]) -> String {
+    uuid.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn is_trigger(axis: Axis) -> bool {
+    matches!(axis, Axis::LeftZ | Axis::RightZ)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gamepad_input_names() {
+        for name in ["South", "DPadLeft", "+LeftStickX", "-RightStickY", "+LeftZ"] {
+            let input: GamepadInput = name.parse().unwrap();
+            assert_eq!(input.to_string(), name);
+        }
+
+        assert!("LeftStickX".parse::<GamepadInput>().is_err());
+        assert!("+South".parse::<GamepadInput>().is_err());
+    }
+
+    #[test]
+    fn gamepad_profiles() {
+        let mut config = InputConfig::default();
+        config.gamepad.stick_threshold = Some(0.25);
+        config
+            .gamepad
+            .buttons
+            .insert("a".into(), vec![GamepadInput::Button(Button::South)]);
+
+        let mut profile = GamepadConfig::default();
+        profile
+            .buttons
+            .insert("a".into(), vec![GamepadInput::Button(Button::East)]);
+        config.profiles.insert("00ff".into(), profile);
+
+        let merged = config.gamepad_config("00FF");
+        assert_eq!(merged.buttons["a"], [GamepadInput::Button(Button::East)]);
+        assert_eq!(merged.threshold(Axis::LeftStickX), 0.25);
+        assert_eq!(merged.threshold(Axis::LeftZ), DEFAULT_TRIGGER_THRESHOLD);
+
+        let other = config.gamepad_config("1234");
+        assert_eq!(other.buttons["a"], [GamepadInput::Button(Button::South)]);
+    }
+}
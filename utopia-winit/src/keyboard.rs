@@ -1,26 +1,97 @@
-use utopia::JoypadState;
+use super::input::{self, InputConfig, BUTTON_COUNT};
+use std::collections::{HashMap, HashSet};
+use tracing::warn;
 use winit::event::{ElementState, KeyEvent};
 use winit::keyboard::{KeyCode, PhysicalKey};
 
-pub fn handle_input(joypad_state: &mut JoypadState, input: KeyEvent) {
-    let JoypadState { buttons, .. } = joypad_state;
-    let pressed = input.state == ElementState::Pressed;
-
-    if let PhysicalKey::Code(key_code) = input.physical_key {
-        match key_code {
-            KeyCode::KeyZ => buttons[0] = pressed,
-            KeyCode::KeyX => buttons[1] = pressed,
-            KeyCode::KeyA => buttons[2] = pressed,
-            KeyCode::KeyS => buttons[3] = pressed,
-            KeyCode::KeyD => buttons[4] = pressed,
-            KeyCode::KeyC => buttons[5] = pressed,
-            KeyCode::Space => buttons[8] = pressed,
-            KeyCode::Enter => buttons[9] = pressed,
-            KeyCode::ArrowUp => buttons[12] = pressed,
-            KeyCode::ArrowDown => buttons[13] = pressed,
-            KeyCode::ArrowLeft => buttons[14] = pressed,
-            KeyCode::ArrowRight => buttons[15] = pressed,
-            _ => (),
+const DEFAULT_BINDINGS: [(KeyCode, usize); 12] = [
+    (KeyCode::KeyZ, 0),
+    (KeyCode::KeyX, 1),
+    (KeyCode::KeyA, 2),
+    (KeyCode::KeyS, 3),
+    (KeyCode::KeyD, 4),
+    (KeyCode::KeyC, 5),
+    (KeyCode::Space, 8),
+    (KeyCode::Enter, 9),
+    (KeyCode::ArrowUp, 12),
+    (KeyCode::ArrowDown, 13),
+    (KeyCode::ArrowLeft, 14),
+    (KeyCode::ArrowRight, 15),
+];
+
+pub struct Keyboard {
+    bindings: HashMap<KeyCode, Vec<usize>>,
+    held: HashSet<KeyCode>,
+    buttons: [bool; BUTTON_COUNT],
+}
+
+impl Keyboard {
+    pub fn new(config: &InputConfig) -> Self {
+        let mut keyboard = Self {
+            bindings: HashMap::new(),
+            held: HashSet::new(),
+            buttons: [false; BUTTON_COUNT],
+        };
+
+        keyboard.set_bindings(config);
+        keyboard
+    }
+
+    pub fn buttons(&self) -> &[bool; BUTTON_COUNT] {
+        &self.buttons
+    }
+
+    pub fn set_bindings(&mut self, config: &InputConfig) {
+        self.bindings.clear();
+
+        for (key_code, button) in DEFAULT_BINDINGS {
+            // Buttons mentioned in the config lose all of their default keys
+            if !config.keyboard.contains_key(input::BUTTON_NAMES[button]) {
+                self.bindings.entry(key_code).or_default().push(button);
+            }
+        }
+
+        for (name, key_codes) in &config.keyboard {
+            let Some(button) = input::button_index(name) else {
+                warn!("Unknown button name in key bindings: '{}'", name);
+                continue;
+            };
+
+            for key_code in key_codes {
+                self.bindings.entry(*key_code).or_default().push(button);
+            }
+        }
+
+        self.update_buttons();
+    }
+
+    pub fn release_all(&mut self) {
+        self.held.clear();
+        self.update_buttons();
+    }
+
+    pub fn handle_input(&mut self, input: KeyEvent) {
+        let PhysicalKey::Code(key_code) = input.physical_key else {
+            return;
+        };
+
+        if input.state == ElementState::Pressed {
+            self.held.insert(key_code);
+        } else {
+            self.held.remove(&key_code);
+        }
+
+        self.update_buttons();
+    }
+
+    fn update_buttons(&mut self) {
+        self.buttons = [false; BUTTON_COUNT];
+
+        // A button stays down for as long as any of its keys are held
+        for key_code in &self.held {
+            for &button in self.bindings.get(key_code).into_iter().flatten() {
+                self.buttons[button] = true;
+            }
         }
     }
 }
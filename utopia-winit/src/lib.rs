@@ -1,19 +1,40 @@
-pub use utopia::{BiosLoader, DefaultBiosLoader, DefaultMemoryMapper, Error, MemoryMapper};
-
-use audio::AudioController;
+pub use capture::save_screenshot;
+pub use utopia::{
+    apply_patch, create, is_patch, AchievementSet, AudioFilter, AudioQueue, BiosLoader, CheatSet,
+    DefaultBiosLoader, DefaultMemoryMapper, Error, FrameScaler, GbColorCorrection, GbPalette,
+    InstanceOptions, MemoryMapper, NesPalette, NtscFilter, RomInfo, Scaler, SystemOptions,
+    SystemType,
+};
+
+pub use input::{GamepadConfig, GamepadInput, InputConfig};
+pub use rebind::InputStore;
+
+use audio::{AudioController, AudioOutput};
+use capture::Recording;
+use emulator::{Command, Frame, Runner};
 use gamepad::Gamepad;
+use input::PortState;
+use input::BUTTON_NAMES;
+use keyboard::Keyboard;
+use osd::Osd;
+use rebind::Rebind;
+use serde::{Deserialize, Serialize};
 use std::error;
-use std::path::PathBuf;
-use std::rc::Rc;
-use utopia::{Instance, InstanceOptions, JoypadState, SystemOptions};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{info, warn};
+use triple_buffer::Output;
+use utopia::{Upscaler, MAX_PORTS};
 use video::VideoController;
 use winit::dpi::PhysicalSize;
-use winit::event::{ElementState, Event, WindowEvent};
+use winit::event::{ElementState, Event, KeyEvent, WindowEvent};
 use winit::event_loop::{ControlFlow, EventLoopBuilder, EventLoopProxy, EventLoopWindowTarget};
-use winit::keyboard::{Key, NamedKey};
+use winit::keyboard::{Key, KeyCode, NamedKey, PhysicalKey};
 
 #[cfg(not(target_arch = "wasm32"))]
-use std::time::Instant;
+use emulator::Emulator;
 
 #[cfg(target_arch = "wasm32")]
 use web_sys::HtmlCanvasElement;
@@ -23,100 +44,722 @@ use web_time::Instant;
 use winit::platform::web::EventLoopExtWebSys;
 
 mod audio;
+mod capture;
+mod emulator;
 mod gamepad;
+mod input;
 mod keyboard;
+mod macros;
+mod osd;
+mod rebind;
+mod stretch;
+mod turbo;
 mod video;
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Sync {
     None,
     Video,
     Audio,
 }
 
+// What to do with audio while fast-forwarding
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FastForwardAudio {
+    Mute,
+    #[default]
+    Stretch,
+}
+
+const DEFAULT_FAST_FORWARD_SPEED: f64 = 4.0;
+
+const SLOW_MOTION_SPEEDS: [f64; 3] = [1.0, 0.5, 0.25];
+
 #[derive(Clone, Debug)]
 pub struct ResetOptions<T: MemoryMapper> {
-    pub bios_loader: Rc<dyn BiosLoader>,
+    pub bios_loader: Arc<dyn BiosLoader>,
     pub memory_mapper: T,
     pub rom_path: PathBuf,
     pub rom_data: Vec<u8>,
+    // Patches dropped onto the window, applied in order to 'rom_data' on every reset
+    pub patches: Vec<Vec<u8>>,
     pub skip_boot: bool,
     pub full_screen: bool,
     pub sync: Option<Sync>,
+    pub scale: Option<u32>,
+    pub audio_latency: Option<u32>,
+    pub fast_forward_speed: Option<f64>,
+    pub fast_forward_audio: Option<FastForwardAudio>,
+    pub audio_filter: Option<AudioFilter>,
+    pub ntsc_filter: Option<NtscFilter>,
+    pub nes_palette: Option<NesPalette>,
+    pub gb_color_correction: Option<GbColorCorrection>,
+    pub gb_frame_blend: Option<bool>,
+    pub gb_palette: Option<GbPalette>,
+    pub scaler: Option<Scaler>,
+    pub capture_dir: Option<PathBuf>,
+    pub input: InputConfig,
+    pub input_store: Option<Arc<dyn InputStore>>,
+    pub rom_loader: Option<Arc<dyn RomLoader<T>>>,
+    pub achievements: Option<AchievementSet>,
+    pub cheats: Option<CheatSet>,
     #[cfg(target_arch = "wasm32")]
     pub canvas: HtmlCanvasElement,
 }
 
+// Builds the options for a ROM file dropped onto the window, as the frontend would have done for
+// the ROM it was started with
+pub trait RomLoader<T: MemoryMapper>: fmt::Debug + Send + std::marker::Sync {
+    fn load(&self, path: &Path) -> Result<ResetOptions<T>, Box<dyn error::Error>>;
+}
+
 struct ResetState {
     video: VideoController,
-    audio: AudioController,
+    _audio: AudioController,
     gamepad: Gamepad,
-    joypad_state: JoypadState,
-    instance: Box<dyn Instance>,
+    keyboard: Keyboard,
+    input: InputConfig,
+    input_store: Option<Arc<dyn InputStore>>,
+    rebind: Option<Rebind>,
+    keyboard_port: usize,
+    assigning_ports: bool,
+    ports: Vec<Option<PortState>>,
+    fast_forward_speed: f64,
+    fast_forward: bool,
+    slow_motion: usize,
+    paused: bool,
+    recording_macro: bool,
+    capture_dir: PathBuf,
+    capture_name: String,
+    frame_rate: f64,
+    sample_rate: u64,
+    recording: bool,
+    cheats_supported: bool,
+    upscaler: Upscaler,
+    osd: Osd,
+    frames: Output<Frame>,
     sync: Sync,
+    #[cfg(not(target_arch = "wasm32"))]
+    emulator: Emulator,
+    #[cfg(target_arch = "wasm32")]
+    runner: Runner,
 }
 
 impl ResetState {
-    pub fn new<T: MemoryMapper>(
+    pub fn new<T: MemoryMapper + Send>(
         window_target: &EventLoopWindowTarget<AppEvent<T>>,
+        proxy: EventLoopProxy<AppEvent<T>>,
         options: ResetOptions<T>,
     ) -> Result<Self, Box<dyn error::Error>> {
+        let rom_data = options
+            .patches
+            .iter()
+            .try_fold(options.rom_data, |rom_data, patch| {
+                apply_patch(rom_data, patch)
+            })?;
+
+        let system_type = SystemType::detect(&rom_data, Some(&options.rom_path))?;
+
         let system = utopia::create(SystemOptions {
-            system_type: options.rom_path.as_path().try_into()?,
+            system_type,
             bios_loader: options.bios_loader.as_ref(),
             memory_mapper: &options.memory_mapper,
             skip_boot: options.skip_boot,
+            audio_filter: options.audio_filter.unwrap_or_default(),
+            ntsc_filter: options.ntsc_filter.unwrap_or_default(),
+            nes_palette: options.nes_palette.clone().unwrap_or_default(),
+            gb_color_correction: options.gb_color_correction.unwrap_or_default(),
+            gb_frame_blend: options.gb_frame_blend.unwrap_or_default(),
+            gb_palette: options.gb_palette.clone().unwrap_or_default(),
         })?;
 
         let source_size: PhysicalSize<u32> =
             <(u32, u32)>::from(system.default_output_resolution()).into();
 
+        let audio_output = AudioOutput::open();
+
         let sync = options.sync.unwrap_or_else(|| {
-            if system.default_sample_rate().is_some() {
+            if audio_output.is_some() && system.default_sample_rate().is_some() {
                 Sync::Audio
             } else {
                 Sync::Video
             }
         });
 
+        // Without an audio device, there's nothing to sync to
+        let sync = if sync == Sync::Audio && audio_output.is_none() {
+            warn!("Audio sync is not available, so falling back to video sync");
+            Sync::Video
+        } else {
+            sync
+        };
+
         let video = VideoController::create_with_context(
             window_target,
             source_size,
             options.full_screen,
+            options.scale,
             sync == Sync::Video,
             #[cfg(target_arch = "wasm32")]
             options.canvas,
         )?;
 
-        let instance = system.create_instance(InstanceOptions {
-            rom_data: options.rom_data,
-            wgpu_context: video.ctx().clone(),
-            output_resolution: <(u32, u32)>::from(video.window().inner_size()).into(),
+        let output_resolution = <(u32, u32)>::from(video.window().inner_size()).into();
+
+        let mut instance = system.create_instance(InstanceOptions {
+            rom_data,
+            // Frames are presented by the UI thread, so only systems that render on the GPU
+            // need a context of their own
+            wgpu_context: system.requires_wgpu_context().then(|| video.ctx().clone()),
+            output_resolution,
         })?;
 
-        let mut audio = AudioController::new(instance.sample_rate())?;
+        if let Some(achievements) = options.achievements {
+            instance.load_achievements(achievements)?;
+        }
+
+        // Loading an empty set doubles as a check for whether cheat files can be dropped in later
+        let cheats_supported = match options.cheats {
+            Some(cheats) => {
+                instance.load_cheats(cheats)?;
+                true
+            }
+            None => instance.load_cheats(CheatSet::default()).is_ok(),
+        };
+
+        // Frames are handed over as raw pixels (after any pixel art scaling), so they get
+        // upscaled on this side
+        let scaler = options.scaler.unwrap_or_default();
+
+        let upscaler = Upscaler::new(
+            video.ctx().clone(),
+            scaler.output_size(system.default_output_resolution()),
+            output_resolution,
+            false,
+        );
+
+        let osd = Osd::new(video.ctx().clone());
+
+        // Screenshots and recordings go next to the ROM, unless configured otherwise
+        let capture_dir = options.capture_dir.unwrap_or_else(|| {
+            options
+                .rom_path
+                .parent()
+                .map(Path::to_path_buf)
+                .unwrap_or_default()
+        });
+
+        let capture_name = options
+            .rom_path
+            .file_stem()
+            .map_or("utopia".into(), |stem| stem.to_string_lossy().into_owned());
+
+        let sample_rate = instance.sample_rate();
+
+        let (mut audio, audio_sender) = AudioController::new(
+            audio_output,
+            instance.sample_rate(),
+            options.audio_latency,
+            options.fast_forward_audio.unwrap_or_default(),
+        );
 
-        let gamepad = Gamepad::new()?;
+        let gamepad = Gamepad::new(&options.input)?;
 
-        let joypad_state = JoypadState::default();
+        let turbo_frames = options
+            .input
+            .turbo_frames
+            .unwrap_or(turbo::DEFAULT_TURBO_FRAMES);
+
+        let (runner, frames) = Runner::new(instance, audio_sender, scaler, turbo_frames);
 
         audio.resume()?;
 
+        #[cfg(not(target_arch = "wasm32"))]
+        let emulator = Emulator::spawn(runner, sync, move || {
+            _ = proxy.send_event(AppEvent::Redraw);
+        });
+
+        #[cfg(target_arch = "wasm32")]
+        let _ = proxy;
+
         Ok(Self {
             video,
-            audio,
+            _audio: audio,
             gamepad,
-            joypad_state,
-            instance,
+            keyboard: Keyboard::new(&options.input),
+            keyboard_port: options
+                .input
+                .keyboard_port
+                .map_or(0, |port| port.clamp(1, MAX_PORTS) - 1),
+            input: options.input,
+            input_store: options.input_store,
+            rebind: None,
+            assigning_ports: false,
+            ports: Vec::new(),
+            fast_forward_speed: options
+                .fast_forward_speed
+                .unwrap_or(DEFAULT_FAST_FORWARD_SPEED)
+                .max(1.0),
+            fast_forward: false,
+            slow_motion: 0,
+            paused: false,
+            recording_macro: false,
+            capture_dir,
+            capture_name,
+            frame_rate: system_type.frame_rate(),
+            sample_rate,
+            recording: false,
+            cheats_supported,
+            upscaler,
+            osd,
+            frames,
             sync,
+            #[cfg(not(target_arch = "wasm32"))]
+            emulator,
+            #[cfg(target_arch = "wasm32")]
+            runner,
+        })
+    }
+
+    fn handle_key(
+        &mut self,
+        window_target: &EventLoopWindowTarget<AppEvent<impl MemoryMapper>>,
+        event: KeyEvent,
+    ) {
+        if let Some(rebind) = &mut self.rebind {
+            if event.state == ElementState::Pressed && !event.repeat {
+                match event.physical_key {
+                    PhysicalKey::Code(KeyCode::Escape) => rebind.skip(),
+                    PhysicalKey::Code(KeyCode::Backspace) => {
+                        self.rebind = None;
+                        self.video.window().set_title("Utopia");
+                        self.osd.message("Rebinding cancelled");
+                        self.video.window().request_redraw();
+                        return;
+                    }
+                    PhysicalKey::Code(key_code) => rebind.bind_key(key_code),
+                    _ => (),
+                }
+
+                self.update_rebind();
+            }
+
+            return;
+        }
+
+        if self.assigning_ports {
+            if event.state == ElementState::Pressed && !event.repeat {
+                match event.physical_key {
+                    PhysicalKey::Code(KeyCode::Escape | KeyCode::F3) => {
+                        self.assigning_ports = false;
+                        self.video.window().set_title("Utopia");
+                        self.video.window().request_redraw();
+                        return;
+                    }
+                    PhysicalKey::Code(..) => {
+                        self.keyboard_port = (self.keyboard_port + 1) % MAX_PORTS;
+                        info!("Keyboard moved to port {}", self.keyboard_port + 1);
+                    }
+                    _ => (),
+                }
+
+                self.update_port_title();
+            }
+
+            return;
+        }
+
+        // Fast-forward only lasts for as long as the key is held
+        if event.logical_key == Key::Named(NamedKey::Tab) && !event.repeat {
+            self.fast_forward = event.state == ElementState::Pressed;
+            self.update_speed();
+        }
+
+        if event.state == ElementState::Pressed {
+            match event.logical_key {
+                Key::Named(NamedKey::Escape) => window_target.exit(),
+                Key::Named(NamedKey::F6) if !event.repeat => {
+                    self.slow_motion = (self.slow_motion + 1) % SLOW_MOTION_SPEEDS.len();
+                    info!("Speed: {}%", SLOW_MOTION_SPEEDS[self.slow_motion] * 100.0);
+                    self.update_speed();
+                    self.osd.message(format!(
+                        "Speed: {}%",
+                        SLOW_MOTION_SPEEDS[self.slow_motion] * 100.0
+                    ));
+                }
+                Key::Named(NamedKey::F2) => {
+                    self.keyboard.release_all();
+                    self.rebind = Some(Rebind::new());
+                    self.update_rebind();
+                    return;
+                }
+                Key::Named(NamedKey::F3) => {
+                    self.keyboard.release_all();
+                    self.assigning_ports = true;
+                    self.update_port_title();
+                    return;
+                }
+                Key::Named(NamedKey::F4) if !event.repeat => {
+                    // Cycles through: off, FPS counter, FPS counter and input display
+                    let show_fps = !self.osd.show_fps() || !self.osd.show_input();
+                    let show_input = self.osd.show_fps() && !self.osd.show_input();
+                    self.osd.set_show_fps(show_fps);
+                    self.osd.set_show_input(show_input);
+                }
+                Key::Named(NamedKey::F5) if !event.repeat => self.toggle_recording(),
+                Key::Named(NamedKey::F7) if !event.repeat => {
+                    self.send_command(Command::RecordMacro);
+                    self.recording_macro = !self.recording_macro;
+
+                    if !self.recording_macro {
+                        self.osd.message("Macro recorded");
+                    }
+                }
+                Key::Named(NamedKey::F8) if !event.repeat => {
+                    self.send_command(Command::PlayMacro);
+
+                    if !self.recording_macro {
+                        self.osd.message("Playing macro");
+                    }
+                }
+                Key::Named(NamedKey::F9) if !event.repeat => {
+                    self.send_command(Command::TogglePause);
+                    self.paused = !self.paused;
+                }
+                Key::Named(NamedKey::F10) => {
+                    self.send_command(Command::FrameAdvance);
+                }
+                Key::Named(NamedKey::F11) => {
+                    self.video.toggle_full_screen(window_target).unwrap();
+                }
+                Key::Named(NamedKey::F12) if !event.repeat => self.save_screenshot(),
+                _ => (),
+            }
+
+            // Make sure the OSD still updates while the emulator isn't producing frames
+            self.video.window().request_redraw();
+        }
+
+        self.keyboard.handle_input(event);
+    }
+
+    fn handle_gamepad_events(&mut self) {
+        let pressed = self.gamepad.handle_events();
+
+        let Some(&(id, input)) = pressed.first() else {
+            return;
+        };
+
+        if let Some(rebind) = &mut self.rebind {
+            if let Some(uuid) = self.gamepad.uuid(id) {
+                rebind.bind_gamepad(uuid.to_owned(), input);
+                self.update_rebind();
+            }
+        } else if self.assigning_ports {
+            self.gamepad.next_port(id);
+            self.update_port_title();
+        }
+    }
+
+    fn update_port_title(&self) {
+        let assignments = self.gamepad.assignments();
+
+        let ports: Vec<String> = (0..MAX_PORTS)
+            .map(|port| {
+                let keyboard = (port == self.keyboard_port).then_some("Keyboard");
+
+                let gamepads = assignments
+                    .iter()
+                    .filter(|(gamepad_port, _)| *gamepad_port == port)
+                    .map(|(_, name)| *name);
+
+                let devices: Vec<&str> = keyboard.into_iter().chain(gamepads).collect();
+
+                if devices.is_empty() {
+                    format!("{}: -", port + 1)
+                } else {
+                    format!("{}: {}", port + 1, devices.join(", "))
+                }
+            })
+            .collect();
+
+        self.video.window().set_title(&format!(
+            "Ports {} (press a key or button to move that device, F3 to finish)",
+            ports.join(" | ")
+        ));
+    }
+
+    fn update_rebind(&mut self) {
+        let Some(rebind) = &self.rebind else {
+            return;
+        };
+
+        if !rebind.is_finished() {
+            self.video.window().set_title(&rebind.prompt());
+            return;
+        }
+
+        let changes = rebind.changes().clone();
+        self.rebind = None;
+        self.video.window().set_title("Utopia");
+
+        self.input.merge(&changes);
+        self.keyboard.set_bindings(&self.input);
+        self.gamepad.set_bindings(&self.input);
+
+        self.osd.message("Input bindings updated");
+
+        if let Some(input_store) = &self.input_store {
+            if let Err(err) = input_store.save(&changes) {
+                warn!("Failed to save input bindings: {}", err);
+                self.osd
+                    .error(format!("Failed to save input bindings: {}", err));
+            }
+        }
+
+        self.video.window().request_redraw();
+    }
+
+    fn send_input(&mut self) {
+        let mut ports = vec![None; MAX_PORTS];
+
+        let keyboard_port = ports[self.keyboard_port].insert(PortState::default());
+
+        for (button, pressed) in self.keyboard.buttons().iter().enumerate() {
+            if *pressed {
+                keyboard_port.press(button);
+            }
+        }
+
+        self.gamepad.update_ports(&mut ports);
+
+        // Nothing reaches the emulator while input is being configured
+        if self.rebind.is_some() || self.assigning_ports {
+            for port in ports.iter_mut().flatten() {
+                *port = PortState::default();
+            }
+        }
+
+        if ports == self.ports {
+            return;
+        }
+
+        self.osd
+            .set_input(ports.iter().enumerate().filter_map(|(index, port)| {
+                let port = port.as_ref()?;
+
+                let buttons: Vec<&str> = BUTTON_NAMES
+                    .iter()
+                    .enumerate()
+                    .filter(|(button, _)| port.pressed(*button))
+                    .map(|(_, name)| *name)
+                    .collect();
+
+                Some(format!("P{}: {}", index + 1, buttons.join(" ")))
+            }));
+
+        self.ports = ports.clone();
+        self.send_command(Command::Input(ports));
+    }
+
+    // Saves the last frame the emulator produced. This is done on the emulator thread, which
+    // still has the frame at its native resolution.
+    fn save_screenshot(&mut self) {
+        if self.frames.output_buffer().pixels.is_empty() {
+            self.osd.error("No frame available to capture");
+            return;
+        }
+
+        match capture::next_path(&self.capture_dir, &self.capture_name, &["png"]) {
+            Ok(path) => {
+                let path = capture::add_extension(&path, "png");
+                self.osd.message(format!("Saved {}", display_name(&path)));
+                self.send_command(Command::Screenshot(path));
+            }
+            Err(err) => {
+                warn!("Failed to save screenshot: {}", err);
+                self.osd
+                    .error(format!("Failed to save screenshot: {}", err));
+            }
+        }
+    }
+
+    fn toggle_recording(&mut self) {
+        if self.recording {
+            self.send_command(Command::StopRecording);
+            self.recording = false;
+            self.osd.message("Recording saved");
+            return;
+        }
+
+        let result = capture::next_path(&self.capture_dir, &self.capture_name, &["y4m", "wav"])
+            .and_then(|path| Recording::new(path, self.frame_rate, self.sample_rate));
+
+        match result {
+            Ok(recording) => {
+                self.osd
+                    .message(format!("Recording to {}", display_name(recording.path())));
+                self.send_command(Command::StartRecording(Box::new(recording)));
+                self.recording = true;
+            }
+            Err(err) => {
+                warn!("Failed to start recording: {}", err);
+                self.osd
+                    .error(format!("Failed to start recording: {}", err));
+            }
+        }
+    }
+
+    fn update_speed(&mut self) {
+        let speed = if self.fast_forward {
+            self.fast_forward_speed
+        } else {
+            SLOW_MOTION_SPEEDS[self.slow_motion]
+        };
+
+        self.send_command(Command::SetSpeed(speed));
+    }
+
+    fn send_command(&mut self, command: Command) {
+        #[cfg(not(target_arch = "wasm32"))]
+        self.emulator.send(command);
+
+        #[cfg(target_arch = "wasm32")]
+        self.runner.handle_command(command);
+    }
+
+    fn redraw(
+        &mut self,
+        window_target: &EventLoopWindowTarget<AppEvent<impl MemoryMapper>>,
+    ) -> Result<(), Box<dyn error::Error>> {
+        // Let the emulator get started on the next frame while we draw this one
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.sync == Sync::Video {
+            self.emulator.send(Command::Tick);
+        }
+
+        if self.frames.update() {
+            self.osd.count_frame();
+            let frame = self.frames.output_buffer();
+            self.upscaler.set_source_size(frame.size);
+            self.upscaler.update(&frame.pixels);
+        }
+
+        let status = self.status();
+        let upscaler = &self.upscaler;
+        let osd = &mut self.osd;
+
+        self.video.redraw(window_target, |canvas| {
+            upscaler.render(canvas);
+            osd.render(canvas, &status);
         })
     }
+
+    // Persistent OSD lines, shown for as long as the state they describe lasts
+    fn status(&self) -> Vec<String> {
+        let mut status = Vec::new();
+
+        if let Some(rebind) = &self.rebind {
+            status.push(format!(
+                "Press a key or button for '{}'",
+                rebind.button_name()
+            ));
+        }
+
+        if self.assigning_ports {
+            status.push("Assigning ports (F3 to finish)".into());
+        }
+
+        if self.paused {
+            status.push("Paused".into());
+        }
+
+        if self.fast_forward {
+            status.push("Fast forward".into());
+        } else if self.slow_motion != 0 {
+            status.push(format!(
+                "Slow motion: {}%",
+                SLOW_MOTION_SPEEDS[self.slow_motion] * 100.0
+            ));
+        }
+
+        if self.recording_macro {
+            status.push("Recording macro".into());
+        }
+
+        if self.recording {
+            status.push("Recording".into());
+        }
+
+        status
+    }
+}
+
+fn display_name(path: &Path) -> String {
+    path.file_name().map_or_else(
+        || path.display().to_string(),
+        |name| name.to_string_lossy().into_owned(),
+    )
+}
+
+// Patches are applied to the running game, while anything else is loaded as a new ROM
+enum DroppedFile<T: MemoryMapper> {
+    Reset(Box<ResetOptions<T>>),
+    Cheats(CheatSet),
+}
+
+fn load_dropped_file<T: MemoryMapper + Clone>(
+    current_options: &ResetOptions<T>,
+    path: &Path,
+) -> Result<DroppedFile<T>, Box<dyn error::Error>> {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    if extension == "cht" {
+        info!("Loading cheats from '{}'", path.display());
+        return Ok(DroppedFile::Cheats(CheatSet::from_cht(
+            &fs::read_to_string(path)?,
+        )?));
+    }
+
+    let data = fs::read(path)?;
+
+    if is_patch(&data) {
+        info!("Applying patch '{}'", path.display());
+        let mut options = current_options.clone();
+
+        // Dropping a patch that is already applied just restarts the game
+        if !options.patches.contains(&data) {
+            options.patches.push(data);
+        }
+
+        return Ok(DroppedFile::Reset(Box::new(options)));
+    }
+
+    let rom_loader = current_options
+        .rom_loader
+        .as_ref()
+        .ok_or("Loading ROMs by drag and drop is not supported")?;
+
+    info!("Loading ROM '{}'", path.display());
+
+    let mut options = rom_loader.load(path)?;
+
+    // The new game should be able to have files dropped onto it in the same way
+    options.rom_loader = Some(rom_loader.clone());
+
+    Ok(DroppedFile::Reset(Box::new(options)))
 }
 
 #[derive(Clone, Debug)]
 pub enum AppEvent<T: MemoryMapper> {
-    Reset(ResetOptions<T>),
+    Reset(Box<ResetOptions<T>>),
     UpdateViewport,
+    Redraw,
 }
 
 #[derive(Default)]
@@ -124,14 +767,14 @@ pub struct App<T: MemoryMapper + 'static> {
     proxy: Option<EventLoopProxy<AppEvent<T>>>,
 }
 
-impl<T: MemoryMapper> App<T> {
+impl<T: MemoryMapper + Clone + Send> App<T> {
     pub fn new() -> Self {
         Self { proxy: None }
     }
 
     pub fn reset(&mut self, options: ResetOptions<T>) -> Result<(), Box<dyn error::Error>> {
         if let Some(proxy) = &self.proxy {
-            proxy.send_event(AppEvent::Reset(options))?;
+            proxy.send_event(AppEvent::Reset(Box::new(options)))?;
         } else {
             start_event_loop(&mut self.proxy, options)?;
         }
@@ -148,79 +791,131 @@ impl<T: MemoryMapper> App<T> {
     }
 }
 
-fn start_event_loop<T: MemoryMapper>(
+fn start_event_loop<T: MemoryMapper + Clone + Send>(
     proxy: &mut Option<EventLoopProxy<AppEvent<T>>>,
     options: ResetOptions<T>,
 ) -> Result<(), Box<dyn error::Error>> {
     let event_loop = EventLoopBuilder::with_user_event().build()?;
 
-    *proxy = Some(event_loop.create_proxy());
+    let event_proxy = event_loop.create_proxy();
+
+    *proxy = Some(event_proxy.clone());
 
-    let mut state = ResetState::new(&event_loop, options)?;
+    // Kept so that dropped patches can be applied to the ROM that is currently running
+    let mut current_options = options.clone();
+
+    let mut state = ResetState::new(&event_loop, event_proxy.clone(), options)?;
 
     let event_loop_body = move |event, elwt: &EventLoopWindowTarget<_>| {
-        elwt.set_control_flow(ControlFlow::Poll);
+        elwt.set_control_flow(ControlFlow::Wait);
 
         match event {
             Event::WindowEvent { event, .. } => match event {
                 WindowEvent::CloseRequested => elwt.exit(),
-                WindowEvent::KeyboardInput { event, .. } => {
-                    if event.state == ElementState::Pressed {
-                        match event.logical_key {
-                            Key::Named(NamedKey::Escape) => elwt.exit(),
-                            Key::Named(NamedKey::F11) => {
-                                state.video.toggle_full_screen(elwt).unwrap();
-                            }
-                            _ => (),
-                        }
-                    }
-
-                    keyboard::handle_input(&mut state.joypad_state, event);
-                }
+                WindowEvent::KeyboardInput { event, .. } => state.handle_key(elwt, event),
                 WindowEvent::Moved(..) => {
-                    state.audio.resync();
+                    #[cfg(target_arch = "wasm32")]
+                    state.runner.resync();
                 }
                 WindowEvent::Resized(..) => {
                     state.video.on_window_size_changed().unwrap();
-                    state.audio.resync();
+                    #[cfg(target_arch = "wasm32")]
+                    state.runner.resync();
                 }
                 WindowEvent::ScaleFactorChanged { .. } => {
                     state.video.on_window_size_changed().unwrap();
-                    state.audio.resync();
+                    #[cfg(target_arch = "wasm32")]
+                    state.runner.resync();
+                }
+                WindowEvent::DroppedFile(path) => {
+                    match load_dropped_file(&current_options, &path) {
+                        Ok(DroppedFile::Reset(options)) => {
+                            _ = event_proxy.send_event(AppEvent::Reset(options));
+                        }
+                        Ok(DroppedFile::Cheats(cheats)) => {
+                            if state.cheats_supported {
+                                state
+                                    .osd
+                                    .message(format!("{} cheats enabled", cheats.cheats.len()));
+                                state.send_command(Command::LoadCheats(cheats.clone()));
+
+                                // Keep them enabled if a patch is dropped in later
+                                current_options.cheats = Some(cheats);
+                            } else {
+                                state.osd.error("Cheats are not supported for this system");
+                            }
+
+                            state.video.window().request_redraw();
+                        }
+                        Err(err) => {
+                            warn!("Failed to load '{}': {}", path.display(), err);
+                            state.osd.error(format!("Failed to load file: {}", err));
+                            state.video.window().request_redraw();
+                        }
+                    }
                 }
                 WindowEvent::RedrawRequested => {
-                    state
-                        .video
-                        .redraw(elwt, |canvas| state.instance.present(canvas))
-                        .unwrap();
+                    if let Err(err) = state.redraw(elwt) {
+                        warn!("Failed to redraw window: {}", err);
+                    }
                 }
                 _ => (),
             },
             Event::UserEvent(AppEvent::Reset(options)) => {
-                state = ResetState::new(elwt, options).unwrap();
+                // Make sure the old instance has stopped before the new one starts up
+                #[cfg(not(target_arch = "wasm32"))]
+                state.emulator.stop();
+
+                // If the new ROM fails to load, report it and carry on with the old one
+                match ResetState::new(elwt, event_proxy.clone(), (*options).clone()) {
+                    Ok(new_state) => {
+                        state = new_state;
+                        current_options = *options;
+                    }
+                    Err(err) => {
+                        #[cfg(not(target_arch = "wasm32"))]
+                        state.emulator.resume();
+
+                        warn!("Failed to load ROM: {}", err);
+                        state.osd.error(format!("Failed to load ROM: {}", err));
+                        state.video.window().request_redraw();
+                    }
+                }
             }
             Event::UserEvent(AppEvent::UpdateViewport) => state.video.update_viewport(elwt),
+            Event::UserEvent(AppEvent::Redraw) => state.video.window().request_redraw(),
             Event::AboutToWait => {
-                state.gamepad.handle_events(&mut state.joypad_state);
+                state.handle_gamepad_events();
+                state.send_input();
 
-                let run_frame = if state.sync == Sync::Audio {
-                    Instant::now() >= state.audio.sync_time()
-                } else {
-                    true
-                };
+                if state.osd.needs_redraw() {
+                    state.video.window().request_redraw();
+                }
 
-                if run_frame {
-                    state.instance.run_frame(&state.joypad_state);
+                // Without threads, the emulator has to run in between window events instead
+                #[cfg(target_arch = "wasm32")]
+                {
+                    let run_frame = if state.sync == Sync::Audio {
+                        Instant::now() >= state.runner.sync_time()
+                    } else {
+                        true
+                    };
+
+                    if run_frame {
+                        for _ in 0..state.runner.take_frames(state.sync) {
+                            state.runner.run_frame();
+                        }
 
-                    if let Some(queue) = state.instance.audio_queue() {
-                        state.audio.queue_samples(queue);
+                        state.video.window().request_redraw();
                     }
-                }
-
-                state.video.window().request_redraw();
 
-                if state.sync == Sync::Audio {
-                    elwt.set_control_flow(ControlFlow::WaitUntil(state.audio.sync_time()));
+                    if state.runner.paused() {
+                        elwt.set_control_flow(ControlFlow::Wait);
+                    } else if state.sync == Sync::Audio {
+                        elwt.set_control_flow(ControlFlow::WaitUntil(state.runner.sync_time()));
+                    } else {
+                        elwt.set_control_flow(ControlFlow::Poll);
+                    }
                 }
             }
             _ => (),
@@ -0,0 +1,126 @@
+use tracing::info;
+use utopia::JoypadState;
+
+type Frame = Vec<Option<JoypadState>>;
+
+// Records the input for each emulated frame, so that it can be replayed later on
+#[derive(Default)]
+pub struct Macros {
+    recording: Option<Vec<Frame>>,
+    recorded: Vec<Frame>,
+    playback: Option<usize>,
+}
+
+impl Macros {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn toggle_recording(&mut self) {
+        if let Some(frames) = self.recording.take() {
+            info!("Macro recorded ({} frames)", frames.len());
+            self.recorded = frames;
+        } else {
+            info!("Macro recording started");
+            self.recording = Some(Vec::new());
+            self.playback = None;
+        }
+    }
+
+    pub fn play(&mut self) {
+        if self.recording.is_some() || self.recorded.is_empty() {
+            return;
+        }
+
+        info!("Macro playback started");
+        self.playback = Some(0);
+    }
+
+    // Called once per emulated frame. Recorded buttons are combined with live input.
+    pub fn apply(&mut self, ports: &mut Frame) {
+        if let Some(frames) = &mut self.recording {
+            frames.push(ports.clone());
+            return;
+        }
+
+        let Some(index) = self.playback else {
+            return;
+        };
+
+        for (port, recorded) in ports.iter_mut().zip(&self.recorded[index]) {
+            let Some(recorded) = recorded else {
+                continue;
+            };
+
+            let Some(port) = port else {
+                continue;
+            };
+
+            for (pressed, recorded) in port.buttons.iter_mut().zip(recorded.buttons) {
+                *pressed |= recorded;
+            }
+
+            if recorded.axes != [0; 4] {
+                port.axes = recorded.axes;
+            }
+        }
+
+        self.playback = Some(index + 1).filter(|index| *index < self.recorded.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(buttons: &[usize], axes: [i32; 4]) -> Frame {
+        let mut joypad = JoypadState {
+            axes,
+            ..Default::default()
+        };
+
+        for &button in buttons {
+            joypad.buttons[button] = true;
+        }
+
+        vec![Some(joypad), None]
+    }
+
+    #[test]
+    fn playback_replays_recorded_frames() {
+        let mut macros = Macros::new();
+
+        macros.toggle_recording();
+
+        for mut ports in [frame(&[0], [0; 4]), frame(&[12], [0, -100, 0, 0])] {
+            macros.apply(&mut ports);
+        }
+
+        macros.toggle_recording();
+        macros.play();
+
+        // Live input is kept alongside the recording
+        let mut ports = frame(&[9], [0; 4]);
+        macros.apply(&mut ports);
+        assert_eq!(ports, frame(&[0, 9], [0; 4]));
+
+        let mut ports = frame(&[], [5, 5, 0, 0]);
+        macros.apply(&mut ports);
+        assert_eq!(ports, frame(&[12], [0, -100, 0, 0]));
+
+        // Playback stops at the end of the recording
+        let mut ports = frame(&[], [0; 4]);
+        macros.apply(&mut ports);
+        assert_eq!(ports, frame(&[], [0; 4]));
+    }
+
+    #[test]
+    fn nothing_to_play() {
+        let mut macros = Macros::new();
+        macros.play();
+
+        let mut ports = frame(&[1], [0; 4]);
+        macros.apply(&mut ports);
+        assert_eq!(ports, frame(&[1], [0; 4]));
+    }
+}
@@ -0,0 +1,276 @@
+use font::{FIRST_CHAR, GLYPHS, GLYPH_HEIGHT, GLYPH_WIDTH};
+use overlay::Overlay;
+use std::collections::VecDeque;
+use utopia::WgpuContext;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Duration, Instant};
+
+#[cfg(target_arch = "wasm32")]
+use web_time::{Duration, Instant};
+
+mod font;
+mod overlay;
+
+const MESSAGE_DURATION: Duration = Duration::from_secs(3);
+const ERROR_DURATION: Duration = Duration::from_secs(10);
+const MAX_MESSAGES: usize = 4;
+
+// Text is scaled up by whole pixels until the overlay is at least this tall
+const BASE_HEIGHT: u32 = 240;
+
+const CELL_WIDTH: usize = GLYPH_WIDTH + 1;
+const CELL_HEIGHT: usize = GLYPH_HEIGHT + 3;
+const MARGIN: usize = 2;
+
+const WHITE: [u8; 4] = [255, 255, 255, 255];
+const YELLOW: [u8; 4] = [255, 224, 64, 255];
+const RED: [u8; 4] = [255, 80, 80, 255];
+const BACKGROUND: [u8; 4] = [0, 0, 0, 160];
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Line {
+    text: String,
+    color: [u8; 4],
+    right_align: bool,
+}
+
+struct FpsCounter {
+    frames: u32,
+    start_time: Instant,
+    fps: f64,
+}
+
+pub struct Osd {
+    overlay: Overlay,
+    messages: VecDeque<(Line, Instant)>,
+    fps: Option<FpsCounter>,
+    input: Option<Vec<String>>,
+    lines: Vec<Line>,
+    pixels: Vec<u8>,
+}
+
+impl Osd {
+    pub fn new(ctx: WgpuContext) -> Self {
+        Self {
+            overlay: Overlay::new(ctx),
+            messages: VecDeque::new(),
+            fps: None,
+            input: None,
+            lines: Vec::new(),
+            pixels: Vec::new(),
+        }
+    }
+
+    pub fn message(&mut self, text: impl Into<String>) {
+        self.push(text.into(), WHITE, MESSAGE_DURATION);
+    }
+
+    pub fn error(&mut self, text: impl Into<String>) {
+        self.push(text.into(), RED, ERROR_DURATION);
+    }
+
+    pub fn show_fps(&self) -> bool {
+        self.fps.is_some()
+    }
+
+    pub fn set_show_fps(&mut self, show_fps: bool) {
+        self.fps = show_fps.then(|| FpsCounter {
+            frames: 0,
+            start_time: Instant::now(),
+            fps: 0.0,
+        });
+    }
+
+    pub fn show_input(&self) -> bool {
+        self.input.is_some()
+    }
+
+    pub fn set_show_input(&mut self, show_input: bool) {
+        self.input = show_input.then(Vec::new);
+    }
+
+    // One line per connected port, listing the buttons that are held
+    pub fn set_input(&mut self, ports: impl Iterator<Item = String>) {
+        if let Some(input) = &mut self.input {
+            *input = ports.collect();
+        }
+    }
+
+    pub fn count_frame(&mut self) {
+        let Some(counter) = &mut self.fps else {
+            return;
+        };
+
+        counter.frames += 1;
+
+        let elapsed = counter.start_time.elapsed();
+
+        if elapsed >= Duration::from_secs(1) {
+            counter.fps = counter.frames as f64 / elapsed.as_secs_f64();
+            counter.frames = 0;
+            counter.start_time = Instant::now();
+        }
+    }
+
+    // Returns true if a message has expired since the last time the OSD was drawn
+    pub fn needs_redraw(&self) -> bool {
+        let now = Instant::now();
+
+        self.messages
+            .iter()
+            .any(|(_, expiry_time)| *expiry_time <= now)
+    }
+
+    // Status lines are shown for as long as they keep being passed in
+    pub fn render(&mut self, canvas: &wgpu::Texture, status: &[String]) {
+        let now = Instant::now();
+
+        self.messages.retain(|(_, expiry_time)| *expiry_time > now);
+
+        let mut lines: Vec<Line> = status
+            .iter()
+            .map(|text| Line {
+                text: text.clone(),
+                color: YELLOW,
+                right_align: false,
+            })
+            .chain(self.messages.iter().map(|(line, _)| line.clone()))
+            .collect();
+
+        if let Some(counter) = &self.fps {
+            lines.push(Line {
+                text: format!("{:.1} FPS", counter.fps),
+                color: WHITE,
+                right_align: true,
+            });
+        }
+
+        if let Some(input) = &self.input {
+            lines.extend(input.iter().map(|text| Line {
+                text: text.clone(),
+                color: WHITE,
+                right_align: false,
+            }));
+        }
+
+        if lines.is_empty() {
+            self.lines.clear();
+            return;
+        }
+
+        let canvas_size = canvas.size();
+        let scale = (canvas_size.height / BASE_HEIGHT).max(1);
+        let width = canvas_size.width.div_ceil(scale);
+        let height = canvas_size.height.div_ceil(scale);
+
+        if lines != self.lines || !self.overlay.matches(width, height) {
+            self.pixels.clear();
+            self.pixels.resize((width * height * 4) as usize, 0);
+            draw_lines(&mut self.pixels, width as usize, height as usize, &lines);
+            self.overlay.update(width, height, &self.pixels);
+            self.lines = lines;
+        }
+
+        self.overlay.render(canvas, scale);
+    }
+
+    fn push(&mut self, text: String, color: [u8; 4], duration: Duration) {
+        if self.messages.len() >= MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+
+        let line = Line {
+            text,
+            color,
+            right_align: false,
+        };
+
+        self.messages.push_back((line, Instant::now() + duration));
+    }
+}
+
+fn draw_lines(pixels: &mut [u8], width: usize, height: usize, lines: &[Line]) {
+    let mut left_y = MARGIN;
+    let mut right_y = MARGIN;
+
+    for line in lines {
+        let text_width = line.text.chars().count() * CELL_WIDTH + 1;
+
+        let (x, y) = if line.right_align {
+            (width.saturating_sub(text_width + MARGIN), &mut right_y)
+        } else {
+            (MARGIN, &mut left_y)
+        };
+
+        if *y + CELL_HEIGHT > height {
+            continue;
+        }
+
+        fill_rect(
+            pixels,
+            width,
+            x,
+            *y,
+            text_width,
+            CELL_HEIGHT - 1,
+            BACKGROUND,
+        );
+
+        for (index, ch) in line.text.chars().enumerate() {
+            draw_glyph(
+                pixels,
+                width,
+                x + 1 + index * CELL_WIDTH,
+                *y + 1,
+                ch,
+                line.color,
+            );
+        }
+
+        *y += CELL_HEIGHT;
+    }
+}
+
+fn draw_glyph(pixels: &mut [u8], width: usize, x: usize, y: usize, ch: char, color: [u8; 4]) {
+    let index = (ch as usize)
+        .checked_sub(FIRST_CHAR as usize)
+        .filter(|index| *index < GLYPHS.len())
+        .unwrap_or('?' as usize - FIRST_CHAR as usize);
+
+    for (row, bits) in GLYPHS[index].iter().enumerate() {
+        for column in 0..GLYPH_WIDTH {
+            if (bits & (0x10 >> column)) != 0 {
+                set_pixel(pixels, width, x + column, y + row, color);
+            }
+        }
+    }
+}
+
+fn fill_rect(
+    pixels: &mut [u8],
+    width: usize,
+    x: usize,
+    y: usize,
+    rect_width: usize,
+    rect_height: usize,
+    color: [u8; 4],
+) {
+    for row in y..(y + rect_height) {
+        for column in x..(x + rect_width) {
+            set_pixel(pixels, width, column, row, color);
+        }
+    }
+}
+
+fn set_pixel(pixels: &mut [u8], width: usize, x: usize, y: usize, color: [u8; 4]) {
+    if x >= width {
+        return;
+    }
+
+    let offset = (y * width + x) * 4;
+
+    if let Some(pixel) = pixels.get_mut(offset..(offset + 4)) {
+        pixel.copy_from_slice(&color);
+    }
+}
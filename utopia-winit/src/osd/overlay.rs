@@ -0,0 +1,311 @@
+use utopia::WgpuContext;
+use wgpu::util::DeviceExt;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct Vertex {
+    position: [f32; 2],
+    tex_coords: [f32; 2],
+}
+
+impl Vertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2];
+
+    fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+#[rustfmt::skip]
+const INDICES: &[u16] = &[
+    0, 1, 2,
+    2, 1, 3,
+];
+
+// Draws an RGBA image over the top of whatever has already been rendered to the canvas
+pub struct Overlay {
+    ctx: WgpuContext,
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    render_pipeline: wgpu::RenderPipeline,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    clip_size: [u32; 4],
+}
+
+impl Overlay {
+    pub fn new(ctx: WgpuContext) -> Self {
+        let WgpuContext {
+            device,
+            output_format,
+            ..
+        } = &ctx;
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("OSD Texture Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("OSD Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("./overlay.wgsl").into()),
+        });
+
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("OSD Render Pipeline Layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("OSD Render Pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[Vertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: *output_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        let texture = create_texture(device, 1, 1);
+        let bind_group = create_bind_group(device, &bind_group_layout, &texture, &sampler);
+        let clip_size = [1; 4];
+        let clip_rect = create_clip_rect(clip_size);
+
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("OSD Vertex Buffer"),
+            contents: bytemuck::cast_slice(&clip_rect),
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("OSD Index Buffer"),
+            contents: bytemuck::cast_slice(INDICES),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        Self {
+            ctx,
+            texture,
+            bind_group,
+            bind_group_layout,
+            sampler,
+            render_pipeline,
+            vertex_buffer,
+            index_buffer,
+            clip_size,
+        }
+    }
+
+    pub fn matches(&self, width: u32, height: u32) -> bool {
+        self.texture.width() == width && self.texture.height() == height
+    }
+
+    pub fn update(&mut self, width: u32, height: u32, pixels: &[u8]) {
+        let WgpuContext { device, queue, .. } = &self.ctx;
+
+        if !self.matches(width, height) {
+            self.texture = create_texture(device, width, height);
+
+            self.bind_group = create_bind_group(
+                device,
+                &self.bind_group_layout,
+                &self.texture,
+                &self.sampler,
+            );
+        }
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width * 4),
+                rows_per_image: Some(height),
+            },
+            self.texture.size(),
+        );
+    }
+
+    // Each overlay pixel covers 'scale' canvas pixels in each direction
+    pub fn render(&mut self, canvas: &wgpu::Texture, scale: u32) {
+        let WgpuContext { device, queue, .. } = &self.ctx;
+
+        let clip_size = [
+            self.texture.width() * scale,
+            self.texture.height() * scale,
+            canvas.width(),
+            canvas.height(),
+        ];
+
+        if clip_size != self.clip_size {
+            let clip_rect = create_clip_rect(clip_size);
+            queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&clip_rect));
+            self.clip_size = clip_size;
+        }
+
+        let view = canvas.create_view(&Default::default());
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("OSD Render Encoder"),
+        });
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("OSD Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            render_pass.set_pipeline(&self.render_pipeline);
+            render_pass.set_bind_group(0, &self.bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            render_pass.draw_indexed(0..INDICES.len() as u32, 0, 0..1);
+        }
+
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+}
+
+fn create_texture(device: &wgpu::Device, width: u32, height: u32) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("OSD Texture"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    })
+}
+
+fn create_bind_group(
+    device: &wgpu::Device,
+    bind_group_layout: &wgpu::BindGroupLayout,
+    texture: &wgpu::Texture,
+    sampler: &wgpu::Sampler,
+) -> wgpu::BindGroup {
+    let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("OSD Texture Bind Group"),
+        layout: bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(sampler),
+            },
+        ],
+    })
+}
+
+// Anchored to the top left corner of the canvas, at whole-pixel scale
+fn create_clip_rect([width, height, target_width, target_height]: [u32; 4]) -> [Vertex; 4] {
+    let right = (width as f32 / target_width as f32) * 2.0 - 1.0;
+    let bottom = 1.0 - (height as f32 / target_height as f32) * 2.0;
+
+    [
+        // Top Left
+        Vertex {
+            position: [-1.0, 1.0],
+            tex_coords: [0.0, 0.0],
+        },
+        // Bottom Left
+        Vertex {
+            position: [-1.0, bottom],
+            tex_coords: [0.0, 1.0],
+        },
+        // Top Right
+        Vertex {
+            position: [right, 1.0],
+            tex_coords: [1.0, 0.0],
+        },
+        // Bottom Right
+        Vertex {
+            position: [right, bottom],
+            tex_coords: [1.0, 1.0],
+        },
+    ]
+}
@@ -0,0 +1,27 @@
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) tex_coords: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>
+};
+
+@vertex
+fn vs_main(model: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(model.position, 0.0, 1.0);
+    out.tex_coords = model.tex_coords;
+    return out;
+}
+
+@group(0) @binding(0)
+var t_overlay: texture_2d<f32>;
+@group(0) @binding(1)
+var s_overlay: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(t_overlay, s_overlay, in.tex_coords);
+}
@@ -0,0 +1,67 @@
+use super::input::{GamepadInput, InputConfig, BUTTON_NAMES};
+use std::error::Error;
+use std::fmt;
+use winit::keyboard::KeyCode;
+
+// Persists bindings chosen in the window. Only the changed buttons are passed in.
+pub trait InputStore: fmt::Debug + Send + std::marker::Sync {
+    fn save(&self, changes: &InputConfig) -> Result<(), Box<dyn Error>>;
+}
+
+// Steps through each button in turn, binding it to whatever gets pressed next
+#[derive(Default)]
+pub struct Rebind {
+    index: usize,
+    changes: InputConfig,
+}
+
+impl Rebind {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn button_name(&self) -> &'static str {
+        BUTTON_NAMES[self.index]
+    }
+
+    pub fn prompt(&self) -> String {
+        format!(
+            "Press a key or button for '{}' ({} of {}, Escape to skip, Backspace to cancel)",
+            self.button_name(),
+            self.index + 1,
+            BUTTON_NAMES.len()
+        )
+    }
+
+    pub fn changes(&self) -> &InputConfig {
+        &self.changes
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.index >= BUTTON_NAMES.len()
+    }
+
+    pub fn skip(&mut self) {
+        self.index += 1;
+    }
+
+    pub fn bind_key(&mut self, key_code: KeyCode) {
+        self.changes
+            .keyboard
+            .insert(BUTTON_NAMES[self.index].into(), vec![key_code]);
+
+        self.index += 1;
+    }
+
+    pub fn bind_gamepad(&mut self, uuid: String, input: GamepadInput) {
+        // Gamepad bindings are stored against the profile of the pad that was used
+        self.changes
+            .profiles
+            .entry(uuid)
+            .or_default()
+            .buttons
+            .insert(BUTTON_NAMES[self.index].into(), vec![input]);
+
+        self.index += 1;
+    }
+}
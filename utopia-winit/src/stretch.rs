@@ -0,0 +1,172 @@
+use std::f32::consts::PI;
+
+// Length of each overlapping segment (in frames). Segments overlap by half.
+const WINDOW_SIZE: usize = 1024;
+const HOP_SIZE: usize = WINDOW_SIZE / 2;
+
+// How far each segment may be moved from its ideal position to line up with the previous one
+const TOLERANCE: usize = 128;
+
+// Changes the length of audio without changing its pitch, using WSOLA (waveform similarity
+// overlap-add). Output segments are taken from the input at intervals scaled by the playback
+// speed, each nudged slightly so that its waveform lines up with the end of the previous segment.
+pub struct TimeStretch {
+    input: Vec<(f32, f32)>,
+    position: f64,
+    continuation: Option<usize>,
+    tail: Vec<(f32, f32)>,
+    window: Vec<f32>,
+}
+
+impl TimeStretch {
+    pub fn new() -> Self {
+        // Periodic Hann window, which sums to one when overlapped by half
+        let window = (0..WINDOW_SIZE)
+            .map(|index| 0.5 - 0.5 * (2.0 * PI * index as f32 / WINDOW_SIZE as f32).cos())
+            .collect();
+
+        Self {
+            // Padding lets the first segment be moved backwards as well as forwards
+            input: vec![(0.0, 0.0); TOLERANCE],
+            position: TOLERANCE as f64,
+            continuation: None,
+            tail: vec![(0.0, 0.0); HOP_SIZE],
+            window,
+        }
+    }
+
+    // Output length is roughly the input length divided by the speed
+    pub fn process(
+        &mut self,
+        speed: f64,
+        samples: impl Iterator<Item = (f32, f32)>,
+        output: &mut Vec<(f32, f32)>,
+    ) {
+        self.input.extend(samples);
+
+        while self.position as usize + TOLERANCE + WINDOW_SIZE <= self.input.len() {
+            let ideal = self.position as usize;
+
+            let start = match self.continuation {
+                Some(continuation) => self.best_match(continuation, ideal),
+                None => ideal,
+            };
+
+            let segment = &self.input[start..(start + WINDOW_SIZE)];
+            let (fade_in, fade_out) = self.window.split_at(HOP_SIZE);
+
+            for ((tail, sample), weight) in self.tail.iter().zip(segment).zip(fade_in) {
+                output.push((tail.0 + sample.0 * weight, tail.1 + sample.1 * weight));
+            }
+
+            for ((tail, sample), weight) in
+                self.tail.iter_mut().zip(&segment[HOP_SIZE..]).zip(fade_out)
+            {
+                *tail = (sample.0 * weight, sample.1 * weight);
+            }
+
+            self.continuation = Some(start + HOP_SIZE);
+            self.position += HOP_SIZE as f64 * speed;
+        }
+
+        // Discard input that no future segment can reach
+        let reachable =
+            (self.position as usize - TOLERANCE).min(self.continuation.unwrap_or(usize::MAX));
+        self.input.drain(..reachable);
+        self.position -= reachable as f64;
+
+        if let Some(continuation) = &mut self.continuation {
+            *continuation -= reachable;
+        }
+    }
+
+    // Finds the start position near 'ideal' whose waveform best matches what would have followed
+    // the previous segment
+    fn best_match(&self, continuation: usize, ideal: usize) -> usize {
+        let mono = |(left, right): (f32, f32)| left + right;
+        let template = &self.input[continuation..(continuation + HOP_SIZE)];
+
+        ((ideal - TOLERANCE)..=(ideal + TOLERANCE))
+            .map(|start| {
+                let candidate = &self.input[start..(start + HOP_SIZE)];
+
+                let similarity: f32 = template
+                    .iter()
+                    .zip(candidate)
+                    .map(|(&expected, &actual)| mono(expected) * mono(actual))
+                    .sum();
+
+                (start, similarity)
+            })
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map_or(ideal, |(start, _)| start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stretch(speed: f64, input_len: usize) -> Vec<(f32, f32)> {
+        let mut time_stretch = TimeStretch::new();
+        let mut output = Vec::new();
+
+        // 441 Hz at 44.1 kHz, fed in one frame's worth of audio at a time
+        let samples: Vec<(f32, f32)> = (0..input_len)
+            .map(|index| {
+                let sample = (2.0 * PI * index as f32 / 100.0).sin() * 0.5;
+                (sample, sample)
+            })
+            .collect();
+
+        for chunk in samples.chunks(735) {
+            time_stretch.process(speed, chunk.iter().copied(), &mut output);
+        }
+
+        output
+    }
+
+    // Counts rising zero crossings, skipping the start (where the output fades in)
+    fn cycles(output: &[(f32, f32)]) -> usize {
+        output[HOP_SIZE..]
+            .windows(2)
+            .filter(|pair| pair[0].0 < 0.0 && pair[1].0 >= 0.0)
+            .count()
+    }
+
+    #[test]
+    fn length_changes_but_pitch_does_not() {
+        for speed in [0.25, 0.5, 2.0, 4.0] {
+            let output = stretch(speed, 88200);
+            let expected_len = 88200.0 / speed;
+            let len = output.len() as f64;
+
+            assert!(
+                (len - expected_len).abs() < (WINDOW_SIZE + TOLERANCE) as f64 / speed.min(1.0),
+                "speed {}: {} samples",
+                speed,
+                len
+            );
+
+            let period = (output.len() - HOP_SIZE) as f64 / cycles(&output) as f64;
+            assert!(
+                (period - 100.0).abs() < 2.0,
+                "speed {}: period {}",
+                speed,
+                period
+            );
+        }
+    }
+
+    #[test]
+    fn steady_tone_keeps_its_level() {
+        let output = stretch(2.0, 44100);
+
+        let peak = output[HOP_SIZE..]
+            .iter()
+            .map(|(left, _)| left.abs())
+            .fold(0.0, f32::max);
+
+        assert!((peak - 0.5).abs() < 0.02, "{}", peak);
+    }
+}
@@ -0,0 +1,84 @@
+use super::input::{PortState, TURBO_BUTTONS};
+use utopia::JoypadState;
+
+pub const DEFAULT_TURBO_FRAMES: u32 = 2;
+
+// Counts emulated frames rather than wall-clock time, so the rate holds at any emulation speed
+pub struct Turbo {
+    frames: u32,
+    counters: Vec<[u32; TURBO_BUTTONS.len()]>,
+}
+
+impl Turbo {
+    pub fn new(frames: u32) -> Self {
+        Self {
+            frames: frames.max(1),
+            counters: Vec::new(),
+        }
+    }
+
+    // Called once per emulated frame
+    pub fn apply(&mut self, ports: &[Option<PortState>]) -> Vec<Option<JoypadState>> {
+        self.counters.resize(ports.len(), Default::default());
+
+        ports
+            .iter()
+            .zip(&mut self.counters)
+            .map(|(port, counters)| {
+                let Some(port) = port else {
+                    *counters = Default::default();
+                    return None;
+                };
+
+                let mut joypad_state = port.joypad.clone();
+
+                for (index, counter) in counters.iter_mut().enumerate() {
+                    if !port.turbo[index] {
+                        *counter = 0;
+                        continue;
+                    }
+
+                    // Always start with the button pressed, so short taps still register
+                    if (*counter / self.frames).is_multiple_of(2) {
+                        joypad_state.buttons[TURBO_BUTTONS[index]] = true;
+                    }
+
+                    *counter = counter.wrapping_add(1);
+                }
+
+                Some(joypad_state)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turbo_rate() {
+        let mut turbo = Turbo::new(2);
+
+        let mut port = PortState::default();
+        port.turbo[1] = true;
+        let ports = [Some(port), None];
+
+        let pressed: Vec<bool> = (0..8)
+            .map(|_| {
+                let output = turbo.apply(&ports);
+                assert!(output[1].is_none());
+                output[0].as_ref().unwrap().buttons[TURBO_BUTTONS[1]]
+            })
+            .collect();
+
+        assert_eq!(
+            pressed,
+            [true, true, false, false, true, true, false, false]
+        );
+
+        // Releasing the button resets the cycle
+        turbo.apply(&[Some(PortState::default())]);
+        assert!(turbo.apply(&ports)[0].as_ref().unwrap().buttons[TURBO_BUTTONS[1]]);
+    }
+}
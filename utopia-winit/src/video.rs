@@ -22,6 +22,7 @@ pub struct VideoController {
     source_size: PhysicalSize<u32>,
     prev_monitor_size: PhysicalSize<u32>,
     full_screen: bool,
+    scale: Option<u32>,
 }
 
 impl VideoController {
@@ -29,6 +30,7 @@ impl VideoController {
         window_target: &EventLoopWindowTarget<AppEvent<impl MemoryMapper>>,
         source_size: PhysicalSize<u32>,
         full_screen: bool,
+        scale: Option<u32>,
         vsync: bool,
         #[cfg(target_arch = "wasm32")] canvas: HtmlCanvasElement,
     ) -> Result<Self, Box<dyn Error>> {
@@ -38,7 +40,7 @@ impl VideoController {
         #[cfg(not(target_arch = "wasm32"))]
         let view_target = window_target;
 
-        let viewport = Viewport::new(view_target, source_size, full_screen);
+        let viewport = Viewport::new(view_target, source_size, full_screen, scale);
 
         let window_builder = WindowBuilder::new().with_title("Utopia");
 
@@ -130,6 +132,7 @@ impl VideoController {
             source_size,
             prev_monitor_size: monitor_size,
             full_screen,
+            scale,
         })
     }
 
@@ -156,7 +159,7 @@ impl VideoController {
         #[cfg(not(target_arch = "wasm32"))]
         let view_target = window_target;
 
-        let viewport = Viewport::new(view_target, self.source_size, self.full_screen);
+        let viewport = Viewport::new(view_target, self.source_size, self.full_screen, self.scale);
 
         if self.full_screen {
             self.window.set_fullscreen(Some(Fullscreen::Exclusive(
@@ -194,7 +197,7 @@ impl VideoController {
         #[cfg(not(target_arch = "wasm32"))]
         let view_target = window_target;
 
-        let viewport = Viewport::new(view_target, self.source_size, self.full_screen);
+        let viewport = Viewport::new(view_target, self.source_size, self.full_screen, self.scale);
 
         if !self.full_screen {
             if let Some(offset) = viewport.offset() {
@@ -210,7 +213,7 @@ impl VideoController {
     pub fn redraw(
         &mut self,
         window_target: &EventLoopWindowTarget<AppEvent<impl MemoryMapper>>,
-        draw_fn: impl Fn(&wgpu::Texture),
+        draw_fn: impl FnOnce(&wgpu::Texture),
     ) -> Result<(), Box<dyn Error>> {
         let monitor_size = self.window.current_monitor().unwrap().size();
 
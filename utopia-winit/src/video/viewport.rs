@@ -29,6 +29,7 @@ impl Viewport {
         window_target: &EventLoopWindowTarget<AppEvent<impl MemoryMapper>>,
         source_size: PhysicalSize<u32>,
         full_screen: bool,
+        max_scale: Option<u32>,
     ) -> Self {
         let monitor = window_target.available_monitors().next();
 
@@ -48,7 +49,7 @@ impl Viewport {
                 // HACK: Leave some space for the desktop environment
                 let usable_size = PhysicalSize::new(monitor_size.width, monitor_size.height - 80);
 
-                let target_size = upscale(source_size, usable_size);
+                let target_size = upscale(source_size, usable_size, max_scale);
                 let offset = center(target_size, usable_size);
 
                 Self {
@@ -71,13 +72,14 @@ impl Viewport {
         canvas: &HtmlCanvasElement,
         source_size: PhysicalSize<u32>,
         _full_screen: bool,
+        max_scale: Option<u32>,
     ) -> Self {
         let bounding_rect = canvas.parent_element().unwrap().get_bounding_client_rect();
 
         let bounding_element_size =
             PhysicalSize::new(bounding_rect.width() as u32, bounding_rect.height() as u32);
 
-        let target_size = upscale(source_size, bounding_element_size);
+        let target_size = upscale(source_size, bounding_element_size, max_scale);
 
         Self {
             size: target_size,
@@ -99,8 +101,12 @@ impl Viewport {
     }
 }
 
-fn upscale(source: PhysicalSize<u32>, target: PhysicalSize<u32>) -> PhysicalSize<u32> {
-    let scale = scale_factor(source, target);
+fn upscale(
+    source: PhysicalSize<u32>,
+    target: PhysicalSize<u32>,
+    max_scale: Option<u32>,
+) -> PhysicalSize<u32> {
+    let scale = scale_factor(source, target).min(max_scale.unwrap_or(u32::MAX));
     (source.width * scale, source.height * scale).into()
 }
 
@@ -0,0 +1,409 @@
+pub use memory::MemoryView;
+
+use crate::Error;
+use condition::{Condition, Flag, Group};
+use memory::MemRefTable;
+use serde::Deserialize;
+use std::collections::VecDeque;
+use tracing::{debug, info};
+
+mod condition;
+mod memory;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct AchievementDefinition {
+    #[serde(rename = "ID")]
+    pub id: u32,
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub points: u32,
+    #[serde(rename = "MemAddr")]
+    pub trigger: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct AchievementSet {
+    #[serde(default)]
+    pub title: String,
+    pub achievements: Vec<AchievementDefinition>,
+}
+
+impl AchievementSet {
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        serde_json::from_str(json).map_err(|err| err.to_string().into())
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AchievementEvent {
+    Unlocked { id: u32, title: String, points: u32 },
+}
+
+pub type AchievementQueue = VecDeque<AchievementEvent>;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum State {
+    // Conditions must be false at least once before the achievement can trigger
+    Waiting,
+    Active,
+    Unlocked,
+}
+
+struct Achievement {
+    definition: AchievementDefinition,
+    state: State,
+    groups: Vec<Group>,
+}
+
+#[derive(Default)]
+pub struct Achievements {
+    memrefs: MemRefTable,
+    achievements: Vec<Achievement>,
+    events: AchievementQueue,
+}
+
+impl Achievements {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(&mut self, set: AchievementSet) -> Result<(), Error> {
+        let mut memrefs = MemRefTable::new();
+        let mut achievements = Vec::with_capacity(set.achievements.len());
+
+        for definition in set.achievements {
+            let groups = condition::parse_trigger(&definition.trigger, &mut memrefs)
+                .map_err(|err| format!("Achievement {}: {}", definition.id, err))?;
+
+            achievements.push(Achievement {
+                definition,
+                state: State::Waiting,
+                groups,
+            });
+        }
+
+        info!("Loaded {} achievements", achievements.len());
+
+        self.memrefs = memrefs;
+        self.achievements = achievements;
+        self.events.clear();
+
+        Ok(())
+    }
+
+    pub fn events(&mut self) -> &mut AchievementQueue {
+        &mut self.events
+    }
+
+    pub fn evaluate(&mut self, memory: &dyn MemoryView) {
+        if self.achievements.is_empty() {
+            return;
+        }
+
+        self.memrefs.update(memory);
+
+        for achievement in &mut self.achievements {
+            if achievement.state == State::Unlocked {
+                continue;
+            }
+
+            let triggered = achievement.test(&self.memrefs);
+
+            match achievement.state {
+                State::Waiting => {
+                    if !triggered {
+                        debug!("Achievement {} now active", achievement.definition.id);
+                        achievement.state = State::Active;
+                    }
+                }
+                State::Active => {
+                    if triggered {
+                        let AchievementDefinition {
+                            id, title, points, ..
+                        } = &achievement.definition;
+
+                        debug!("Achievement {} triggered", id);
+                        achievement.state = State::Unlocked;
+
+                        self.events.push_back(AchievementEvent::Unlocked {
+                            id: *id,
+                            title: title.clone(),
+                            points: *points,
+                        });
+                    }
+                }
+                State::Unlocked => unreachable!(),
+            }
+        }
+    }
+}
+
+impl Achievement {
+    fn test(&mut self, memrefs: &MemRefTable) -> bool {
+        let mut reset = false;
+        let mut results = Vec::with_capacity(self.groups.len());
+
+        for group in &mut self.groups {
+            let paused = evaluate_group(group, memrefs, true, &mut reset);
+
+            let result = if paused {
+                false
+            } else {
+                evaluate_group(group, memrefs, false, &mut reset)
+            };
+
+            results.push(result);
+        }
+
+        if reset {
+            for group in &mut self.groups {
+                for condition in &mut group.conditions {
+                    condition.hits = 0;
+                }
+            }
+
+            return false;
+        }
+
+        let (core, alts) = results.split_first().unwrap();
+
+        *core && (alts.is_empty() || alts.iter().any(|result| *result))
+    }
+}
+
+// With 'pause_pass' set, only PauseIf conditions are evaluated, and the return value indicates
+// whether the group is paused. Otherwise, every other condition is evaluated and the return value
+// indicates whether the group as a whole is true.
+fn evaluate_group(
+    group: &mut Group,
+    memrefs: &MemRefTable,
+    pause_pass: bool,
+    reset: &mut bool,
+) -> bool {
+    let mut result = !pause_pass;
+    let mut start = 0;
+
+    for end in 0..group.conditions.len() {
+        if group.conditions[end].is_modifier() {
+            continue;
+        }
+
+        // A chain consists of any number of modifiers followed by a single 'real' condition
+        let chain = &mut group.conditions[start..=end];
+        start = end + 1;
+
+        let flag = chain[chain.len() - 1].flag;
+
+        if (flag == Flag::PauseIf) != pause_pass {
+            continue;
+        }
+
+        let value = evaluate_chain(chain, memrefs);
+
+        match flag {
+            Flag::PauseIf => result |= value,
+            Flag::ResetIf => *reset |= value,
+            _ => result &= value,
+        }
+    }
+
+    result
+}
+
+fn evaluate_chain(chain: &mut [Condition], memrefs: &MemRefTable) -> bool {
+    let mut add_value: u32 = 0;
+    let mut add_hits: u32 = 0;
+    let mut and_next = true;
+    let mut value = false;
+
+    for condition in chain {
+        let left = condition.left.value(memrefs);
+        let right = condition.right.value(memrefs);
+
+        match condition.flag {
+            Flag::AddSource => {
+                add_value = add_value.wrapping_add(condition.operator.modify(left, right));
+                continue;
+            }
+            Flag::SubSource => {
+                add_value = add_value.wrapping_sub(condition.operator.modify(left, right));
+                continue;
+            }
+            _ => (),
+        }
+
+        let raw = and_next
+            && condition
+                .operator
+                .compare(add_value.wrapping_add(left), right);
+
+        add_value = 0;
+
+        if raw && (condition.required_hits == 0 || condition.hits < condition.required_hits) {
+            condition.hits = condition.hits.saturating_add(1);
+        }
+
+        match condition.flag {
+            Flag::AndNext => and_next = raw,
+            Flag::AddHits => {
+                add_hits += condition.hits;
+                and_next = true;
+            }
+            _ => {
+                value = if condition.required_hits > 0 {
+                    (condition.hits + add_hits) >= condition.required_hits
+                } else {
+                    raw
+                };
+            }
+        }
+    }
+
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Ram([u8; 16]);
+
+    impl MemoryView for Ram {
+        fn peek(&self, address: u32) -> u8 {
+            self.0.get(address as usize).copied().unwrap_or(0)
+        }
+    }
+
+    fn load(trigger: &str) -> Achievements {
+        let mut achievements = Achievements::new();
+
+        achievements
+            .load(AchievementSet {
+                title: String::new(),
+                achievements: vec![AchievementDefinition {
+                    id: 1,
+                    title: "Test".into(),
+                    description: String::new(),
+                    points: 5,
+                    trigger: trigger.into(),
+                }],
+            })
+            .unwrap();
+
+        achievements
+    }
+
+    fn run(achievements: &mut Achievements, ram: &Ram) -> bool {
+        achievements.evaluate(ram);
+        !achievements
+            .events()
+            .drain(..)
+            .collect::<Vec<_>>()
+            .is_empty()
+    }
+
+    #[test]
+    fn waits_for_false_before_triggering() {
+        let mut achievements = load("0xH0000=1");
+        let mut ram = Ram([0; 16]);
+
+        ram.0[0] = 1;
+        assert!(!run(&mut achievements, &ram));
+
+        ram.0[0] = 0;
+        assert!(!run(&mut achievements, &ram));
+
+        ram.0[0] = 1;
+        assert!(run(&mut achievements, &ram));
+
+        // Only triggers once
+        assert!(!run(&mut achievements, &ram));
+    }
+
+    #[test]
+    fn delta_and_hit_counts() {
+        let mut achievements = load("0xH0000>d0xH0000.3.");
+        let mut ram = Ram([0; 16]);
+
+        assert!(!run(&mut achievements, &ram));
+
+        for value in 1..=2 {
+            ram.0[0] = value;
+            assert!(!run(&mut achievements, &ram));
+        }
+
+        ram.0[0] = 3;
+        assert!(run(&mut achievements, &ram));
+    }
+
+    #[test]
+    fn add_source_and_reset_if() {
+        let mut achievements = load("A:0xH0000_0xH0001=10.2._R:0xH0002=1");
+        let mut ram = Ram([0; 16]);
+
+        assert!(!run(&mut achievements, &ram));
+
+        ram.0[0] = 4;
+        ram.0[1] = 6;
+        assert!(!run(&mut achievements, &ram));
+
+        ram.0[2] = 1;
+        assert!(!run(&mut achievements, &ram));
+
+        ram.0[2] = 0;
+        assert!(!run(&mut achievements, &ram));
+        assert!(run(&mut achievements, &ram));
+    }
+
+    #[test]
+    fn pause_if_and_alt_groups() {
+        let mut achievements = load("0xH0000=1_P:0xH0001=1S0xH0002=1S0xH0003=1");
+        let mut ram = Ram([0; 16]);
+
+        assert!(!run(&mut achievements, &ram));
+
+        ram.0[0] = 1;
+        ram.0[1] = 1;
+        ram.0[3] = 1;
+        assert!(!run(&mut achievements, &ram));
+
+        ram.0[1] = 0;
+        assert!(run(&mut achievements, &ram));
+    }
+
+    #[test]
+    fn memory_sizes() {
+        let mut achievements = load("0x 0000=h1234_0xL0002=5_0xR0002=1_0xK0003=3");
+        let mut ram = Ram([0; 16]);
+
+        assert!(!run(&mut achievements, &ram));
+
+        ram.0[0] = 0x34;
+        ram.0[1] = 0x12;
+        ram.0[2] = 0x25;
+        ram.0[3] = 0x07;
+        assert!(run(&mut achievements, &ram));
+    }
+
+    #[test]
+    fn invalid_trigger() {
+        let mut achievements = Achievements::new();
+
+        let result = achievements.load(AchievementSet {
+            title: String::new(),
+            achievements: vec![AchievementDefinition {
+                id: 1,
+                title: "Test".into(),
+                description: String::new(),
+                points: 5,
+                trigger: "0xH0000?1".into(),
+            }],
+        });
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,424 @@
+use super::memory::{MemRef, MemRefTable, MemSize};
+use crate::Error;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Flag {
+    None,
+    ResetIf,
+    PauseIf,
+    AddSource,
+    SubSource,
+    AddHits,
+    AndNext,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ValueType {
+    Current,
+    Delta,
+    Prior,
+    Bcd,
+    Inverted,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Operand {
+    Constant(u32),
+    Memory(ValueType, usize),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    Multiply,
+    Divide,
+    BitwiseAnd,
+    None,
+}
+
+#[derive(Clone, Debug)]
+pub struct Condition {
+    pub flag: Flag,
+    pub left: Operand,
+    pub operator: Operator,
+    pub right: Operand,
+    pub required_hits: u32,
+    pub hits: u32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Group {
+    pub conditions: Vec<Condition>,
+}
+
+impl Operand {
+    pub fn value(&self, memrefs: &MemRefTable) -> u32 {
+        match *self {
+            Self::Constant(value) => value,
+            Self::Memory(value_type, index) => {
+                let memref = memrefs.get(index);
+
+                match value_type {
+                    ValueType::Current => memref.value,
+                    ValueType::Delta => memref.delta,
+                    ValueType::Prior => memref.prior,
+                    ValueType::Bcd => from_bcd(memref.value),
+                    ValueType::Inverted => !memref.value & memref.size.mask(),
+                }
+            }
+        }
+    }
+}
+
+impl Operator {
+    pub fn compare(self, left: u32, right: u32) -> bool {
+        match self {
+            Self::Equal => left == right,
+            Self::NotEqual => left != right,
+            Self::Less => left < right,
+            Self::LessEqual => left <= right,
+            Self::Greater => left > right,
+            Self::GreaterEqual => left >= right,
+            _ => left != 0,
+        }
+    }
+
+    pub fn modify(self, left: u32, right: u32) -> u32 {
+        match self {
+            Self::Multiply => left.wrapping_mul(right),
+            Self::Divide => left.checked_div(right).unwrap_or(0),
+            Self::BitwiseAnd => left & right,
+            _ => left,
+        }
+    }
+}
+
+impl Condition {
+    pub fn is_modifier(&self) -> bool {
+        matches!(
+            self.flag,
+            Flag::AddSource | Flag::SubSource | Flag::AddHits | Flag::AndNext
+        )
+    }
+}
+
+pub fn parse_trigger(source: &str, memrefs: &mut MemRefTable) -> Result<Vec<Group>, Error> {
+    let mut parser = Parser::new(source);
+    let mut groups = Vec::new();
+    let mut conditions = Vec::new();
+
+    // The first group is the 'core' group, and any after that are alternates. The core group
+    // may be empty.
+    if parser.is_empty() {
+        return Ok(vec![Group::default()]);
+    }
+
+    if parser.peek() == Some('S') {
+        parser.next();
+        groups.push(Group::default());
+    }
+
+    // 'S' also appears inside operands (e.g. '0xS1234'), so it only separates groups where a
+    // condition ends
+    loop {
+        conditions.push(parse_condition(&mut parser, memrefs)?);
+
+        match parser.next() {
+            None => break,
+            Some('_') => (),
+            Some('S') => groups.push(Group {
+                conditions: std::mem::take(&mut conditions),
+            }),
+            Some(_) => return Err(parser.error("Unexpected trailing characters")),
+        }
+    }
+
+    groups.push(Group { conditions });
+
+    Ok(groups)
+}
+
+fn parse_condition(parser: &mut Parser, memrefs: &mut MemRefTable) -> Result<Condition, Error> {
+    let flag = if parser.peek_at(1) == Some(':') {
+        let flag = match parser.next().unwrap().to_ascii_uppercase() {
+            'R' => Flag::ResetIf,
+            'P' => Flag::PauseIf,
+            'A' => Flag::AddSource,
+            'B' => Flag::SubSource,
+            'C' => Flag::AddHits,
+            'N' => Flag::AndNext,
+            ch => return Err(parser.error(&format!("Unsupported condition flag '{}'", ch))),
+        };
+
+        parser.next();
+        flag
+    } else {
+        Flag::None
+    };
+
+    let left = parser.operand(memrefs)?;
+
+    let operator = parser.operator()?;
+
+    let is_source = matches!(flag, Flag::AddSource | Flag::SubSource);
+
+    let (operator, right) = match operator {
+        Operator::None if is_source => (Operator::None, Operand::Constant(0)),
+        Operator::None => return Err(parser.error("Expected comparison operator")),
+        Operator::Multiply | Operator::Divide | Operator::BitwiseAnd if !is_source => {
+            return Err(parser.error("Modifier operators are only valid for AddSource/SubSource"))
+        }
+        operator => (operator, parser.operand(memrefs)?),
+    };
+
+    let required_hits = parser.hit_target()?;
+
+    Ok(Condition {
+        flag,
+        left,
+        operator,
+        right,
+        required_hits,
+        hits: 0,
+    })
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            chars: source.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn error(&self, message: &str) -> Error {
+        format!("{} at position {} in '{}'", message, self.pos, self.source).into()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.chars.len()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let ch = self.peek();
+        self.pos += 1;
+        ch
+    }
+
+    fn operand(&mut self, memrefs: &mut MemRefTable) -> Result<Operand, Error> {
+        let value_type = match self.peek() {
+            Some('d') => ValueType::Delta,
+            Some('p') => ValueType::Prior,
+            Some('b') => ValueType::Bcd,
+            Some('~') => ValueType::Inverted,
+            _ => ValueType::Current,
+        };
+
+        if value_type != ValueType::Current {
+            self.next();
+        }
+
+        let is_memory =
+            matches!(self.peek(), Some('0')) && matches!(self.peek_at(1), Some('x') | Some('X'));
+
+        if is_memory {
+            self.pos += 2;
+
+            let size = match self.peek().map(|ch| ch.to_ascii_uppercase()) {
+                Some('H') => Some(MemSize::Bits8),
+                Some('X') => Some(MemSize::Bits32),
+                Some('W') => Some(MemSize::Bits24),
+                Some('I') => Some(MemSize::Bits16Be),
+                Some('J') => Some(MemSize::Bits24Be),
+                Some('G') => Some(MemSize::Bits32Be),
+                Some('L') => Some(MemSize::LowerNibble),
+                Some('U') => Some(MemSize::UpperNibble),
+                Some('K') => Some(MemSize::BitCount),
+                Some(ch @ 'M'..='T') => Some(MemSize::Bit(ch as u8 - b'M')),
+                Some(' ') => Some(MemSize::Bits16),
+                _ => None,
+            };
+
+            // No size prefix at all also means 16-bit
+            let size = if let Some(size) = size {
+                self.next();
+                size
+            } else {
+                MemSize::Bits16
+            };
+
+            let address = self.hex()?;
+
+            let index = memrefs.intern(MemRef::new(address, size));
+
+            return Ok(Operand::Memory(value_type, index));
+        }
+
+        if value_type != ValueType::Current {
+            return Err(self.error("Value prefix is only valid for memory operands"));
+        }
+
+        let value = match self.peek() {
+            Some('h') | Some('H') => {
+                self.next();
+                self.hex()?
+            }
+            Some('-') => {
+                self.next();
+                (self.decimal()? as i32).wrapping_neg() as u32
+            }
+            _ => self.decimal()?,
+        };
+
+        Ok(Operand::Constant(value))
+    }
+
+    fn operator(&mut self) -> Result<Operator, Error> {
+        let Some(ch) = self.peek() else {
+            return Ok(Operator::None);
+        };
+
+        let operator = match (ch, self.peek_at(1)) {
+            ('=', Some('=')) => {
+                self.pos += 1;
+                Operator::Equal
+            }
+            ('=', _) => Operator::Equal,
+            ('!', Some('=')) => {
+                self.pos += 1;
+                Operator::NotEqual
+            }
+            ('<', Some('=')) => {
+                self.pos += 1;
+                Operator::LessEqual
+            }
+            ('<', _) => Operator::Less,
+            ('>', Some('=')) => {
+                self.pos += 1;
+                Operator::GreaterEqual
+            }
+            ('>', _) => Operator::Greater,
+            ('*', _) => Operator::Multiply,
+            ('/', _) => Operator::Divide,
+            ('&', _) => Operator::BitwiseAnd,
+            // Conditions without an operator end at a hit target or a separator
+            ('.', _) | ('(', _) | ('_', _) | ('S', _) => return Ok(Operator::None),
+            _ => return Err(self.error("Invalid operator")),
+        };
+
+        self.next();
+
+        Ok(operator)
+    }
+
+    fn hit_target(&mut self) -> Result<u32, Error> {
+        let terminator = match self.peek() {
+            Some('.') => '.',
+            Some('(') => ')',
+            _ => return Ok(0),
+        };
+
+        self.next();
+
+        let hits = self.decimal()?;
+
+        if self.next() != Some(terminator) {
+            return Err(self.error("Unterminated hit target"));
+        }
+
+        Ok(hits)
+    }
+
+    fn hex(&mut self) -> Result<u32, Error> {
+        self.digits(16)
+    }
+
+    fn decimal(&mut self) -> Result<u32, Error> {
+        self.digits(10)
+    }
+
+    fn digits(&mut self, radix: u32) -> Result<u32, Error> {
+        let start = self.pos;
+        let mut value: u32 = 0;
+
+        while let Some(digit) = self.peek().and_then(|ch| ch.to_digit(radix)) {
+            value = value.wrapping_mul(radix).wrapping_add(digit);
+            self.next();
+        }
+
+        if self.pos == start {
+            return Err(self.error("Expected number"));
+        }
+
+        Ok(value)
+    }
+}
+
+fn from_bcd(value: u32) -> u32 {
+    let mut result = 0;
+    let mut multiplier = 1;
+    let mut remaining = value;
+
+    while remaining != 0 {
+        result += (remaining & 0x0f) * multiplier;
+        multiplier *= 10;
+        remaining >>= 4;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bit_operands_in_alt_groups() {
+        let mut memrefs = MemRefTable::new();
+        let groups =
+            parse_trigger("0xS0010=1S0xH0000=2S0xS0020=0_0xT0030=1", &mut memrefs).unwrap();
+
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[0].conditions.len(), 1);
+        assert_eq!(groups[1].conditions.len(), 1);
+        assert_eq!(groups[2].conditions.len(), 2);
+
+        let Operand::Memory(_, index) = groups[2].conditions[0].left else {
+            panic!("Expected memory operand");
+        };
+
+        let memref = memrefs.get(index);
+        assert_eq!(memref.address, 0x0020);
+        assert_eq!(memref.size, MemSize::Bit(6));
+    }
+
+    #[test]
+    fn empty_core_group() {
+        let mut memrefs = MemRefTable::new();
+        let groups = parse_trigger("S0xS0010=1", &mut memrefs).unwrap();
+
+        assert_eq!(groups.len(), 2);
+        assert!(groups[0].conditions.is_empty());
+        assert_eq!(groups[1].conditions.len(), 1);
+    }
+}
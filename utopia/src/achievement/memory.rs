@@ -0,0 +1,110 @@
+pub trait MemoryView {
+    fn peek(&self, address: u32) -> u8;
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum MemSize {
+    Bits8,
+    Bits16,
+    Bits24,
+    Bits32,
+    Bits16Be,
+    Bits24Be,
+    Bits32Be,
+    LowerNibble,
+    UpperNibble,
+    Bit(u8),
+    BitCount,
+}
+
+impl MemSize {
+    pub fn mask(self) -> u32 {
+        match self {
+            Self::Bits8 | Self::BitCount => 0xff,
+            Self::Bits16 | Self::Bits16Be => 0xffff,
+            Self::Bits24 | Self::Bits24Be => 0x00ff_ffff,
+            Self::Bits32 | Self::Bits32Be => 0xffff_ffff,
+            Self::LowerNibble | Self::UpperNibble => 0x0f,
+            Self::Bit(..) => 0x01,
+        }
+    }
+
+    fn read(self, memory: &dyn MemoryView, address: u32) -> u32 {
+        let byte = |offset: u32| memory.peek(address.wrapping_add(offset)) as u32;
+
+        match self {
+            Self::Bits8 => byte(0),
+            Self::Bits16 => byte(0) | (byte(1) << 8),
+            Self::Bits24 => byte(0) | (byte(1) << 8) | (byte(2) << 16),
+            Self::Bits32 => byte(0) | (byte(1) << 8) | (byte(2) << 16) | (byte(3) << 24),
+            Self::Bits16Be => (byte(0) << 8) | byte(1),
+            Self::Bits24Be => (byte(0) << 16) | (byte(1) << 8) | byte(2),
+            Self::Bits32Be => (byte(0) << 24) | (byte(1) << 16) | (byte(2) << 8) | byte(3),
+            Self::LowerNibble => byte(0) & 0x0f,
+            Self::UpperNibble => byte(0) >> 4,
+            Self::Bit(bit) => (byte(0) >> bit) & 1,
+            Self::BitCount => byte(0).count_ones(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MemRef {
+    pub address: u32,
+    pub size: MemSize,
+    pub value: u32,
+    pub delta: u32,
+    pub prior: u32,
+}
+
+impl MemRef {
+    pub fn new(address: u32, size: MemSize) -> Self {
+        Self {
+            address,
+            size,
+            value: 0,
+            delta: 0,
+            prior: 0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct MemRefTable {
+    memrefs: Vec<MemRef>,
+}
+
+impl MemRefTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, index: usize) -> &MemRef {
+        &self.memrefs[index]
+    }
+
+    pub fn intern(&mut self, memref: MemRef) -> usize {
+        let existing = self
+            .memrefs
+            .iter()
+            .position(|other| other.address == memref.address && other.size == memref.size);
+
+        existing.unwrap_or_else(|| {
+            self.memrefs.push(memref);
+            self.memrefs.len() - 1
+        })
+    }
+
+    pub fn update(&mut self, memory: &dyn MemoryView) {
+        for memref in &mut self.memrefs {
+            let value = memref.size.read(memory, memref.address);
+
+            if value != memref.value {
+                memref.prior = memref.value;
+            }
+
+            memref.delta = memref.value;
+            memref.value = value;
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use crate::Error;
+use std::collections::HashMap;
+use tracing::info;
+
+// Memory that cheats can write to. Addresses use the same memory map as achievements.
+pub trait MemoryPoke {
+    fn poke(&mut self, address: u32, value: u8);
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Cheat {
+    pub description: String,
+    pub writes: Vec<(u32, u8)>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CheatSet {
+    pub cheats: Vec<Cheat>,
+}
+
+impl CheatSet {
+    // Parses a RetroArch cheat file, keeping only the cheats that are enabled. Codes are RAM
+    // writes, either in the 'address:value' form (hex, joined by '+' for multiple writes) or as
+    // the address and value fields used by RetroArch's own cheat engine.
+    pub fn from_cht(source: &str) -> Result<Self, Error> {
+        let mut fields = HashMap::new();
+
+        for line in source.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(format!("Invalid line in cheat file: '{}'", line).into());
+            };
+
+            fields.insert(key.trim(), value.trim().trim_matches('"'));
+        }
+
+        let count: usize = fields
+            .get("cheats")
+            .ok_or("Cheat file has no 'cheats' count")?
+            .parse()
+            .map_err(|_| "Invalid 'cheats' count")?;
+
+        let mut cheats = Vec::new();
+
+        for index in 0..count {
+            let field = |name: &str| {
+                fields
+                    .get(format!("cheat{}_{}", index, name).as_str())
+                    .copied()
+            };
+
+            if field("enable") != Some("true") {
+                continue;
+            }
+
+            let description = field("desc").map_or_else(String::new, |desc| desc.to_string());
+
+            let writes = if field("handler") == Some("1") {
+                parse_handler_fields(field)
+            } else {
+                field("code")
+                    .ok_or("Cheat has no code")
+                    .map_err(Error::from)
+                    .and_then(parse_code)
+            }
+            .map_err(|err| format!("Cheat {} ('{}'): {}", index, description, err))?;
+
+            cheats.push(Cheat {
+                description,
+                writes,
+            });
+        }
+
+        Ok(Self { cheats })
+    }
+}
+
+#[derive(Default)]
+pub struct Cheats {
+    writes: Vec<(u32, u8)>,
+}
+
+impl Cheats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(&mut self, set: CheatSet) {
+        info!("Loaded {} cheats", set.cheats.len());
+
+        self.writes = set
+            .cheats
+            .into_iter()
+            .flat_map(|cheat| cheat.writes)
+            .collect();
+    }
+
+    // Called once per frame, so that the values stay in place even if the game changes them
+    pub fn apply(&self, memory: &mut dyn MemoryPoke) {
+        for &(address, value) in &self.writes {
+            memory.poke(address, value);
+        }
+    }
+}
+
+fn parse_code(code: &str) -> Result<Vec<(u32, u8)>, Error> {
+    code.split('+')
+        .map(|code| {
+            let invalid = || Error::from(format!("Unsupported cheat code '{}'", code));
+            let (address, value) = code.split_once(':').ok_or_else(invalid)?;
+            let address = u32::from_str_radix(address.trim(), 16).map_err(|_| invalid())?;
+            let value = u8::from_str_radix(value.trim(), 16).map_err(|_| invalid())?;
+            Ok((address, value))
+        })
+        .collect()
+}
+
+fn parse_handler_fields<'a>(
+    field: impl Fn(&str) -> Option<&'a str>,
+) -> Result<Vec<(u32, u8)>, Error> {
+    let number = |name: &str, default: Option<u32>| {
+        field(name)
+            .map(|value| value.parse().map_err(|_| format!("Invalid '{}'", name)))
+            .transpose()?
+            .or(default)
+            .ok_or_else(|| Error::from(format!("Missing '{}'", name)))
+    };
+
+    let address = number("address", None)?;
+    let value = number("value", None)?;
+
+    // RetroArch numbers search sizes from 1-bit (0) up to 32-bit (5)
+    let len = match number("memory_search_size", Some(3))? {
+        3 => 1,
+        4 => 2,
+        5 => 4,
+        _ => return Err("Only 8, 16 and 32-bit values are supported".into()),
+    };
+
+    let big_endian = field("big_endian") == Some("true");
+
+    Ok((0..len)
+        .map(|offset| {
+            let shift = if big_endian { len - 1 - offset } else { offset } * 8;
+            (address + offset, (value >> shift) as u8)
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Ram([u8; 16]);
+
+    impl MemoryPoke for Ram {
+        fn poke(&mut self, address: u32, value: u8) {
+            if let Some(byte) = self.0.get_mut(address as usize) {
+                *byte = value;
+            }
+        }
+    }
+
+    #[test]
+    fn parse_cht_file() {
+        let set = CheatSet::from_cht(
+            r#"
+            cheats = 3
+
+            cheat0_desc = "Infinite Lives"
+            cheat0_code = "0005:09+0006:01"
+            cheat0_enable = true
+
+            cheat1_desc = "Disabled"
+            cheat1_code = "0007:FF"
+            cheat1_enable = false
+
+            cheat2_desc = "Max Money"
+            cheat2_handler = "1"
+            cheat2_address = "8"
+            cheat2_value = "999"
+            cheat2_memory_search_size = "4"
+            cheat2_enable = true
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            set.cheats,
+            [
+                Cheat {
+                    description: "Infinite Lives".into(),
+                    writes: vec![(5, 0x09), (6, 0x01)],
+                },
+                Cheat {
+                    description: "Max Money".into(),
+                    writes: vec![(8, 0xe7), (9, 0x03)],
+                },
+            ]
+        );
+
+        let mut cheats = Cheats::new();
+        cheats.load(set);
+
+        let mut ram = Ram([0; 16]);
+        cheats.apply(&mut ram);
+        assert_eq!(ram.0[5..10], [0x09, 0x01, 0x00, 0xe7, 0x03]);
+    }
+
+    #[test]
+    fn unsupported_codes_are_rejected() {
+        let game_genie = "cheats = 1\ncheat0_code = \"SXIOPO\"\ncheat0_enable = true";
+        assert!(CheatSet::from_cht(game_genie).is_err());
+        assert!(CheatSet::from_cht("cheat0_code = \"0000:00\"").is_err());
+        assert_eq!(CheatSet::from_cht("cheats = 0").unwrap().cheats, []);
+    }
+}
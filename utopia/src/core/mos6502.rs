@@ -1,3 +1,4 @@
+use crate::util::state::{Snapshot, State};
 use std::fmt;
 use tracing::trace;
 
@@ -397,6 +398,24 @@ impl<T: Bus> Core<T> {
     }
 }
 
+impl<T: Bus + Snapshot> Snapshot for Core<T> {
+    fn snapshot(&mut self, state: &mut State) {
+        self.a.snapshot(state);
+        self.x.snapshot(state);
+        self.y.snapshot(state);
+        self.s.snapshot(state);
+        self.pc.snapshot(state);
+        self.flags.n.snapshot(state);
+        self.flags.v.snapshot(state);
+        self.flags.d.snapshot(state);
+        state.variant(&mut self.flags.i, &[IrqDisable::Clear, IrqDisable::Set]);
+        self.flags.z.snapshot(state);
+        self.flags.c.snapshot(state);
+        self.interrupt.snapshot(state);
+        self.bus.snapshot(state);
+    }
+}
+
 impl<T: Bus> fmt::Display for Core<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
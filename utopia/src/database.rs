@@ -0,0 +1,336 @@
+use crate::{Error, SystemType};
+use sha1_smol::Sha1;
+use std::sync::OnceLock;
+use tracing::{info, warn};
+
+const GAMES: &str = include_str!("database/games.txt");
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Mirroring {
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RomLayout {
+    LoRom,
+    HiRom,
+}
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Overrides {
+    pub mapper: Option<u8>,
+    pub mirroring: Option<Mirroring>,
+    pub battery: Option<bool>,
+    pub multicart: bool,
+    pub layout: Option<RomLayout>,
+}
+
+#[derive(Clone, Debug)]
+pub struct GameEntry {
+    pub system_type: SystemType,
+    pub crc32: u32,
+    pub sha1: Option<&'static str>,
+    pub name: &'static str,
+    pub overrides: Overrides,
+}
+
+pub fn lookup(system_type: SystemType, rom_data: &[u8]) -> Option<&'static GameEntry> {
+    let data = strip_header(system_type, rom_data);
+    let crc32 = crc32fast::hash(data);
+    let mut sha1 = None;
+
+    entries().iter().find(|entry| {
+        entry.system_type == system_type
+            && entry.crc32 == crc32
+            && entry.sha1.is_none_or(|expected| {
+                let actual = sha1.get_or_insert_with(|| Sha1::from(data).digest().to_string());
+                expected.eq_ignore_ascii_case(actual)
+            })
+    })
+}
+
+pub fn identify(system_type: SystemType, rom_data: &[u8]) -> Option<&'static GameEntry> {
+    let entry = lookup(system_type, rom_data);
+
+    match entry {
+        Some(entry) => info!("Game: {}", entry.name),
+        None => info!("Game not found in database"),
+    }
+
+    entry
+}
+
+pub fn overrides(system_type: SystemType, rom_data: &[u8]) -> Overrides {
+    let overrides =
+        identify(system_type, rom_data).map_or_else(Overrides::default, |entry| entry.overrides);
+
+    correct(system_type, rom_data, overrides)
+}
+
+// Fills in any fields not set by the database using heuristics for common header errors
+pub fn correct(system_type: SystemType, rom_data: &[u8], mut overrides: Overrides) -> Overrides {
+    match system_type {
+        SystemType::Nes => {
+            if overrides.mapper.is_none() && has_garbage_ines_header(rom_data) {
+                let mapper = rom_data[6] >> 4;
+                info!("Ignoring garbage in iNES header (using mapper {})", mapper);
+                overrides.mapper = Some(mapper);
+            }
+        }
+        SystemType::GameBoy => {
+            if !overrides.multicart && is_mbc1_multicart(rom_data) {
+                info!("Detected MBC1 multicart");
+                overrides.multicart = true;
+            }
+        }
+        _ => (),
+    }
+
+    overrides
+}
+
+// Old dumping tools left text such as 'DiskDude!' in bytes 7-15, which should all be zero in
+// iNES 1.0 headers. Byte 7 then holds nonsense in the upper nibble of the mapper number.
+fn has_garbage_ines_header(rom_data: &[u8]) -> bool {
+    rom_data.len() >= 16
+        && rom_data.starts_with(b"NES\x1a")
+        && (rom_data[7] & 0x0c) != 0x08
+        && rom_data[12..16].iter().any(|&byte| byte != 0)
+}
+
+// MBC1M carts report themselves as plain MBC1, but each 256 KiB block contains a separate game
+// with its own header. Check for a second copy of the boot logo at the start of the second game.
+fn is_mbc1_multicart(rom_data: &[u8]) -> bool {
+    const LOGO: std::ops::Range<usize> = 0x0104..0x0134;
+    const GAME_SIZE: usize = 0x0004_0000;
+
+    rom_data.len() == GAME_SIZE * 4
+        && matches!(rom_data[0x0147], 0x01..=0x03)
+        && rom_data[LOGO] == rom_data[(LOGO.start + GAME_SIZE)..(LOGO.end + GAME_SIZE)]
+}
+
+pub fn checksums(system_type: SystemType, rom_data: &[u8]) -> (u32, String) {
+    let data = strip_header(system_type, rom_data);
+    (crc32fast::hash(data), Sha1::from(data).digest().to_string())
+}
+
+pub fn strip_header(system_type: SystemType, rom_data: &[u8]) -> &[u8] {
+    let header_size = match system_type {
+        SystemType::Nes if rom_data.starts_with(b"NES\x1a") && rom_data.len() > 6 => {
+            let trainer_present = (rom_data[6] & 0x04) != 0;
+            16 + if trainer_present { 512 } else { 0 }
+        }
+        SystemType::Snes if rom_data.len() % 0x0400 == 0x0200 => 0x0200,
+        _ => 0,
+    };
+
+    rom_data.get(header_size..).unwrap_or_default()
+}
+
+fn entries() -> &'static [GameEntry] {
+    static ENTRIES: OnceLock<Vec<GameEntry>> = OnceLock::new();
+
+    ENTRIES.get_or_init(|| {
+        GAMES
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|(index, line)| {
+                parse_entry(line)
+                    .map_err(|err| warn!("Game database line {}: {}", index + 1, err))
+                    .ok()
+            })
+            .collect()
+    })
+}
+
+fn parse_entry(line: &'static str) -> Result<GameEntry, Error> {
+    let fields: Vec<&str> = line.split('|').collect();
+
+    let [system, crc32, sha1, name, overrides] = fields[..] else {
+        return Err("Expected 5 fields".into());
+    };
+
+    let system_type = match system {
+        "gb" => SystemType::GameBoy,
+        "gba" => SystemType::GameBoyAdvance,
+        "n64" => SystemType::Nintendo64,
+        "nes" => SystemType::Nes,
+        "sms" => SystemType::SegaMasterSystem,
+        "snes" => SystemType::Snes,
+        _ => return Err(format!("Unknown system '{}'", system).into()),
+    };
+
+    let crc32 = u32::from_str_radix(crc32, 16).map_err(|_| format!("Invalid CRC32 '{}'", crc32))?;
+
+    let sha1 = match sha1 {
+        "-" => None,
+        sha1 if sha1.len() == 40 && sha1.chars().all(|ch| ch.is_ascii_hexdigit()) => Some(sha1),
+        _ => return Err(format!("Invalid SHA-1 '{}'", sha1).into()),
+    };
+
+    Ok(GameEntry {
+        system_type,
+        crc32,
+        sha1,
+        name,
+        overrides: parse_overrides(overrides)?,
+    })
+}
+
+fn parse_overrides(overrides: &str) -> Result<Overrides, Error> {
+    let mut result = Overrides::default();
+
+    for item in overrides.split_whitespace() {
+        let Some((key, value)) = item.split_once('=') else {
+            return Err(format!("Invalid override '{}'", item).into());
+        };
+
+        let invalid = || Error::from(format!("Invalid value for '{}': '{}'", key, value));
+
+        let flag = || match value {
+            "0" => Ok(false),
+            "1" => Ok(true),
+            _ => Err(invalid()),
+        };
+
+        match key {
+            "mapper" => result.mapper = Some(value.parse().map_err(|_| invalid())?),
+            "mirroring" => {
+                result.mirroring = Some(match value {
+                    "h" => Mirroring::Horizontal,
+                    "v" => Mirroring::Vertical,
+                    _ => return Err(invalid()),
+                })
+            }
+            "battery" => result.battery = Some(flag()?),
+            "multicart" => result.multicart = flag()?,
+            "layout" => {
+                result.layout = Some(match value {
+                    "lorom" => RomLayout::LoRom,
+                    "hirom" => RomLayout::HiRom,
+                    _ => return Err(invalid()),
+                })
+            }
+            _ => return Err(format!("Unknown override '{}'", key).into()),
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn database_is_valid() {
+        for line in GAMES.lines() {
+            if !line.is_empty() && !line.starts_with('#') {
+                parse_entry(line).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn parse_overrides_line() {
+        let entry = parse_entry("nes|0000ABCD|-|Test Game|mapper=4 mirroring=v battery=1").unwrap();
+        assert_eq!(entry.system_type, SystemType::Nes);
+        assert_eq!(entry.crc32, 0xabcd);
+        assert_eq!(entry.name, "Test Game");
+
+        assert_eq!(
+            entry.overrides,
+            Overrides {
+                mapper: Some(4),
+                mirroring: Some(Mirroring::Vertical),
+                battery: Some(true),
+                ..Default::default()
+            }
+        );
+
+        assert!(parse_entry("nes|0000ABCD|-|Test Game|mapper=x").is_err());
+        assert!(parse_entry("gb|0000ABCD|-|Test Game").is_err());
+    }
+
+    // Appends four bytes to the data so that its CRC32 becomes the given value. CRC32 is linear
+    // over GF(2), so the effect of each appended bit can be combined to reach any target.
+    fn forge_crc32(data: &mut Vec<u8>, target: u32) {
+        let start = data.len();
+        data.extend_from_slice(&[0; 4]);
+        let base = crc32fast::hash(data);
+        let mut basis = [(0u32, 0u32); 32];
+
+        for bit in 0..32 {
+            data[start + bit / 8] ^= 1 << (bit % 8);
+            let mut delta = crc32fast::hash(data) ^ base;
+            let mut mask = 1u32 << bit;
+            data[start + bit / 8] ^= 1 << (bit % 8);
+
+            for pivot in (0..32).rev() {
+                if (delta & (1 << pivot)) == 0 {
+                    continue;
+                }
+
+                if basis[pivot].0 == 0 {
+                    basis[pivot] = (delta, mask);
+                    break;
+                }
+
+                delta ^= basis[pivot].0;
+                mask ^= basis[pivot].1;
+            }
+        }
+
+        let mut remaining = base ^ target;
+        let mut mask = 0;
+
+        for pivot in (0..32).rev() {
+            if (remaining & (1 << pivot)) != 0 {
+                remaining ^= basis[pivot].0;
+                mask ^= basis[pivot].1;
+            }
+        }
+
+        assert_eq!(remaining, 0);
+        data[start..].copy_from_slice(&mask.to_le_bytes());
+        assert_eq!(crc32fast::hash(&data[..]), target);
+    }
+
+    #[test]
+    fn overrides_apply_to_known_rom() {
+        // Header claims mapper 1 with horizontal mirroring and battery-backed RAM
+        let mut rom = b"NES\x1a\x02\x01\x12\x00".to_vec();
+        rom.resize(16, 0);
+        rom.resize(16 + 0x8000 + 0x2000 - 4, 0xea);
+
+        let mut body = rom.split_off(16);
+        forge_crc32(&mut body, 0x3337ec46);
+        rom.extend_from_slice(&body);
+
+        let entry = lookup(SystemType::Nes, &rom).unwrap();
+        assert_eq!(entry.name, "Super Mario Bros. (World)");
+
+        assert_eq!(
+            overrides(SystemType::Nes, &rom),
+            Overrides {
+                mapper: Some(0),
+                mirroring: Some(Mirroring::Vertical),
+                battery: Some(false),
+                ..Default::default()
+            }
+        );
+
+        // Same data for a different system should not match
+        assert_eq!(overrides(SystemType::GameBoy, &body), Overrides::default());
+    }
+
+    #[test]
+    fn hash_ignores_ines_header() {
+        let mut rom = b"NES\x1a\x01\x00\x04\x00".to_vec();
+        rom.resize(16 + 512, 0xff);
+        rom.extend_from_slice(&[1, 2, 3, 4]);
+        assert_eq!(strip_header(SystemType::Nes, &rom), [1, 2, 3, 4]);
+    }
+}
@@ -8,10 +8,20 @@
     clippy::single_match
 )]
 
+pub use achievement::{AchievementEvent, AchievementQueue, AchievementSet};
+pub use cheat::CheatSet;
+pub use patch::{apply_patch, is_patch};
 pub use system::{
-    create, AudioQueue, Instance, InstanceOptions, JoypadState, System, SystemOptions, SystemType,
+    create, AudioQueue, Instance, InstanceOptions, JoypadState, RomInfo, System, SystemOptions,
+    SystemType, JOYPAD_BUTTON_COUNT, MAX_PORTS,
 };
 
+pub use system::gb::{GbColorCorrection, GbPalette};
+pub use system::nes::NesPalette;
+pub use util::audio::AudioFilter;
+pub use util::ntsc::NtscFilter;
+pub use util::scaler::{FrameScaler, Scaler};
+pub use util::upscaler::Upscaler;
 pub use util::Size;
 
 use std::error;
@@ -26,6 +36,10 @@ pub mod core;
 #[cfg(not(feature = "cpu-tests"))]
 mod core;
 
+mod achievement;
+mod cheat;
+mod database;
+mod patch;
 mod system;
 mod util;
 
@@ -52,7 +66,7 @@ impl From<&str> for Error {
 
 impl error::Error for Error {}
 
-pub trait BiosLoader: fmt::Debug {
+pub trait BiosLoader: fmt::Debug + Send + Sync {
     fn load(&self, name: &str) -> Result<Vec<u8>, Error>;
 }
 
@@ -65,9 +79,9 @@ impl BiosLoader for DefaultBiosLoader {
     }
 }
 
-pub trait Mapped: MirrorableMut<Output = u8> {}
+pub trait Mapped: MirrorableMut<Output = u8> + Send {}
 
-impl<T: MirrorableMut<Output = u8>> Mapped for T {}
+impl<T: MirrorableMut<Output = u8> + Send> Mapped for T {}
 
 pub trait MemoryMapper: fmt::Debug {
     type Mapped: Mapped;
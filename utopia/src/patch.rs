@@ -0,0 +1,408 @@
+use crate::Error;
+use tracing::{debug, info};
+
+mod bps;
+mod ips;
+mod ups;
+
+const COPIER_HEADER_SIZE: usize = 0x0200;
+
+// Larger than any ROM for a supported system (N64 ROMs top out at 64 MiB). Sizes in patch
+// headers are checked against this before anything is allocated.
+const MAX_TARGET_SIZE: usize = 0x0800_0000;
+
+pub fn is_patch(data: &[u8]) -> bool {
+    [ips::MAGIC, bps::MAGIC, ups::MAGIC]
+        .iter()
+        .any(|magic| data.starts_with(magic))
+}
+
+pub fn apply_patch(rom_data: Vec<u8>, patch_data: &[u8]) -> Result<Vec<u8>, Error> {
+    if patch_data.starts_with(ips::MAGIC) {
+        info!("Applying IPS patch");
+
+        // IPS patches record neither the size nor the checksum of the ROM they were made for, so
+        // there's no way to tell whether a copier header is expected. The ROM is patched as-is.
+        ips::apply(rom_data, patch_data)
+    } else if patch_data.starts_with(bps::MAGIC) {
+        info!("Applying BPS patch");
+        let (source_size, source_crc) = bps::source(patch_data)?;
+        bps::apply(
+            select_source(&rom_data, source_size, source_crc)?,
+            patch_data,
+        )
+    } else if patch_data.starts_with(ups::MAGIC) {
+        info!("Applying UPS patch");
+        let (source_size, source_crc) = ups::source(patch_data)?;
+        ups::apply(
+            select_source(&rom_data, source_size, source_crc)?,
+            patch_data,
+        )
+    } else {
+        Err("Unrecognised patch format".into())
+    }
+}
+
+fn strip_copier_header(rom_data: &[u8]) -> Option<&[u8]> {
+    (rom_data.len() % 0x0400 == COPIER_HEADER_SIZE).then(|| &rom_data[COPIER_HEADER_SIZE..])
+}
+
+// A copier header is only removed if the ROM without it is exactly what the patch expects
+fn select_source(rom_data: &[u8], expected_size: usize, expected_crc: u32) -> Result<&[u8], Error> {
+    let matches =
+        |data: &[u8]| data.len() == expected_size && crc32fast::hash(data) == expected_crc;
+
+    if matches(rom_data) {
+        return Ok(rom_data);
+    }
+
+    if let Some(stripped) = strip_copier_header(rom_data).filter(|stripped| matches(stripped)) {
+        debug!("Removing copier header");
+        return Ok(stripped);
+    }
+
+    Err(format!(
+        "Source ROM checksum does not match patch (expected {:08X}, got {:08X})",
+        expected_crc,
+        crc32fast::hash(rom_data)
+    )
+    .into())
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], pos: usize) -> Self {
+        Self { data, pos }
+    }
+
+    fn pos(&self) -> usize {
+        self.pos
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let bytes = self
+            .pos
+            .checked_add(len)
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or("Unexpected end of patch file")?;
+
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    // Variable-length integer encoding shared by BPS and UPS
+    fn number(&mut self) -> Result<usize, Error> {
+        let mut value: usize = 0;
+        let mut shift: usize = 1;
+
+        loop {
+            let byte = self.byte()?;
+
+            value = (byte as usize & 0x7f)
+                .checked_mul(shift)
+                .and_then(|data| value.checked_add(data))
+                .ok_or("Invalid number in patch file")?;
+
+            if (byte & 0x80) != 0 {
+                return Ok(value);
+            }
+
+            shift = shift.checked_shl(7).ok_or("Invalid number in patch file")?;
+            value = value
+                .checked_add(shift)
+                .ok_or("Invalid number in patch file")?;
+        }
+    }
+}
+
+struct Footer {
+    source_crc: u32,
+    target_crc: u32,
+}
+
+// BPS and UPS share the same footer layout
+fn read_footer(patch_data: &[u8], header_size: usize) -> Result<Footer, Error> {
+    if patch_data.len() < header_size + 12 {
+        return Err("Patch file is too short".into());
+    }
+
+    let footer = &patch_data[(patch_data.len() - 12)..];
+    let crc = |index: usize| u32::from_le_bytes(footer[index..(index + 4)].try_into().unwrap());
+    let patch_crc = crc(8);
+
+    if crc32fast::hash(&patch_data[..(patch_data.len() - 4)]) != patch_crc {
+        return Err("Patch file is corrupt (checksum mismatch)".into());
+    }
+
+    Ok(Footer {
+        source_crc: crc(0),
+        target_crc: crc(4),
+    })
+}
+
+fn check_target_size(target_size: usize) -> Result<(), Error> {
+    if target_size > MAX_TARGET_SIZE {
+        return Err(format!("Patched ROM size is too large ({} bytes)", target_size).into());
+    }
+
+    Ok(())
+}
+
+fn verify_target(target: Vec<u8>, expected_crc: u32) -> Result<Vec<u8>, Error> {
+    let actual_crc = crc32fast::hash(&target);
+
+    if actual_crc != expected_crc {
+        return Err(format!(
+            "Patched ROM checksum does not match patch (expected {:08X}, got {:08X})",
+            expected_crc, actual_crc
+        )
+        .into());
+    }
+
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bps::{SOURCE_COPY, SOURCE_READ, TARGET_COPY, TARGET_READ};
+
+    fn encode_number(mut value: usize, out: &mut Vec<u8>) {
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+
+            if value == 0 {
+                out.push(byte | 0x80);
+                break;
+            }
+
+            out.push(byte);
+            value -= 1;
+        }
+    }
+
+    fn finish(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+        patch.extend_from_slice(&crc32fast::hash(source).to_le_bytes());
+        patch.extend_from_slice(&crc32fast::hash(target).to_le_bytes());
+        patch.extend_from_slice(&crc32fast::hash(&patch).to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn number_round_trip() {
+        for value in [0, 1, 0x7f, 0x80, 0x4000, 0x12345678] {
+            let mut data = Vec::new();
+            encode_number(value, &mut data);
+            assert_eq!(Reader::new(&data, 0).number().unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn detect_patch_formats() {
+        assert!(is_patch(b"PATCH\x00\x00\x01"));
+        assert!(is_patch(b"BPS1\x04\x04"));
+        assert!(is_patch(b"UPS1\x04\x04"));
+        assert!(!is_patch(b"NES\x1a"));
+        assert!(!is_patch(b""));
+    }
+
+    #[test]
+    fn ips_extends_rom() {
+        let mut patch = b"PATCH".to_vec();
+        patch.extend_from_slice(&[0x00, 0x00, 0x01, 0x00, 0x02, 0xaa, 0xbb]);
+        patch.extend_from_slice(&[0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x03, 0xcc]);
+        patch.extend_from_slice(b"EOF");
+
+        let patched = apply_patch(vec![0x11; 4], &patch).unwrap();
+        assert_eq!(
+            patched,
+            [0x11, 0xaa, 0xbb, 0x11, 0x00, 0x00, 0xcc, 0xcc, 0xcc]
+        );
+    }
+
+    #[test]
+    fn ups_applies_and_validates() {
+        let source = [0x01, 0x02, 0x03, 0x04];
+        let target = [0x01, 0x12, 0x03, 0x04, 0x05];
+
+        let mut patch = b"UPS1".to_vec();
+        encode_number(source.len(), &mut patch);
+        encode_number(target.len(), &mut patch);
+        encode_number(1, &mut patch);
+        patch.extend_from_slice(&[0x10, 0x00]);
+        encode_number(1, &mut patch);
+        patch.extend_from_slice(&[0x05, 0x00]);
+        let patch = finish(patch, &source, &target);
+
+        assert_eq!(apply_patch(source.to_vec(), &patch).unwrap(), target);
+        assert!(apply_patch(vec![0xff; 4], &patch).is_err());
+    }
+
+    #[test]
+    fn bps_applies_and_validates() {
+        let source = [0x01, 0x02, 0x03, 0x04];
+        let target = [0x01, 0x02, 0x09, 0x09, 0x09, 0x03, 0x04];
+
+        let mut patch = b"BPS1".to_vec();
+        encode_number(source.len(), &mut patch);
+        encode_number(target.len(), &mut patch);
+        encode_number(0, &mut patch);
+        encode_number((1 << 2) | SOURCE_READ, &mut patch);
+        encode_number(TARGET_READ, &mut patch);
+        patch.push(0x09);
+        encode_number((1 << 2) | TARGET_COPY, &mut patch);
+        encode_number(2 << 1, &mut patch);
+        encode_number((1 << 2) | SOURCE_COPY, &mut patch);
+        encode_number(2 << 1, &mut patch);
+        let patch = finish(patch, &source, &target);
+
+        assert_eq!(apply_patch(source.to_vec(), &patch).unwrap(), target);
+        assert!(apply_patch(vec![0xff; 4], &patch).is_err());
+    }
+
+    #[test]
+    fn bps_strips_copier_header() {
+        let source: Vec<u8> = (0..0x0400).map(|index| index as u8).collect();
+        let mut target = source.clone();
+        target.push(0x09);
+
+        let mut patch = b"BPS1".to_vec();
+        encode_number(source.len(), &mut patch);
+        encode_number(target.len(), &mut patch);
+        encode_number(0, &mut patch);
+        encode_number(((source.len() - 1) << 2) | SOURCE_READ, &mut patch);
+        encode_number(TARGET_READ, &mut patch);
+        patch.push(0x09);
+        let patch = finish(patch, &source, &target);
+
+        let mut headered = vec![0; COPIER_HEADER_SIZE];
+        headered.extend_from_slice(&source);
+
+        assert_eq!(apply_patch(headered, &patch).unwrap(), target);
+    }
+
+    #[test]
+    fn oversized_target_is_rejected() {
+        let source = [0x01, 0x02, 0x03, 0x04];
+
+        for magic in [bps::MAGIC, ups::MAGIC] {
+            let mut patch = magic.to_vec();
+            encode_number(source.len(), &mut patch);
+            encode_number(usize::MAX >> 8, &mut patch);
+            encode_number(0, &mut patch);
+            let patch = finish(patch, &source, &source);
+
+            let err = apply_patch(source.to_vec(), &patch).unwrap_err();
+            assert!(err.0.contains("too large"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn bps_output_is_limited_to_target_size() {
+        let source = [0x01, 0x02, 0x03, 0x04];
+        let target = [0x01, 0x01];
+
+        // Run-length encoding far more data than the header allows
+        let mut patch = b"BPS1".to_vec();
+        encode_number(source.len(), &mut patch);
+        encode_number(target.len(), &mut patch);
+        encode_number(0, &mut patch);
+        encode_number(SOURCE_READ, &mut patch);
+        encode_number((0x7fff_ffff << 2) | TARGET_COPY, &mut patch);
+        encode_number(0, &mut patch);
+        let patch = finish(patch, &source, &target);
+
+        assert!(apply_patch(source.to_vec(), &patch).is_err());
+    }
+
+    #[test]
+    fn malformed_bps_offsets_are_rejected() {
+        let source = [0x01, 0x02, 0x03, 0x04];
+        let target = [0x01, 0x02];
+
+        // Source copies that land far beyond either end of the source ROM
+        for offset in [(1 << 62) << 1, ((1 << 62) << 1) | 1, (usize::MAX >> 2) << 1] {
+            let mut patch = b"BPS1".to_vec();
+            encode_number(source.len(), &mut patch);
+            encode_number(target.len(), &mut patch);
+            encode_number(0, &mut patch);
+            encode_number((1 << 2) | SOURCE_COPY, &mut patch);
+            encode_number(offset, &mut patch);
+            let patch = finish(patch, &source, &target);
+
+            assert!(apply_patch(source.to_vec(), &patch).is_err());
+        }
+
+        // Metadata longer than the patch itself
+        let mut patch = b"BPS1".to_vec();
+        encode_number(source.len(), &mut patch);
+        encode_number(target.len(), &mut patch);
+        encode_number(usize::MAX >> 1, &mut patch);
+        let patch = finish(patch, &source, &target);
+
+        assert!(apply_patch(source.to_vec(), &patch).is_err());
+    }
+
+    #[test]
+    fn malformed_ups_offsets_are_rejected() {
+        let source = [0x01, 0x02, 0x03, 0x04];
+
+        // Two skips that would add up to more than the address space
+        let mut patch = b"UPS1".to_vec();
+        encode_number(source.len(), &mut patch);
+        encode_number(source.len(), &mut patch);
+
+        for _ in 0..2 {
+            encode_number(usize::MAX >> 1, &mut patch);
+            patch.push(0x00);
+        }
+
+        let patch = finish(patch, &source, &source);
+
+        let err = apply_patch(source.to_vec(), &patch).unwrap_err();
+        assert!(err.0.contains("beyond end"), "{}", err);
+    }
+
+    #[test]
+    fn copier_header_is_kept_unless_patch_expects_otherwise() {
+        let mut headered = vec![0; COPIER_HEADER_SIZE];
+        headered.extend((0..0x0400).map(|index| index as u8));
+
+        // IPS patches have nothing to check against, so offsets include the header
+        let mut patch = b"PATCH".to_vec();
+        patch.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x01, 0xaa]);
+        patch.extend_from_slice(b"EOF");
+
+        let patched = apply_patch(headered.clone(), &patch).unwrap();
+        assert_eq!(patched.len(), headered.len());
+        assert_eq!(patched[0], 0xaa);
+        assert_eq!(
+            patched[COPIER_HEADER_SIZE..],
+            headered[COPIER_HEADER_SIZE..]
+        );
+
+        // A BPS patch made for the headered ROM leaves the header in place
+        let mut target = headered.clone();
+        target.push(0x09);
+
+        let mut patch = b"BPS1".to_vec();
+        encode_number(headered.len(), &mut patch);
+        encode_number(target.len(), &mut patch);
+        encode_number(0, &mut patch);
+        encode_number(((headered.len() - 1) << 2) | SOURCE_READ, &mut patch);
+        encode_number(TARGET_READ, &mut patch);
+        patch.push(0x09);
+        let patch = finish(patch, &headered, &target);
+
+        assert_eq!(apply_patch(headered, &patch).unwrap(), target);
+    }
+}
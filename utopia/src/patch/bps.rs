@@ -0,0 +1,106 @@
+use super::Reader;
+use crate::Error;
+
+pub const MAGIC: &[u8] = b"BPS1";
+
+pub const SOURCE_READ: usize = 0;
+pub const TARGET_READ: usize = 1;
+pub const SOURCE_COPY: usize = 2;
+pub const TARGET_COPY: usize = 3;
+
+// Size and checksum of the ROM the patch was made for
+pub fn source(patch_data: &[u8]) -> Result<(usize, u32), Error> {
+    let footer = super::read_footer(patch_data, MAGIC.len())?;
+    let source_size = Reader::new(patch_data, MAGIC.len()).number()?;
+    Ok((source_size, footer.source_crc))
+}
+
+pub fn apply(source: &[u8], patch_data: &[u8]) -> Result<Vec<u8>, Error> {
+    let footer = super::read_footer(patch_data, MAGIC.len())?;
+    let end = patch_data.len() - 12;
+    let mut reader = Reader::new(&patch_data[..end], MAGIC.len());
+
+    let source_size = reader.number()?;
+    let target_size = reader.number()?;
+    let metadata_size = reader.number()?;
+    reader.bytes(metadata_size)?;
+
+    if source_size != source.len() {
+        return Err("Source ROM size does not match patch".into());
+    }
+
+    super::check_target_size(target_size)?;
+
+    let mut target: Vec<u8> = Vec::with_capacity(target_size);
+    let mut source_offset: usize = 0;
+    let mut target_offset: usize = 0;
+
+    while reader.pos() < end {
+        let data = reader.number()?;
+        let len = (data >> 2) + 1;
+
+        if len > target_size - target.len() {
+            return Err("Patch writes beyond end of target ROM".into());
+        }
+
+        match data & 3 {
+            SOURCE_READ => {
+                let pos = target.len();
+
+                target.extend_from_slice(
+                    source
+                        .get(pos..(pos + len))
+                        .ok_or("Patch reads beyond end of source ROM")?,
+                );
+            }
+            TARGET_READ => target.extend_from_slice(reader.bytes(len)?),
+            SOURCE_COPY => {
+                source_offset = relative_offset(source_offset, reader.number()?)?;
+                let source_end = source_offset
+                    .checked_add(len)
+                    .ok_or("Invalid offset in patch file")?;
+
+                target.extend_from_slice(
+                    source
+                        .get(source_offset..source_end)
+                        .ok_or("Patch reads beyond end of source ROM")?,
+                );
+
+                source_offset = source_end;
+            }
+            TARGET_COPY => {
+                target_offset = relative_offset(target_offset, reader.number()?)?;
+
+                // The regions may overlap (this is used for run-length encoding), so copy
+                // byte-by-byte
+                for _ in 0..len {
+                    let value = *target
+                        .get(target_offset)
+                        .ok_or("Patch reads beyond end of target ROM")?;
+
+                    target.push(value);
+                    target_offset += 1;
+                }
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    if target.len() != target_size {
+        return Err("Patched ROM size does not match patch".into());
+    }
+
+    super::verify_target(target, footer.target_crc)
+}
+
+fn relative_offset(offset: usize, data: usize) -> Result<usize, Error> {
+    let delta = data >> 1;
+
+    let result = if (data & 1) != 0 {
+        offset.checked_sub(delta)
+    } else {
+        offset.checked_add(delta)
+    };
+
+    result.ok_or_else(|| "Invalid offset in patch file".into())
+}
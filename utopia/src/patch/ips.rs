@@ -0,0 +1,45 @@
+use super::Reader;
+use crate::Error;
+
+pub const MAGIC: &[u8] = b"PATCH";
+
+const EOF_MARKER: &[u8] = b"EOF";
+
+pub fn apply(mut rom_data: Vec<u8>, patch_data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut reader = Reader::new(patch_data, MAGIC.len());
+
+    loop {
+        let offset = reader.bytes(3)?;
+
+        if offset == EOF_MARKER {
+            break;
+        }
+
+        let offset = u32::from_be_bytes([0, offset[0], offset[1], offset[2]]) as usize;
+        let size = u16::from_be_bytes(reader.bytes(2)?.try_into().unwrap()) as usize;
+
+        if size == 0 {
+            let count = u16::from_be_bytes(reader.bytes(2)?.try_into().unwrap()) as usize;
+            let value = reader.byte()?;
+            resize_to_fit(&mut rom_data, offset + count);
+            rom_data[offset..(offset + count)].fill(value);
+        } else {
+            let data = reader.bytes(size)?;
+            resize_to_fit(&mut rom_data, offset + size);
+            rom_data[offset..(offset + size)].copy_from_slice(data);
+        }
+    }
+
+    // Some patches follow the EOF marker with the size to truncate the ROM to
+    if let Ok(size) = reader.bytes(3) {
+        rom_data.truncate(u32::from_be_bytes([0, size[0], size[1], size[2]]) as usize);
+    }
+
+    Ok(rom_data)
+}
+
+fn resize_to_fit(rom_data: &mut Vec<u8>, end: usize) {
+    if end > rom_data.len() {
+        rom_data.resize(end, 0);
+    }
+}
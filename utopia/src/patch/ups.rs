@@ -0,0 +1,58 @@
+use super::Reader;
+use crate::Error;
+
+pub const MAGIC: &[u8] = b"UPS1";
+
+// Size and checksum of the ROM the patch was made for
+pub fn source(patch_data: &[u8]) -> Result<(usize, u32), Error> {
+    let footer = super::read_footer(patch_data, MAGIC.len())?;
+    let source_size = Reader::new(patch_data, MAGIC.len()).number()?;
+    Ok((source_size, footer.source_crc))
+}
+
+pub fn apply(source: &[u8], patch_data: &[u8]) -> Result<Vec<u8>, Error> {
+    let footer = super::read_footer(patch_data, MAGIC.len())?;
+    let end = patch_data.len() - 12;
+    let mut reader = Reader::new(&patch_data[..end], MAGIC.len());
+
+    let source_size = reader.number()?;
+    let target_size = reader.number()?;
+
+    if source_size != source.len() {
+        return Err("Source ROM size does not match patch".into());
+    }
+
+    super::check_target_size(target_size)?;
+
+    let mut target = source.to_vec();
+    target.resize(target_size, 0);
+
+    let mut pos: usize = 0;
+
+    while reader.pos() < end {
+        pos = pos
+            .checked_add(reader.number()?)
+            .ok_or("Invalid offset in patch file")?;
+
+        if pos > target.len() {
+            return Err("Patch writes beyond end of target ROM".into());
+        }
+
+        loop {
+            let value = reader.byte()?;
+
+            if value == 0 {
+                pos += 1;
+                break;
+            }
+
+            *target
+                .get_mut(pos)
+                .ok_or("Patch writes beyond end of target ROM")? ^= value;
+
+            pos += 1;
+        }
+    }
+
+    super::verify_target(target, footer.target_crc)
+}
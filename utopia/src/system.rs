@@ -1,7 +1,13 @@
 use super::WgpuContext;
+use crate::database::{self, Overrides};
+use crate::util::audio::AudioFilter;
+use crate::util::ntsc::NtscFilter;
 use crate::util::size::Size;
-use crate::{BiosLoader, Error, MemoryMapper};
+use crate::{AchievementQueue, AchievementSet, BiosLoader, CheatSet, Error, MemoryMapper};
+use gb::{GbColorCorrection, GbPalette};
+use nes::NesPalette;
 use std::collections::VecDeque;
+use std::fmt;
 use std::path::Path;
 
 pub mod gb;
@@ -13,12 +19,35 @@ pub mod snes;
 
 #[derive(Clone, Default, Debug, Eq, PartialEq)]
 pub struct JoypadState {
-    pub buttons: [bool; 17],
+    pub buttons: [bool; JOYPAD_BUTTON_COUNT],
     pub axes: [i32; 4],
 }
 
+// Standard gamepad layout, as used by the W3C Gamepad API
+pub const JOYPAD_BUTTON_COUNT: usize = 17;
+
+// The most controller ports on any supported system (N64, or SNES with a multitap)
+pub const MAX_PORTS: usize = 4;
+
 pub type AudioQueue = VecDeque<(f32, f32)>;
 
+const GB_LOGO: [u8; 48] = [
+    0xce, 0xed, 0x66, 0x66, 0xcc, 0x0d, 0x00, 0x0b, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0c, 0x00, 0x0d,
+    0x00, 0x08, 0x11, 0x1f, 0x88, 0x89, 0x00, 0x0e, 0xdc, 0xcc, 0x6e, 0xe6, 0xdd, 0xdd, 0xd9, 0x99,
+    0xbb, 0xbb, 0x67, 0x63, 0x6e, 0x0e, 0xec, 0xcc, 0xdd, 0xdc, 0x99, 0x9f, 0xbb, 0xb9, 0x33, 0x3e,
+];
+
+// The GBA logo is much longer, but the start of it is enough to tell it apart
+const GBA_LOGO_PREFIX: [u8; 8] = [0x24, 0xff, 0xae, 0x51, 0x69, 0x9a, 0xa2, 0x21];
+
+const N64_MAGIC: [[u8; 4]; 3] = [
+    [0x80, 0x37, 0x12, 0x40], // .z64 (big endian)
+    [0x37, 0x80, 0x40, 0x12], // .v64 (byte swapped)
+    [0x40, 0x12, 0x37, 0x80], // .n64 (little endian)
+];
+
+const SMS_HEADER_OFFSETS: [usize; 3] = [0x7ff0, 0x3ff0, 0x1ff0];
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum SystemType {
     GameBoy,
@@ -29,6 +58,82 @@ pub enum SystemType {
     Snes,
 }
 
+impl fmt::Display for SystemType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::GameBoy => write!(f, "Game Boy"),
+            Self::GameBoyAdvance => write!(f, "Game Boy Advance"),
+            Self::Nes => write!(f, "NES"),
+            Self::Nintendo64 => write!(f, "Nintendo 64"),
+            Self::SegaMasterSystem => write!(f, "Sega Master System"),
+            Self::Snes => write!(f, "SNES"),
+        }
+    }
+}
+
+impl SystemType {
+    pub fn detect(rom_data: &[u8], path_hint: Option<&Path>) -> Result<Self, Error> {
+        if let Some(system_type) = Self::detect_from_contents(rom_data) {
+            return Ok(system_type);
+        }
+
+        let Some(path) = path_hint else {
+            return Err("Could not determine system type from ROM contents".into());
+        };
+
+        path.try_into()
+    }
+
+    // Native (NTSC) refresh rate, in frames per second
+    pub fn frame_rate(&self) -> f64 {
+        match self {
+            Self::GameBoy | Self::GameBoyAdvance => 59.7275,
+            Self::Nes | Self::Snes => 60.0988,
+            Self::Nintendo64 => 60.0,
+            Self::SegaMasterSystem => 59.9227,
+        }
+    }
+
+    fn detect_from_contents(rom_data: &[u8]) -> Option<Self> {
+        let matches = |offset: usize, bytes: &[u8]| {
+            rom_data
+                .get(offset..(offset + bytes.len()))
+                .is_some_and(|slice| slice == bytes)
+        };
+
+        // Covers both iNES and NES 2.0
+        if matches(0, b"NES\x1a") {
+            return Some(Self::Nes);
+        }
+
+        if N64_MAGIC.iter().any(|magic| matches(0, magic)) {
+            return Some(Self::Nintendo64);
+        }
+
+        if matches(0x04, &GBA_LOGO_PREFIX) && matches(0xb2, &[0x96]) {
+            return Some(Self::GameBoyAdvance);
+        }
+
+        if matches(0x0104, &GB_LOGO) {
+            return Some(Self::GameBoy);
+        }
+
+        if SMS_HEADER_OFFSETS
+            .iter()
+            .any(|&offset| matches(offset, b"TMR SEGA"))
+        {
+            return Some(Self::SegaMasterSystem);
+        }
+
+        // SNES ROMs have no magic number, so this has to be done last
+        if snes::detect(rom_data) {
+            return Some(Self::Snes);
+        }
+
+        None
+    }
+}
+
 impl TryFrom<&Path> for SystemType {
     type Error = Error;
 
@@ -41,7 +146,7 @@ impl TryFrom<&Path> for SystemType {
         match extension.as_str() {
             "gb" | "gbc" => Ok(Self::GameBoy),
             "gba" => Ok(Self::GameBoyAdvance),
-            "n64" | "z64" => Ok(Self::Nintendo64),
+            "n64" | "v64" | "z64" => Ok(Self::Nintendo64),
             "nes" => Ok(Self::Nes),
             "sfc" | "smc" => Ok(Self::Snes),
             "sms" => Ok(Self::SegaMasterSystem),
@@ -50,33 +155,84 @@ impl TryFrom<&Path> for SystemType {
     }
 }
 
+#[derive(Clone, Debug)]
+pub struct RomInfo {
+    pub system_type: SystemType,
+    pub name: Option<String>,
+    pub crc32: u32,
+    pub sha1: String,
+    pub fields: Vec<(&'static str, String)>,
+    overrides: Overrides,
+}
+
+impl RomInfo {
+    fn new(system_type: SystemType, rom_data: &[u8]) -> Self {
+        let entry = database::lookup(system_type, rom_data);
+        let (crc32, sha1) = database::checksums(system_type, rom_data);
+
+        Self {
+            system_type,
+            name: entry.map(|entry| entry.name.to_owned()),
+            crc32,
+            sha1,
+            fields: Vec::new(),
+            overrides: database::correct(
+                system_type,
+                rom_data,
+                entry.map_or_else(Overrides::default, |entry| entry.overrides),
+            ),
+        }
+    }
+
+    fn add(&mut self, label: &'static str, value: impl fmt::Display) {
+        self.fields.push((label, value.to_string()));
+    }
+}
+
 pub struct SystemOptions<'a, T: MemoryMapper> {
     pub system_type: SystemType,
     pub bios_loader: &'a dyn BiosLoader,
     pub memory_mapper: &'a T,
     pub skip_boot: bool,
+    pub audio_filter: AudioFilter,
+    pub ntsc_filter: NtscFilter,
+    pub nes_palette: NesPalette,
+    pub gb_color_correction: GbColorCorrection,
+    pub gb_frame_blend: bool,
+    pub gb_palette: GbPalette,
 }
 
 pub trait System<T: MemoryMapper> {
     fn create_instance(&self, options: InstanceOptions) -> Result<Box<dyn Instance>, Error>;
     fn default_output_resolution(&self) -> Size;
+    fn rom_info(&self, rom_data: &[u8]) -> Result<RomInfo, Error>;
 
     fn default_sample_rate(&self) -> Option<u64> {
         None
     }
+
+    // Whether instances need a wgpu context to render at all (rather than just to present)
+    fn requires_wgpu_context(&self) -> bool {
+        false
+    }
 }
 
 #[derive(Debug)]
 pub struct InstanceOptions {
     pub rom_data: Vec<u8>,
-    pub wgpu_context: WgpuContext,
+    pub wgpu_context: Option<WgpuContext>,
     pub output_resolution: Size,
 }
 
-pub trait Instance {
-    fn run_frame(&mut self, joypad_state: &JoypadState);
+pub trait Instance: Send {
+    // One entry per controller port, where 'None' means nothing is plugged in
+    fn run_frame(&mut self, ports: &[Option<JoypadState>]);
     fn present(&self, canvas: &wgpu::Texture);
 
+    fn pixels(&self) -> Option<(&[u8], Size)> {
+        None
+    }
+
     fn sample_rate(&self) -> u64 {
         44100
     }
@@ -84,6 +240,57 @@ pub trait Instance {
     fn audio_queue(&mut self) -> Option<&mut AudioQueue> {
         None
     }
+
+    // Sound channels that can be muted individually, or rendered as stems
+    fn audio_channels(&self) -> &[&'static str] {
+        &[]
+    }
+
+    fn set_audio_channel_muted(&mut self, _index: usize, _muted: bool) {}
+
+    fn audio_channel_muted(&self, _index: usize) -> bool {
+        false
+    }
+
+    // Mutes every channel except the given one
+    fn solo_audio_channel(&mut self, index: usize) {
+        for other in 0..self.audio_channels().len() {
+            self.set_audio_channel_muted(other, other != index);
+        }
+    }
+
+    // While enabled, each channel is also rendered on its own (whether muted or not) into a
+    // separate queue, in the same order as 'audio_channels'
+    fn set_stems_enabled(&mut self, _enabled: bool) {}
+
+    fn stem_queues(&mut self) -> Option<&mut [AudioQueue]> {
+        None
+    }
+
+    fn load_achievements(&mut self, _set: AchievementSet) -> Result<(), Error> {
+        Err("Achievements are not supported for this system".into())
+    }
+
+    fn achievement_events(&mut self) -> Option<&mut AchievementQueue> {
+        None
+    }
+
+    fn load_cheats(&mut self, _set: CheatSet) -> Result<(), Error> {
+        Err("Cheats are not supported for this system".into())
+    }
+
+    // Save states are only valid for the same game, and are best taken between frames
+    fn save_state(&mut self) -> Option<Vec<u8>> {
+        None
+    }
+
+    fn load_state(&mut self, _data: &[u8]) -> Result<(), Error> {
+        Err("Save states are not supported for this system".into())
+    }
+}
+
+pub(crate) fn joypad(ports: &[Option<JoypadState>], index: usize) -> Option<&JoypadState> {
+    ports.get(index).and_then(Option::as_ref)
 }
 
 pub fn create<'a, T: MemoryMapper + 'static>(
@@ -98,3 +305,66 @@ pub fn create<'a, T: MemoryMapper + 'static>(
         SystemType::Snes => Box::new(snes::System::new(options)),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom_with(offset: usize, bytes: &[u8]) -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[offset..(offset + bytes.len())].copy_from_slice(bytes);
+        rom
+    }
+
+    #[test]
+    fn detect_from_magic() {
+        let nes = rom_with(0, b"NES\x1a");
+        assert_eq!(SystemType::detect(&nes, None).unwrap(), SystemType::Nes);
+
+        for magic in N64_MAGIC {
+            let n64 = rom_with(0, &magic);
+            assert_eq!(
+                SystemType::detect(&n64, None).unwrap(),
+                SystemType::Nintendo64
+            );
+        }
+
+        let gb = rom_with(0x0104, &GB_LOGO);
+        assert_eq!(SystemType::detect(&gb, None).unwrap(), SystemType::GameBoy);
+
+        let mut gba = rom_with(0x04, &GBA_LOGO_PREFIX);
+        gba[0xb2] = 0x96;
+        assert_eq!(
+            SystemType::detect(&gba, None).unwrap(),
+            SystemType::GameBoyAdvance
+        );
+
+        let sms = rom_with(0x7ff0, b"TMR SEGA");
+        assert_eq!(
+            SystemType::detect(&sms, None).unwrap(),
+            SystemType::SegaMasterSystem
+        );
+    }
+
+    #[test]
+    fn detect_snes_header() {
+        let mut rom = rom_with(0x7fc0, b"SNES TEST            ");
+        rom[0x7fd5] = 0x20;
+        rom[0x7fd7] = 0x05;
+        rom[0x7fdc..=0x7fdf].copy_from_slice(&[0xff, 0xff, 0x00, 0x00]);
+        rom[0x7ffc..=0x7ffd].copy_from_slice(&[0x00, 0x80]);
+        rom[0x0000] = 0x78;
+        assert_eq!(SystemType::detect(&rom, None).unwrap(), SystemType::Snes);
+    }
+
+    #[test]
+    fn detect_falls_back_to_extension() {
+        let rom = vec![0; 0x8000];
+        assert!(SystemType::detect(&rom, None).is_err());
+
+        assert_eq!(
+            SystemType::detect(&rom, Some(Path::new("game.sfc"))).unwrap(),
+            SystemType::Snes
+        );
+    }
+}
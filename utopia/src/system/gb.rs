@@ -1,21 +1,28 @@
+use crate::achievement::{Achievements, MemoryView};
+use crate::cheat::{Cheats, MemoryPoke};
 use crate::core::sm83::{Bus, Core, State};
+use crate::database;
+use crate::util::audio::AudioFilter;
 use crate::util::mirror::MirrorVec;
 use crate::util::upscaler::Upscaler;
 use crate::{
-    AudioQueue, BiosLoader, InstanceOptions, JoypadState, Mapped, MemoryMapper, Size, SystemOptions,
+    AchievementQueue, AchievementSet, AudioQueue, BiosLoader, CheatSet, InstanceOptions,
+    JoypadState, Mapped, MemoryMapper, RomInfo, Size, SystemOptions, SystemType,
 };
 use apu::Apu;
 use cartridge::Cartridge;
 use dma::Dma;
 use interrupt::Interrupt;
 use joypad::Joypad;
-use ppu::Ppu;
+use ppu::{Ppu, ScreenOptions};
 use std::error::Error;
 use std::fmt;
 use timer::Timer;
 use tracing::{trace, warn};
 use wram::Wram;
 
+pub use ppu::{GbColorCorrection, GbPalette};
+
 mod apu;
 mod cartridge;
 mod dma;
@@ -33,6 +40,10 @@ pub struct System<'a, U: MemoryMapper + 'static> {
     bios_loader: &'a dyn BiosLoader,
     memory_mapper: &'a U,
     skip_boot: bool,
+    audio_filter: AudioFilter,
+    color_correction: GbColorCorrection,
+    frame_blend: bool,
+    palette: GbPalette,
 }
 
 impl<'a, T: MemoryMapper> System<'a, T> {
@@ -41,6 +52,10 @@ impl<'a, T: MemoryMapper> System<'a, T> {
             bios_loader: options.bios_loader,
             memory_mapper: options.memory_mapper,
             skip_boot: options.skip_boot,
+            audio_filter: options.audio_filter,
+            color_correction: options.gb_color_correction,
+            frame_blend: options.gb_frame_blend,
+            palette: options.gb_palette,
         }
     }
 }
@@ -58,10 +73,18 @@ impl<'a, T: MemoryMapper> crate::System<T> for System<'a, T> {
         &self,
         options: InstanceOptions,
     ) -> Result<Box<dyn crate::Instance>, crate::Error> {
+        let screen_options = ScreenOptions {
+            color_correction: self.color_correction,
+            frame_blend: self.frame_blend,
+            dmg_colors: self.palette.colors(&options.rom_data),
+        };
+
         let result = Instance::new(
             self.bios_loader,
             self.memory_mapper,
             self.skip_boot,
+            self.audio_filter,
+            screen_options,
             options,
         );
 
@@ -69,11 +92,36 @@ impl<'a, T: MemoryMapper> crate::System<T> for System<'a, T> {
             result.map_err(|err| crate::Error(err.to_string()))?,
         ))
     }
+
+    fn rom_info(&self, rom_data: &[u8]) -> Result<RomInfo, crate::Error> {
+        let mut info = RomInfo::new(SystemType::GameBoy, rom_data);
+        let header = cartridge::Header::parse(rom_data, info.overrides)?;
+
+        info.add("Title", header.title);
+        info.add("CGB Flag", header.is_cgb);
+        info.add("Mapper Number", format!("{:02X}", header.mapper_number));
+        info.add("ROM Size", header.rom_size);
+        info.add("RAM Size", header.ram_size);
+        info.add("Battery Backed", header.battery_backed);
+
+        info.add(
+            "Header Checksum",
+            if header.checksum_valid {
+                "Valid"
+            } else {
+                "Invalid"
+            },
+        );
+
+        Ok(info)
+    }
 }
 
 pub struct Instance<T: Mapped> {
     core: Core<Hardware<T>>,
-    upscaler: Upscaler,
+    upscaler: Option<Upscaler>,
+    achievements: Achievements,
+    cheats: Cheats,
 }
 
 impl<T: Mapped> Instance<T> {
@@ -81,9 +129,12 @@ impl<T: Mapped> Instance<T> {
         bios_loader: &dyn BiosLoader,
         memory_mapper: &U,
         skip_boot: bool,
+        audio_filter: AudioFilter,
+        screen_options: ScreenOptions,
         options: InstanceOptions,
     ) -> Result<Self, Box<dyn Error>> {
-        let cartridge = Cartridge::new(options.rom_data, memory_mapper)?;
+        let overrides = database::overrides(SystemType::GameBoy, &options.rom_data);
+        let cartridge = Cartridge::new(options.rom_data, memory_mapper, overrides)?;
 
         let bios_data = if !skip_boot {
             let bios_name = if cartridge.is_cgb() {
@@ -126,17 +177,30 @@ impl<T: Mapped> Instance<T> {
         });
 
         // TODO: Should skip boot sequence for other hardware components as well
-        let hw = Hardware::new(cartridge, bios_data, skip_boot)?;
+        let hw = Hardware::new(
+            cartridge,
+            bios_data,
+            skip_boot,
+            audio_filter,
+            screen_options,
+        )?;
         let core = Core::new(hw, initial_state);
 
-        let upscaler = Upscaler::new(
-            options.wgpu_context,
-            (ppu::WIDTH as u32, ppu::HEIGHT as u32).into(),
-            options.output_resolution,
-            false,
-        );
+        let upscaler = options.wgpu_context.map(|ctx| {
+            Upscaler::new(
+                ctx,
+                (ppu::WIDTH as u32, ppu::HEIGHT as u32).into(),
+                options.output_resolution,
+                false,
+            )
+        });
 
-        Ok(Instance { core, upscaler })
+        Ok(Instance {
+            core,
+            upscaler,
+            achievements: Achievements::new(),
+            cheats: Cheats::new(),
+        })
     }
 }
 
@@ -149,10 +213,54 @@ impl<T: Mapped> crate::Instance for Instance<T> {
         Some(self.core.bus_mut().apu.audio_queue())
     }
 
-    fn run_frame(&mut self, joypad_state: &JoypadState) {
+    fn audio_channels(&self) -> &[&'static str] {
+        self.core.bus().apu.audio_channels().names()
+    }
+
+    fn set_audio_channel_muted(&mut self, index: usize, muted: bool) {
+        self.core
+            .bus_mut()
+            .apu
+            .audio_channels_mut()
+            .set_muted(index, muted);
+    }
+
+    fn audio_channel_muted(&self, index: usize) -> bool {
+        self.core.bus().apu.audio_channels().is_muted(index)
+    }
+
+    fn set_stems_enabled(&mut self, enabled: bool) {
+        self.core
+            .bus_mut()
+            .apu
+            .audio_channels_mut()
+            .set_stems_enabled(enabled);
+    }
+
+    fn stem_queues(&mut self) -> Option<&mut [AudioQueue]> {
+        Some(self.core.bus_mut().apu.audio_channels_mut().stem_queues())
+    }
+
+    fn load_achievements(&mut self, set: AchievementSet) -> Result<(), crate::Error> {
+        self.achievements.load(set)
+    }
+
+    fn achievement_events(&mut self) -> Option<&mut AchievementQueue> {
+        Some(self.achievements.events())
+    }
+
+    fn load_cheats(&mut self, set: CheatSet) -> Result<(), crate::Error> {
+        self.cheats.load(set);
+        Ok(())
+    }
+
+    fn run_frame(&mut self, ports: &[Option<JoypadState>]) {
+        self.cheats.apply(self.core.bus_mut());
+
         let core = &mut self.core;
 
-        core.bus_mut().joypad.update(joypad_state);
+        let joypad_state = super::joypad(ports, 0).cloned().unwrap_or_default();
+        core.bus_mut().joypad.update(&joypad_state);
         core.bus_mut().ppu.start_frame();
 
         while !core.bus().ppu.ready() {
@@ -160,11 +268,21 @@ impl<T: Mapped> crate::Instance for Instance<T> {
             core.step();
         }
 
-        self.upscaler.update(self.core.bus().ppu.pixels());
+        self.achievements.evaluate(self.core.bus());
     }
 
     fn present(&self, canvas: &wgpu::Texture) {
-        self.upscaler.render(canvas);
+        if let Some(upscaler) = &self.upscaler {
+            upscaler.update(self.core.bus().ppu.pixels());
+            upscaler.render(canvas);
+        }
+    }
+
+    fn pixels(&self) -> Option<(&[u8], Size)> {
+        Some((
+            self.core.bus().ppu.pixels(),
+            (ppu::WIDTH as u32, ppu::HEIGHT as u32).into(),
+        ))
     }
 }
 
@@ -190,6 +308,8 @@ impl<T: Mapped> Hardware<T> {
         cartridge: Cartridge<T>,
         bios_data: Option<Vec<u8>>,
         skip_boot: bool,
+        audio_filter: AudioFilter,
+        screen_options: ScreenOptions,
     ) -> Result<Self, Box<dyn Error>> {
         let is_cgb = cartridge.is_cgb();
 
@@ -203,8 +323,12 @@ impl<T: Mapped> Hardware<T> {
             hram: MirrorVec::new(HRAM_SIZE),
             wram: Wram::new(is_cgb),
             cartridge,
-            ppu: Ppu::new(is_cgb, skip_boot),
-            apu: Apu::new(),
+            ppu: Ppu::new(is_cgb, skip_boot, screen_options),
+            apu: Apu::new(audio_filter.resolve(if is_cgb {
+                AudioFilter::Cgb
+            } else {
+                AudioFilter::Dmg
+            })),
             joypad: Joypad::new(),
             dma: Dma::new(),
             bios_data,
@@ -449,6 +573,39 @@ impl<T: Mapped> Bus for Hardware<T> {
     }
 }
 
+impl<T: Mapped> MemoryView for Hardware<T> {
+    fn peek(&self, address: u32) -> u8 {
+        match address {
+            0x0000..=0x7fff => self.cartridge.read_rom(address as u16),
+            0x8000..=0x9fff => self.ppu.read_vram(address as u16),
+            0xa000..=0xbfff => self.cartridge.read_ram(address as u16),
+            0xc000..=0xfdff => self.wram[address as usize],
+            0xfe00..=0xfe9f => self.ppu.read_oam(address as u8),
+            0xff80..=0xfffe => self.hram[address as usize],
+            0xffff => self.interrupt.enable(),
+            // CGB WRAM banks 2-7
+            0x10000..=0x15fff if self.cartridge.is_cgb() => {
+                self.wram.peek(address as usize - 0xe000)
+            }
+            _ => 0,
+        }
+    }
+}
+
+impl<T: Mapped> MemoryPoke for Hardware<T> {
+    fn poke(&mut self, address: u32, value: u8) {
+        match address {
+            0xa000..=0xbfff => self.cartridge.write_ram(address as u16, value),
+            0xc000..=0xfdff => self.wram[address as usize] = value,
+            0xff80..=0xfffe => self.hram[address as usize] = value,
+            0x10000..=0x15fff if self.cartridge.is_cgb() => {
+                self.wram.poke(address as usize - 0xe000, value)
+            }
+            _ => (),
+        }
+    }
+}
+
 impl<T: Mapped> fmt::Display for Hardware<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -1,3 +1,4 @@
+use crate::util::audio::{AudioChannels, AudioFilter, BlipBuffer, OutputFilter};
 use crate::AudioQueue;
 use noise::Noise;
 use pulse::Pulse;
@@ -10,13 +11,12 @@ mod pulse;
 mod wave;
 
 const CYCLES_PER_SECOND: u64 = 1048576;
-const CLOCK_SHIFT: u32 = 16;
-const SAMPLE_PERIOD: u64 = (CYCLES_PER_SECOND << CLOCK_SHIFT) / Apu::SAMPLE_RATE;
-const SAMPLES_PER_CYCLE: u64 = 1 << CLOCK_SHIFT;
 
 // 0-15 channel output, 4 channels, 0-7 volume level
 const MAX_OUTPUT_VALUE: f32 = 15.0 * 4.0 * 7.0;
 
+const CHANNEL_NAMES: [&str; 4] = ["Pulse 1", "Pulse 2", "Wave", "Noise"];
+
 #[derive(Clone, Default)]
 struct Channel {
     enabled: [bool; 4],
@@ -29,7 +29,9 @@ pub struct Apu {
     wave: Wave,
     noise: Noise,
     divider: u64,
-    sample_clock: u64,
+    blip: [BlipBuffer; 2],
+    filter: OutputFilter,
+    audio_channels: AudioChannels,
     power: bool,
     channels: [Channel; 2],
     audio_queue: AudioQueue,
@@ -38,14 +40,24 @@ pub struct Apu {
 impl Apu {
     pub const SAMPLE_RATE: u64 = 44100;
 
-    pub fn new() -> Self {
+    pub fn new(audio_filter: AudioFilter) -> Self {
         Self {
             pulse1: Pulse::new(true),
             pulse2: Pulse::new(false),
             wave: Wave::new(),
             noise: Noise::new(),
             divider: 0,
-            sample_clock: 0,
+            blip: [
+                BlipBuffer::new(CYCLES_PER_SECOND, Self::SAMPLE_RATE),
+                BlipBuffer::new(CYCLES_PER_SECOND, Self::SAMPLE_RATE),
+            ],
+            filter: OutputFilter::new(audio_filter, Self::SAMPLE_RATE),
+            audio_channels: AudioChannels::new(
+                CHANNEL_NAMES.to_vec(),
+                audio_filter,
+                Some(CYCLES_PER_SECOND),
+                Self::SAMPLE_RATE,
+            ),
             power: false,
             channels: Default::default(),
             audio_queue: AudioQueue::new(),
@@ -56,6 +68,14 @@ impl Apu {
         &mut self.audio_queue
     }
 
+    pub fn audio_channels(&self) -> &AudioChannels {
+        &self.audio_channels
+    }
+
+    pub fn audio_channels_mut(&mut self) -> &mut AudioChannels {
+        &mut self.audio_channels
+    }
+
     pub fn read(&mut self, address: u8) -> u8 {
         match address {
             0x10..=0x14 => self.pulse1.read(address - 0x10),
@@ -126,23 +146,37 @@ impl Apu {
         self.wave.step();
         self.noise.step();
 
-        self.sample_clock += SAMPLES_PER_CYCLE;
-
-        if self.sample_clock < SAMPLE_PERIOD {
-            return;
-        }
-
-        self.sample_clock -= SAMPLE_PERIOD;
-
-        let output = if self.power {
-            let left = self.channel_output(0);
-            let right = self.channel_output(1);
-            (left, right)
+        let levels = [
+            self.pulse1.output(),
+            self.pulse2.output(),
+            self.wave.output(),
+            self.noise.output(),
+        ];
+
+        // The DC offset applies to the mix as a whole, rather than to each stem
+        let (left, right) = if self.power {
+            let (left, right) = self.mix(levels, |index| !self.audio_channels.is_muted(index));
+            (left - 0.5, right - 0.5)
         } else {
             (0.0, 0.0)
         };
 
-        self.audio_queue.push_back(output);
+        self.blip[0].set_amplitude(left);
+        self.blip[1].set_amplitude(right);
+
+        // Both buffers run at the same rate, so they always produce samples together
+        if let (Some(left), Some(right)) = (self.blip[0].step(), self.blip[1].step()) {
+            self.audio_queue.push_back(self.filter.apply((left, right)));
+        }
+
+        if self.audio_channels.stems_enabled() {
+            for index in 0..levels.len() {
+                let output = self.mix(levels, |other| other == index);
+                self.audio_channels.set_stem_amplitude(index, output);
+            }
+
+            self.audio_channels.step_stems();
+        }
     }
 
     pub fn on_divider_clock(&mut self) {
@@ -154,26 +188,32 @@ impl Apu {
         self.noise.on_divider_clock(self.divider);
     }
 
-    fn channel_output(&self, index: usize) -> f32 {
-        let channel = &self.channels[index];
-        let mut output = 0;
-
-        if channel.enabled[0] {
-            output += self.pulse1.output();
+    fn mix(&self, levels: [u8; 4], enabled: impl Fn(usize) -> bool) -> (f32, f32) {
+        if !self.power {
+            return (0.0, 0.0);
         }
 
-        if channel.enabled[1] {
-            output += self.pulse2.output();
-        }
+        (
+            self.channel_output(0, levels, &enabled),
+            self.channel_output(1, levels, &enabled),
+        )
+    }
 
-        if channel.enabled[2] {
-            output += self.wave.output();
-        }
+    fn channel_output(
+        &self,
+        index: usize,
+        levels: [u8; 4],
+        enabled: &impl Fn(usize) -> bool,
+    ) -> f32 {
+        let channel = &self.channels[index];
+        let mut output = 0;
 
-        if channel.enabled[3] {
-            output += self.noise.output();
+        for (source, level) in levels.into_iter().enumerate() {
+            if channel.enabled[source] && enabled(source) {
+                output += level;
+            }
         }
 
-        ((channel.volume as f32 * output as f32) / MAX_OUTPUT_VALUE) - 0.5
+        (channel.volume as f32 * output as f32) / MAX_OUTPUT_VALUE
     }
 }
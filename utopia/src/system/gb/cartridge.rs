@@ -1,3 +1,4 @@
+use crate::database::Overrides;
 use crate::util::mirror::{Mirror, MirrorVec};
 use crate::{Mapped, MemoryMapper};
 use mbc::{Mappings, Mbc, MbcType, RamMapping};
@@ -18,14 +19,33 @@ pub struct Cartridge<T: Mapped> {
     mapper: MbcType,
 }
 
-impl<T: Mapped> Cartridge<T> {
-    pub fn new(
-        rom: Vec<u8>,
-        memory_mapper: &impl MemoryMapper<Mapped = T>,
-    ) -> Result<Self, Box<dyn Error>> {
+const HEADER_END: usize = 0x0150;
+
+pub struct Header {
+    pub title: String,
+    pub is_cgb: bool,
+    pub mapper_number: u8,
+    pub rom_size: usize,
+    pub ram_size: usize,
+    pub battery_backed: bool,
+    pub checksum_valid: bool,
+}
+
+impl Header {
+    pub fn parse(rom: &[u8], overrides: Overrides) -> Result<Self, crate::Error> {
+        if rom.len() < HEADER_END {
+            return Err("ROM is too small to contain a header".into());
+        }
+
+        let title = String::from_utf8_lossy(&rom[0x0134..=0x0143]).into_owned();
         let is_cgb = (rom[0x0143] & 0x80) != 0;
-        let mapper_number = rom[0x0147];
-        let rom_size = BASE_ROM_SIZE << rom[0x0148];
+        let mapper_number = overrides.mapper.unwrap_or(rom[0x0147]);
+
+        // Codes above 8 (8 MiB) are not used by any real cartridge
+        let rom_size = match rom[0x0148] {
+            code @ 0..=8 => BASE_ROM_SIZE << code,
+            code => return Err(format!("Invalid ROM size code: {:02X}", code).into()),
+        };
 
         let ram_size = match rom[0x0149] {
             2 => 8192,
@@ -35,23 +55,53 @@ impl<T: Mapped> Cartridge<T> {
             _ => 0,
         };
 
-        info!("Title: {}", String::from_utf8_lossy(&rom[0x0134..=0x0143]));
-        info!("Model: {}", if is_cgb { "CGB" } else { "DMG" });
-        info!("Mapper Number: {:02X}", mapper_number);
-        info!("ROM Size: {}", rom_size);
-        info!("RAM Size: {}", ram_size);
+        let battery_backed = ram_size > 0
+            && overrides
+                .battery
+                .unwrap_or(BATTERY_BACKED.contains(&mapper_number));
+
+        let checksum = rom[0x0134..=0x014c]
+            .iter()
+            .fold(0u8, |acc, byte| acc.wrapping_sub(*byte).wrapping_sub(1));
+
+        Ok(Self {
+            title,
+            is_cgb,
+            mapper_number,
+            rom_size,
+            ram_size,
+            battery_backed,
+            checksum_valid: checksum == rom[0x014d],
+        })
+    }
+}
+
+impl<T: Mapped> Cartridge<T> {
+    pub fn new(
+        rom: Vec<u8>,
+        memory_mapper: &impl MemoryMapper<Mapped = T>,
+        overrides: Overrides,
+    ) -> Result<Self, Box<dyn Error>> {
+        let header = Header::parse(&rom, overrides)?;
+
+        info!("Title: {}", header.title);
+        info!("Model: {}", if header.is_cgb { "CGB" } else { "DMG" });
+        info!("Mapper Number: {:02X}", header.mapper_number);
+        info!("ROM Size: {}", header.rom_size);
+        info!("RAM Size: {}", header.ram_size);
 
         let mut mappings = Mappings::new();
-        let mut mapper = MbcType::new(mapper_number);
+        let mut mapper = MbcType::new(header.mapper_number, overrides.multicart);
         mapper.init_mappings(&mut mappings);
 
-        let battery_backed = ram_size > 0 && BATTERY_BACKED.contains(&mapper_number);
-        info!("Battery Backed: {}", battery_backed);
+        info!("Battery Backed: {}", header.battery_backed);
 
         Ok(Self {
             rom: rom.into(),
-            ram: memory_mapper.open(ram_size, battery_backed)?.into(),
-            is_cgb,
+            ram: memory_mapper
+                .open(header.ram_size, header.battery_backed)?
+                .into(),
+            is_cgb: header.is_cgb,
             mappings,
             mapper,
         })
@@ -87,3 +137,50 @@ impl<T: Mapped> Cartridge<T> {
             .write_register(&mut self.mappings, address, value);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{database, SystemType};
+
+    #[test]
+    fn rom_size_code_is_bounded() {
+        let mut rom = vec![0; HEADER_END];
+
+        rom[0x0148] = 0x05;
+        let header = Header::parse(&rom, Overrides::default()).unwrap();
+        assert_eq!(header.rom_size, 1024 * 1024);
+
+        for code in [0x09, 0x40, 0xff] {
+            rom[0x0148] = code;
+            assert!(Header::parse(&rom, Overrides::default()).is_err());
+        }
+    }
+
+    #[test]
+    fn mbc1_multicart_is_detected() {
+        const GAME_SIZE: usize = 0x0004_0000;
+
+        let mut rom = vec![0; GAME_SIZE * 4];
+
+        for (index, game) in rom.chunks_mut(GAME_SIZE).enumerate() {
+            game[0x0104..0x0134].fill(0xce);
+            game[0x0134] = b'A' + index as u8;
+            game[0x0147] = 0x01;
+            game[0x0148] = 0x05;
+        }
+
+        let overrides = database::correct(SystemType::GameBoy, &rom, Overrides::default());
+        assert!(overrides.multicart);
+
+        // Only the first header is used, so the mapper is unchanged
+        let header = Header::parse(&rom, overrides).unwrap();
+        assert_eq!(header.mapper_number, 0x01);
+        assert_eq!(header.title.trim_end_matches('\0'), "A");
+
+        // A regular MBC1 game of the same size has no second logo
+        rom[GAME_SIZE + 0x0104] = 0;
+        let overrides = database::correct(SystemType::GameBoy, &rom, Overrides::default());
+        assert!(!overrides.multicart);
+    }
+}
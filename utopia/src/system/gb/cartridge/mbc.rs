@@ -31,10 +31,10 @@ pub enum MbcType {
 }
 
 impl MbcType {
-    pub fn new(mapper_number: u8) -> Self {
+    pub fn new(mapper_number: u8, multicart: bool) -> Self {
         match mapper_number {
             0x00 => Self::RomOnly(RomOnly::new()),
-            0x01..=0x03 => Self::Mbc1(Mbc1::new()),
+            0x01..=0x03 => Self::Mbc1(Mbc1::new(multicart)),
             0x0f..=0x13 => Self::Mbc3(Mbc3::new()),
             0x19..=0x1e => Self::Mbc5(Mbc5::new()),
             _ => panic!("Mapper {:02X} not yet supported", mapper_number),
@@ -5,14 +5,16 @@ pub struct Mbc1 {
     ram_enable: bool,
     register: [u8; 2],
     mode: bool,
+    multicart: bool,
 }
 
 impl Mbc1 {
-    pub fn new() -> Self {
+    pub fn new(multicart: bool) -> Self {
         Self {
             ram_enable: false,
             register: [1, 0],
             mode: false,
+            multicart,
         }
     }
 
@@ -27,7 +29,13 @@ impl Mbc1 {
             RamMapping::None
         };
 
-        let high_bank_offset = (self.register[1] as usize) << 5;
+        // On multicarts, the high bits are wired one place lower and bit 4 of the low bank
+        // register is unused
+        let (high_bank_offset, low_bank_mask) = if self.multicart {
+            ((self.register[1] as usize) << 4, 0x0f)
+        } else {
+            ((self.register[1] as usize) << 5, 0x1f)
+        };
 
         mappings.rom[0] = if self.mode {
             Mappings::ROM_PAGE_SIZE * high_bank_offset
@@ -35,7 +43,7 @@ impl Mbc1 {
             0
         };
 
-        let rom_bank = high_bank_offset + (self.register[0] as usize);
+        let rom_bank = high_bank_offset + (self.register[0] as usize & low_bank_mask);
         mappings.rom[1] = Mappings::ROM_PAGE_SIZE * rom_bank;
 
         trace!("MBC1 ROM Mapping: {:?}", mappings.rom);
@@ -6,8 +6,10 @@ use render::RenderState;
 use screen::Screen;
 use tracing::trace;
 
-pub use screen::{HEIGHT, WIDTH};
+pub use color::GbPalette;
+pub use screen::{GbColorCorrection, ScreenOptions, HEIGHT, WIDTH};
 
+mod color;
 mod oam;
 mod palette;
 mod render;
@@ -75,7 +77,7 @@ pub struct Ppu {
 }
 
 impl Ppu {
-    pub fn new(is_cgb: bool, skip_boot: bool) -> Self {
+    pub fn new(is_cgb: bool, skip_boot: bool, screen_options: ScreenOptions) -> Self {
         Self {
             ready: false,
             is_cgb,
@@ -109,7 +111,7 @@ impl Ppu {
             cgb_palette_bg: Palette::new("BG"),
             cgb_palette_obj: Palette::new("OBJ"),
             render: Default::default(),
-            screen: Screen::new(),
+            screen: Screen::new(screen_options),
             vram: MirrorVec::new(VRAM_BANK_SIZE * if is_cgb { 2 } else { 1 }),
             vram_bank_offset: 0,
             oam: Oam::new(),
@@ -315,6 +317,7 @@ impl Ppu {
 
                         if self.line == VBLANK_LINE {
                             self.ready = true;
+                            self.screen.finish_frame();
                             self.screen.reset();
                             self.set_mode(interrupt, Mode::VBlank);
                             interrupt.raise(InterruptType::VBlank);
@@ -0,0 +1,270 @@
+use crate::util::Rgb;
+use crate::Error;
+
+const PAL_FILE_SIZE: usize = 4 * 3;
+const PAL_FILE_SIZE_PER_LAYER: usize = 3 * 4 * 3;
+
+// Layers that have their own colours when a DMG game is colorized (OBJ1 follows OBJ0)
+pub const LAYER_BG: usize = 0;
+pub const LAYER_OBJ0: usize = 1;
+
+// The palettes that can be picked on the CGB boot screen, in the order of the key combinations:
+// Up, Up+A, Up+B, Left, Left+A, Left+B, Down, Down+A, Down+B, Right, Right+A, Right+B
+const MANUAL_NAMES: [&str; 12] = [
+    "up", "up+a", "up+b", "left", "left+a", "left+b", "down", "down+a", "down+b", "right",
+    "right+a", "right+b",
+];
+
+const MANUAL_COMBINATIONS: [u8; 12] = [5, 43, 28, 48, 40, 7, 8, 3, 49, 1, 0, 6];
+
+// Which colours to give the four shades of a DMG (non-CGB) game
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum GbPalette {
+    // The greyscale of the DMG itself
+    #[default]
+    Dmg,
+    // Picked from the title of the game, as the CGB boot ROM does
+    Auto,
+    // One of the key combinations on the CGB boot screen (an index into 'MANUAL_NAMES')
+    Manual(usize),
+    // Contents of a palette file: 4 RGB colours used for every layer, or 12 (4 each for BG, OBJ0
+    // and OBJ1)
+    Custom(Vec<u8>),
+}
+
+// Colours for each layer (BG, OBJ0 and OBJ1)
+pub enum DmgColors {
+    Grey,
+    // 15-bit colours, converted in the same way as CGB palette data
+    Cgb([[u16; 4]; 3]),
+    Rgb([[Rgb; 4]; 3]),
+}
+
+impl GbPalette {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "dmg" => Some(Self::Dmg),
+            "auto" => Some(Self::Auto),
+            name => MANUAL_NAMES
+                .iter()
+                .position(|&manual| manual == name)
+                .map(Self::Manual),
+        }
+    }
+
+    pub fn from_pal(data: Vec<u8>) -> Result<Self, Error> {
+        if data.len() != PAL_FILE_SIZE && data.len() != PAL_FILE_SIZE_PER_LAYER {
+            return Err(format!(
+                "Game Boy palette files must be {} or {} bytes long (found {})",
+                PAL_FILE_SIZE,
+                PAL_FILE_SIZE_PER_LAYER,
+                data.len()
+            )
+            .into());
+        }
+
+        Ok(Self::Custom(data))
+    }
+
+    pub fn colors(&self, rom_data: &[u8]) -> DmgColors {
+        match self {
+            Self::Dmg => DmgColors::Grey,
+            Self::Auto => {
+                let index = PALETTE_PER_TITLE[title_index(rom_data)];
+                DmgColors::Cgb(combination(index))
+            }
+            Self::Manual(index) => DmgColors::Cgb(combination(MANUAL_COMBINATIONS[*index])),
+            Self::Custom(data) => {
+                let colors: Vec<Rgb> = data
+                    .chunks_exact(3)
+                    .map(|rgb| Rgb(rgb[0], rgb[1], rgb[2]))
+                    .collect();
+
+                DmgColors::Rgb(std::array::from_fn(|layer| {
+                    std::array::from_fn(|shade| colors[(layer * 4 + shade) % colors.len()])
+                }))
+            }
+        }
+    }
+}
+
+// Returns an index into 'PALETTE_PER_TITLE', where 0 means the game was not recognised
+fn title_index(rom_data: &[u8]) -> usize {
+    let Some(header) = rom_data.get(0x0100..0x0150) else {
+        return 0;
+    };
+
+    // Only games published by Nintendo are looked up
+    let old_licensee = header[0x4b];
+
+    if old_licensee != 0x01 && (old_licensee != 0x33 || &header[0x44..=0x45] != b"01") {
+        return 0;
+    }
+
+    let checksum = header[0x34..=0x43]
+        .iter()
+        .fold(0u8, |sum, &byte| sum.wrapping_add(byte));
+
+    if let Some(index) = TITLE_CHECKSUMS.iter().position(|&value| value == checksum) {
+        return index + 1;
+    }
+
+    // Some checksums are shared, in which case the 4th letter of the title tells the games apart
+    let Some(column) = SHARED_CHECKSUMS.iter().position(|&value| value == checksum) else {
+        return 0;
+    };
+
+    let letter = header[0x37];
+
+    (column..TITLE_LETTERS.len())
+        .step_by(SHARED_CHECKSUMS.len())
+        .find(|&index| TITLE_LETTERS[index] == letter)
+        .map_or(0, |index| TITLE_CHECKSUMS.len() + 1 + index)
+}
+
+// Returns the colours for each layer in the order BG, OBJ0, OBJ1
+fn combination(index: u8) -> [[u16; 4]; 3] {
+    let (obj0, obj1, bg) = COMBINATIONS[index as usize];
+    let palette = |offset: usize| std::array::from_fn(|shade| PALETTE_DATA[offset + shade]);
+    [palette(bg), palette(obj0), palette(obj1)]
+}
+
+// Title checksums known to the CGB boot ROM
+const TITLE_CHECKSUMS: [u8; 64] = [
+    0x88, 0x16, 0x36, 0xd1, 0xdb, 0xf2, 0x3c, 0x8c, 0x92, 0x3d, 0x5c, 0x58, 0xc9, 0x3e, 0x70, 0x1d,
+    0x59, 0x69, 0x19, 0x35, 0xa8, 0x14, 0xaa, 0x75, 0x95, 0x99, 0x34, 0x6f, 0x15, 0xff, 0x97, 0x4b,
+    0x90, 0x17, 0x10, 0x39, 0xf7, 0xf6, 0xa2, 0x49, 0x4e, 0x43, 0x68, 0xe0, 0x8b, 0xf0, 0xce, 0x0c,
+    0x29, 0xe8, 0xb7, 0x86, 0x9a, 0x52, 0x01, 0x9d, 0x71, 0x9c, 0xbd, 0x5d, 0x6d, 0x67, 0x3f, 0x6b,
+];
+
+const SHARED_CHECKSUMS: [u8; 14] = [
+    0xb3, 0x46, 0x28, 0xa5, 0xc6, 0xd3, 0x27, 0x61, 0x18, 0x66, 0x6a, 0xbf, 0x0d, 0xf4,
+];
+
+// One row of letters for each set of games sharing checksums
+const TITLE_LETTERS: &[u8; 29] = b"BEFAARBEKEK R-URAR INAILICE R";
+
+// Index into 'COMBINATIONS' for each game: the default, then one for each checksum, then one for
+// each title letter
+const PALETTE_PER_TITLE: [u8; 94] = [
+    0, 4, 5, 35, 34, 3, 31, 15, 10, 5, 19, 36, 7, 37, 30, 44, 21, 32, 31, 20, 5, 33, 13, 14, 5, 29,
+    5, 18, 9, 3, 2, 26, 25, 25, 41, 42, 26, 45, 42, 45, 36, 38, 26, 42, 30, 41, 34, 34, 5, 42, 6,
+    5, 33, 25, 42, 42, 40, 2, 16, 25, 42, 42, 5, 0, 39, 36, 22, 25, 6, 32, 12, 36, 11, 39, 18, 39,
+    24, 31, 50, 17, 46, 6, 27, 0, 47, 41, 41, 0, 0, 19, 34, 23, 18, 29,
+];
+
+// Offsets into 'PALETTE_DATA' for OBJ0, OBJ1 and BG. The boot ROM overlaps some of its palettes,
+// so not every offset is a multiple of 4.
+const COMBINATIONS: [(usize, usize, usize); 51] = [
+    (16, 16, 116),
+    (72, 72, 72),
+    (80, 80, 80),
+    (96, 96, 96),
+    (36, 36, 36),
+    (0, 0, 0),
+    (108, 108, 108),
+    (20, 20, 20),
+    (48, 48, 48),
+    (104, 104, 104),
+    (64, 32, 32),
+    (16, 112, 112),
+    (16, 8, 8),
+    (12, 16, 16),
+    (16, 116, 116),
+    (112, 16, 112),
+    (8, 68, 8),
+    (64, 64, 32),
+    (16, 16, 28),
+    (16, 16, 72),
+    (16, 16, 80),
+    (76, 76, 36),
+    (15, 15, 44),
+    (68, 68, 8),
+    (16, 16, 8),
+    (16, 16, 12),
+    (112, 112, 0),
+    (12, 12, 0),
+    (0, 0, 4),
+    (72, 88, 72),
+    (80, 88, 80),
+    (96, 88, 96),
+    (64, 88, 32),
+    (68, 16, 52),
+    (111, 0, 56),
+    (111, 16, 60),
+    (76, 88, 36),
+    (64, 112, 40),
+    (16, 92, 112),
+    (68, 88, 8),
+    (16, 0, 8),
+    (16, 112, 12),
+    (112, 12, 0),
+    (12, 112, 16),
+    (84, 112, 16),
+    (12, 112, 0),
+    (100, 12, 112),
+    (0, 112, 32),
+    (16, 12, 112),
+    (112, 12, 24),
+    (16, 112, 116),
+];
+
+const PALETTE_DATA: [u16; 120] = [
+    0x7fff, 0x32bf, 0x00d0, 0x0000, 0x639f, 0x4279, 0x15b0, 0x04cb, 0x7fff, 0x6e31, 0x454a, 0x0000,
+    0x7fff, 0x1bef, 0x0200, 0x0000, 0x7fff, 0x421f, 0x1cf2, 0x0000, 0x7fff, 0x5294, 0x294a, 0x0000,
+    0x7fff, 0x03ff, 0x012f, 0x0000, 0x7fff, 0x03ef, 0x01d6, 0x0000, 0x7fff, 0x42b5, 0x3dc8, 0x0000,
+    0x7e74, 0x03ff, 0x0180, 0x0000, 0x67ff, 0x77ac, 0x1a13, 0x2d6b, 0x7ed6, 0x4bff, 0x2175, 0x0000,
+    0x53ff, 0x4a5f, 0x7e52, 0x0000, 0x4fff, 0x7ed2, 0x3a4c, 0x1ce0, 0x03ed, 0x7fff, 0x255f, 0x0000,
+    0x036a, 0x021f, 0x03ff, 0x7fff, 0x7fff, 0x01df, 0x0112, 0x0000, 0x231f, 0x035f, 0x00f2, 0x0009,
+    0x7fff, 0x03ea, 0x011f, 0x0000, 0x299f, 0x001a, 0x000c, 0x0000, 0x7fff, 0x027f, 0x001f, 0x0000,
+    0x7fff, 0x03e0, 0x0206, 0x0120, 0x7fff, 0x7eeb, 0x001f, 0x7c00, 0x7fff, 0x3fff, 0x7e00, 0x001f,
+    0x7fff, 0x03ff, 0x001f, 0x0000, 0x03ff, 0x001f, 0x000c, 0x0000, 0x7fff, 0x033f, 0x0193, 0x0000,
+    0x0000, 0x4200, 0x037f, 0x7fff, 0x7fff, 0x7e8c, 0x7c00, 0x0000, 0x7fff, 0x1bef, 0x6180, 0x0000,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rom_with_title(title: &[u8], licensee: u8) -> Vec<u8> {
+        let mut rom = vec![0; 0x8000];
+        rom[0x0134..(0x0134 + title.len())].copy_from_slice(title);
+        rom[0x014b] = licensee;
+        rom
+    }
+
+    #[test]
+    fn unknown_games_get_default_palette() {
+        let rom = rom_with_title(b"ZELDA", 0x08);
+        assert_eq!(title_index(&rom), 0);
+
+        let rom = rom_with_title(b"NOT A REAL GAME", 0x01);
+        assert_eq!(title_index(&rom), 0);
+    }
+
+    #[test]
+    fn titles_are_found_by_checksum() {
+        let rom = rom_with_title(b"ZELDA", 0x01);
+        assert_eq!(PALETTE_PER_TITLE[title_index(&rom)], 44);
+    }
+
+    #[test]
+    fn shared_checksums_use_fourth_letter() {
+        // Both have a checksum of 0x46
+        let rom = rom_with_title(b"SUPER MARIOLAND", 0x01);
+        assert_eq!(title_index(&rom), 66);
+
+        let rom = rom_with_title(b"METROID2", 0x01);
+        assert_eq!(title_index(&rom), 80);
+    }
+
+    #[test]
+    fn manual_palettes_match_boot_screen() {
+        // Right+A is the default palette
+        let GbPalette::Manual(index) = GbPalette::from_name("Right+A").unwrap() else {
+            panic!("Expected a manual palette");
+        };
+
+        assert_eq!(combination(MANUAL_COMBINATIONS[index]), combination(0));
+    }
+}
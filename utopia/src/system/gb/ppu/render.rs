@@ -1,3 +1,4 @@
+use super::color::{LAYER_BG, LAYER_OBJ0};
 use super::oam::Sprite;
 use super::VRAM_BANK_SIZE;
 use fifo::{BackgroundFifo, BgAttrByte, SpriteFifo, SpritePixel};
@@ -95,15 +96,18 @@ impl super::Ppu {
             };
             self.screen.draw_pixel_cgb(color);
         } else {
-            let color = if sprite_visible {
+            let (layer, color) = if sprite_visible {
                 let sprite_palette = self.dmg_palette_obj[sprite_pixel.palette as usize];
-                (sprite_palette >> (sprite_pixel.color << 1)) & 3
+                (
+                    LAYER_OBJ0 + sprite_pixel.palette as usize,
+                    (sprite_palette >> (sprite_pixel.color << 1)) & 3,
+                )
             } else if self.ctrl.bg_enable {
-                (self.dmg_palette_bg >> (bg_pixel << 1)) & 3
+                (LAYER_BG, (self.dmg_palette_bg >> (bg_pixel << 1)) & 3)
             } else {
-                0
+                (LAYER_BG, 0)
             };
-            self.screen.draw_pixel_dmg(color);
+            self.screen.draw_pixel_dmg(layer, color);
         }
 
         self.render.pos_x += 1;
@@ -1,4 +1,6 @@
+use super::color::DmgColors;
 use crate::util::Rgb;
+use serde::{Deserialize, Serialize};
 
 pub const WIDTH: usize = 160;
 pub const HEIGHT: usize = 144;
@@ -12,31 +14,91 @@ const DMG_COLORS: [Rgb; 4] = [
 
 const CGB_TABLE_SIZE: usize = 32768;
 
-const CGB_COLORS: [Rgb; CGB_TABLE_SIZE] = create_cgb_table();
+// How 15-bit CGB colours are converted to RGB
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GbColorCorrection {
+    // The washed-out colours of the original CGB LCD
+    #[default]
+    Gbc,
+    // The brighter, more saturated backlit screen of the GBA SP (AGS-101)
+    GbaSp,
+    // Linear expansion to 8 bits per channel (as the game's palette data would suggest)
+    None,
+}
+
+pub struct ScreenOptions {
+    pub color_correction: GbColorCorrection,
+    pub frame_blend: bool,
+    pub dmg_colors: DmgColors,
+}
 
 pub struct Screen {
     pixels: [u8; WIDTH * HEIGHT * 4],
+    dmg_colors: [[Rgb; 4]; 3],
+    cgb_colors: Vec<Rgb>,
+    // When frame blending, the previous frame (empty until the first frame is finished) and the
+    // blended output
+    blend: Option<(Vec<u8>, Vec<u8>)>,
     index: usize,
 }
 
 impl Screen {
-    pub fn new() -> Self {
+    pub fn new(options: ScreenOptions) -> Self {
+        let cgb_colors = create_cgb_table(options.color_correction);
+
+        let dmg_colors = match options.dmg_colors {
+            DmgColors::Grey => [DMG_COLORS; 3],
+            DmgColors::Cgb(layers) => {
+                layers.map(|layer| layer.map(|color| cgb_colors[color as usize]))
+            }
+            DmgColors::Rgb(layers) => layers,
+        };
+
         Self {
             pixels: [0; WIDTH * HEIGHT * 4],
+            dmg_colors,
+            cgb_colors,
+            blend: options
+                .frame_blend
+                .then(|| (Vec::new(), vec![0; WIDTH * HEIGHT * 4])),
             index: 0,
         }
     }
 
     pub fn pixels(&self) -> &[u8] {
-        &self.pixels
+        match &self.blend {
+            Some((_, output)) => output,
+            None => &self.pixels,
+        }
     }
 
     pub fn reset(&mut self) {
         self.index = 0;
     }
 
-    pub fn draw_pixel_dmg(&mut self, color: u8) {
-        let rgb = DMG_COLORS[color as usize];
+    // Blends each frame with the one before it, as the slow response of the LCD would. Games
+    // that flicker sprites on alternate frames (for transparency) rely on this.
+    pub fn finish_frame(&mut self) {
+        let Some((previous, output)) = &mut self.blend else {
+            return;
+        };
+
+        // There is nothing to blend the first frame with
+        if previous.is_empty() {
+            previous.extend_from_slice(&self.pixels);
+        }
+
+        for ((output, &current), previous) in
+            output.iter_mut().zip(&self.pixels).zip(previous.iter_mut())
+        {
+            *output = ((current as u16 + *previous as u16 + 1) >> 1) as u8;
+            *previous = current;
+        }
+    }
+
+    pub fn draw_pixel_dmg(&mut self, layer: usize, color: u8) {
+        let rgb = self.dmg_colors[layer][color as usize];
         self.pixels[self.index] = rgb.0;
         self.pixels[self.index + 1] = rgb.1;
         self.pixels[self.index + 2] = rgb.2;
@@ -45,7 +107,7 @@ impl Screen {
     }
 
     pub fn draw_pixel_cgb(&mut self, color: u16) {
-        let rgb = CGB_COLORS[color as usize];
+        let rgb = self.cgb_colors[color as usize];
         self.pixels[self.index] = rgb.0;
         self.pixels[self.index + 1] = rgb.1;
         self.pixels[self.index + 2] = rgb.2;
@@ -54,23 +116,123 @@ impl Screen {
     }
 }
 
-const fn create_cgb_table() -> [Rgb; CGB_TABLE_SIZE] {
-    let mut table = [Rgb(0, 0, 0); CGB_TABLE_SIZE];
-    let mut index = 0;
+fn create_cgb_table(color_correction: GbColorCorrection) -> Vec<Rgb> {
+    (0..CGB_TABLE_SIZE)
+        .map(|index| {
+            let red = index & 0x1f;
+            let green = (index >> 5) & 0x1f;
+            let blue = (index >> 10) & 0x1f;
+
+            match color_correction {
+                GbColorCorrection::Gbc => Rgb(
+                    ((red * 13 + green * 2 + blue) >> 1) as u8,
+                    ((green * 3 + blue) << 1) as u8,
+                    ((red * 3 + green * 2 + blue * 11) >> 1) as u8,
+                ),
+                GbColorCorrection::GbaSp => gba_sp_color(red, green, blue),
+                GbColorCorrection::None => {
+                    let expand = |value: usize| ((value << 3) | (value >> 2)) as u8;
+                    Rgb(expand(red), expand(green), expand(blue))
+                }
+            }
+        })
+        .collect()
+}
+
+// Pokefan531's 'SP101' colour profile for the GBA SP (AGS-101) screen, as used by the colour
+// correction shaders in RetroArch and other emulators. Colours are mixed in linear light, with
+// each output channel taking in some of the other two. The profile's overall luminance
+// adjustment is left out, so that white stays white.
+fn gba_sp_color(red: usize, green: usize, blue: usize) -> Rgb {
+    const GAMMA: f32 = 2.2;
+
+    // Rows are output channels, columns are input channels
+    const MATRIX: [[f32; 3]; 3] = [
+        [0.86, 0.19, -0.05],
+        [0.11, 0.66, 0.23],
+        [0.1325, 0.0575, 0.81],
+    ];
+
+    let linear = [red, green, blue].map(|value| (value as f32 / 31.0).powf(GAMMA));
+
+    let [red, green, blue] = MATRIX.map(|row| {
+        let value: f32 = row
+            .iter()
+            .zip(linear)
+            .map(|(weight, value)| weight * value)
+            .sum();
+        (value.powf(1.0 / GAMMA) * 255.0).round().clamp(0.0, 255.0) as u8
+    });
+
+    Rgb(red, green, blue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn white_stays_white() {
+        for color_correction in [GbColorCorrection::GbaSp, GbColorCorrection::None] {
+            let Rgb(red, green, blue) = create_cgb_table(color_correction)[0x7fff];
+            assert_eq!(
+                (red, green, blue),
+                (255, 255, 255),
+                "{:?}",
+                color_correction
+            );
+        }
+    }
+
+    #[test]
+    fn frame_blend_averages_frames() {
+        let mut screen = Screen::new(ScreenOptions {
+            color_correction: GbColorCorrection::None,
+            frame_blend: true,
+            dmg_colors: DmgColors::Grey,
+        });
+
+        for color in [0x7fff, 0x0000] {
+            screen.reset();
+
+            for _ in 0..(WIDTH * HEIGHT) {
+                screen.draw_pixel_cgb(color);
+            }
 
-    while index < CGB_TABLE_SIZE {
-        let red = index & 0x1f;
-        let green = (index >> 5) & 0x1f;
-        let blue = (index >> 10) & 0x1f;
+            screen.finish_frame();
+        }
+
+        assert_eq!(&screen.pixels()[0..4], &[128, 128, 128, 255]);
+    }
 
-        table[index] = Rgb(
-            ((red * 13 + green * 2 + blue) >> 1) as u8,
-            ((green * 3 + blue) << 1) as u8,
-            ((red * 3 + green * 2 + blue * 11) >> 1) as u8,
-        );
+    #[test]
+    fn first_blended_frame_is_not_darkened() {
+        let mut screen = Screen::new(ScreenOptions {
+            color_correction: GbColorCorrection::None,
+            frame_blend: true,
+            dmg_colors: DmgColors::Grey,
+        });
+
+        for _ in 0..(WIDTH * HEIGHT) {
+            screen.draw_pixel_cgb(0x7fff);
+        }
 
-        index += 1;
+        screen.finish_frame();
+        assert_eq!(&screen.pixels()[0..4], &[255, 255, 255, 255]);
     }
 
-    table
+    #[test]
+    fn gba_sp_profile() {
+        let table = create_cgb_table(GbColorCorrection::GbaSp);
+
+        // Greys are unaffected, as each row of the matrix adds up to one
+        for value in [0x00, 0x08, 0x10, 0x1f] {
+            let Rgb(red, green, blue) = table[value | (value << 5) | (value << 10)];
+            assert!(red == green && green == blue, "{:02X}", value);
+        }
+
+        // Pure red loses some of its saturation, taking on a little green and blue
+        let Rgb(red, green, blue) = table[0x001f];
+        assert!(red > 230 && green > 0 && blue > green);
+    }
 }
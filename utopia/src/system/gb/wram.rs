@@ -25,6 +25,14 @@ impl Wram {
         self.bank_value
     }
 
+    pub fn peek(&self, offset: usize) -> u8 {
+        self.data[offset]
+    }
+
+    pub fn poke(&mut self, offset: usize, value: u8) {
+        self.data[offset] = value;
+    }
+
     pub fn set_bank(&mut self, value: u8) {
         self.bank_value = value & 0x07;
 
@@ -1,7 +1,8 @@
 use crate::core::arm7tdmi::{Bus, Core, Mode, State};
 use crate::util::memory::{Memory, Reader, Value, Writer};
 use crate::{
-    BiosLoader, InstanceOptions, JoypadState, MemoryMapper, Size, SystemOptions, WgpuContext,
+    BiosLoader, InstanceOptions, JoypadState, MemoryMapper, RomInfo, Size, SystemOptions,
+    SystemType, WgpuContext,
 };
 use audio::Audio;
 use cartridge::Cartridge;
@@ -59,11 +60,23 @@ impl<'a, T: MemoryMapper> crate::System<T> for System<'a, T> {
             result.map_err(|err| crate::Error(err.to_string()))?,
         ))
     }
+
+    fn rom_info(&self, rom_data: &[u8]) -> Result<RomInfo, crate::Error> {
+        if rom_data.len() < 0xc0 {
+            return Err("ROM is too small to contain a header".into());
+        }
+
+        let mut info = RomInfo::new(SystemType::GameBoyAdvance, rom_data);
+        info.add("Title", cartridge::title(rom_data));
+        info.add("ROM Size", rom_data.len());
+        info.add("Backup Type", cartridge::backup_type(rom_data));
+        Ok(info)
+    }
 }
 
 pub struct Instance {
     core: Core<Hardware>,
-    _wgpu_context: WgpuContext,
+    _wgpu_context: Option<WgpuContext>,
 }
 
 impl Instance {
@@ -95,7 +108,7 @@ impl Instance {
 }
 
 impl crate::Instance for Instance {
-    fn run_frame(&mut self, _joypad_state: &JoypadState) {
+    fn run_frame(&mut self, _ports: &[Option<JoypadState>]) {
         let core = &mut self.core;
 
         loop {
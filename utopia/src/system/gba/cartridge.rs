@@ -4,7 +4,7 @@ use subslice::SubsliceExt;
 use tracing::{info, warn};
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
-enum BackupType {
+pub enum BackupType {
     None,
     Eeprom,
     Sram,
@@ -26,23 +26,8 @@ pub struct Cartridge {
 
 impl Cartridge {
     pub fn new(rom: Vec<u8>) -> Self {
-        let title = String::from_utf8_lossy(&rom[0xa0..=0xab]).into_owned();
-
-        let backup_type =
-            BACKUP_TYPES
-                .iter()
-                .fold(BackupType::None, |acc, (id_string, backup_type)| {
-                    if rom.find(id_string.as_bytes()).is_none() {
-                        return acc;
-                    }
-
-                    if acc != BackupType::None {
-                        warn!("ROM contains multiple backup ID strings");
-                        return acc;
-                    }
-
-                    *backup_type
-                });
+        let title = title(&rom);
+        let backup_type = backup_type(&rom);
 
         info!("Title: {}", title);
         info!("ROM Size: {}", rom.len());
@@ -78,3 +63,24 @@ impl fmt::Display for BackupType {
         }
     }
 }
+
+pub fn title(rom: &[u8]) -> String {
+    String::from_utf8_lossy(&rom[0xa0..=0xab]).into_owned()
+}
+
+pub fn backup_type(rom: &[u8]) -> BackupType {
+    BACKUP_TYPES
+        .iter()
+        .fold(BackupType::None, |acc, (id_string, backup_type)| {
+            if rom.find(id_string.as_bytes()).is_none() {
+                return acc;
+            }
+
+            if acc != BackupType::None {
+                warn!("ROM contains multiple backup ID strings");
+                return acc;
+            }
+
+            *backup_type
+        })
+}
@@ -1,6 +1,11 @@
+use crate::achievement::{Achievements, MemoryView};
+use crate::cheat::{Cheats, MemoryPoke};
 use crate::core::mips::{self, Core, InitialState, NullCp2};
 use crate::util::memory::{Memory, Reader, Value, Writer};
-use crate::{InstanceOptions, JoypadState, MemoryMapper, Size, SystemOptions, WgpuContext};
+use crate::{
+    AchievementQueue, AchievementSet, CheatSet, InstanceOptions, JoypadState, MemoryMapper,
+    RomInfo, Size, SystemOptions, SystemType, WgpuContext,
+};
 use audio::AudioInterface;
 use interrupt::{CpuInterrupt, RcpInterrupt};
 use mips_interface::MipsInterface;
@@ -54,6 +59,10 @@ impl<T: MemoryMapper> crate::System<T> for System<T> {
         None
     }
 
+    fn requires_wgpu_context(&self) -> bool {
+        true
+    }
+
     fn create_instance(
         &self,
         options: InstanceOptions,
@@ -64,10 +73,28 @@ impl<T: MemoryMapper> crate::System<T> for System<T> {
             result.map_err(|err| crate::Error(err.to_string()))?,
         ))
     }
+
+    fn rom_info(&self, rom_data: &[u8]) -> Result<RomInfo, crate::Error> {
+        if rom_data.len() < 0x40 {
+            return Err("ROM is too small to contain a header".into());
+        }
+
+        let rom_data = to_big_endian(rom_data.to_vec());
+        let mut info = RomInfo::new(SystemType::Nintendo64, &rom_data);
+        info.add(
+            "Title",
+            String::from_utf8_lossy(&rom_data[0x20..0x34]).trim_end(),
+        );
+        info.add("Game Code", String::from_utf8_lossy(&rom_data[0x3b..0x3f]));
+        info.add("ROM Size", rom_data.len());
+        Ok(info)
+    }
 }
 
 pub struct Instance {
     core: Core<Bus>,
+    achievements: Achievements,
+    cheats: Cheats,
 }
 
 impl Instance {
@@ -82,21 +109,52 @@ impl Instance {
             regs,
         };
 
+        let rom_data = to_big_endian(options.rom_data);
+
+        let ctx = options
+            .wgpu_context
+            .ok_or("Nintendo 64 emulation requires a wgpu context")?;
+
         Ok(Self {
             core: Core::new(
-                Bus::new(options.wgpu_context, options.rom_data),
+                Bus::new(ctx, rom_data),
                 Cp0::new(),
                 Cp1::new(),
                 NullCp2,
                 initial_state,
             ),
+            achievements: Achievements::new(),
+            cheats: Cheats::new(),
         })
     }
 }
 
+fn to_big_endian(mut rom_data: Vec<u8>) -> Vec<u8> {
+    match rom_data.get(0..4) {
+        Some([0x37, 0x80, 0x40, 0x12]) => {
+            debug!("Converting byte-swapped ROM to big endian");
+
+            for chunk in rom_data.chunks_exact_mut(2) {
+                chunk.swap(0, 1);
+            }
+        }
+        Some([0x40, 0x12, 0x37, 0x80]) => {
+            debug!("Converting little endian ROM to big endian");
+
+            for chunk in rom_data.chunks_exact_mut(4) {
+                chunk.reverse();
+            }
+        }
+        _ => (),
+    }
+
+    rom_data
+}
+
 impl crate::Instance for Instance {
-    fn run_frame(&mut self, joypad_state: &JoypadState) {
-        self.core.bus_mut().si.pif_mut().update_joypad(joypad_state);
+    fn run_frame(&mut self, ports: &[Option<JoypadState>]) {
+        self.cheats.apply(self.core.bus_mut());
+        self.core.bus_mut().si.pif_mut().update_joypads(ports);
         self.core.bus_mut().vi.reset_frame_complete();
 
         while !self.core.bus().vi.frame_complete() {
@@ -107,11 +165,30 @@ impl crate::Instance for Instance {
         let rdram = bus.rdram.data_mut();
         bus.rdp.sync(rdram);
         bus.vi.update(rdram).unwrap();
+
+        self.achievements.evaluate(self.core.bus());
     }
 
     fn present(&self, canvas: &wgpu::Texture) {
         self.core.bus().vi.render(canvas);
     }
+
+    fn pixels(&self) -> Option<(&[u8], Size)> {
+        Some(self.core.bus().vi.pixels())
+    }
+
+    fn load_achievements(&mut self, set: AchievementSet) -> Result<(), crate::Error> {
+        self.achievements.load(set)
+    }
+
+    fn achievement_events(&mut self) -> Option<&mut AchievementQueue> {
+        Some(self.achievements.events())
+    }
+
+    fn load_cheats(&mut self, set: CheatSet) -> Result<(), crate::Error> {
+        self.cheats.load(set);
+        Ok(())
+    }
 }
 
 struct Bus {
@@ -151,6 +228,24 @@ impl Bus {
     }
 }
 
+impl MemoryView for Bus {
+    fn peek(&self, address: u32) -> u8 {
+        self.rdram
+            .data()
+            .get(address as usize)
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+impl MemoryPoke for Bus {
+    fn poke(&mut self, address: u32, value: u8) {
+        if let Some(byte) = self.rdram.data_mut().get_mut(address as usize) {
+            *byte = value;
+        }
+    }
+}
+
 impl mips::Bus for Bus {
     const NAME: &'static str = "VR4300";
     const ENABLE_64_BIT: bool = true;
@@ -1,5 +1,5 @@
-use std::cell::Cell;
-use std::rc::Rc;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
 use tracing::{debug, debug_span};
 
 #[repr(u8)]
@@ -24,34 +24,36 @@ pub enum RcpIntType {
 
 #[derive(Clone)]
 pub struct CpuInterrupt {
-    status: Rc<Cell<u8>>,
+    status: Arc<AtomicU8>,
 }
 
 impl CpuInterrupt {
     pub fn new() -> Self {
         Self {
-            status: Rc::new(Cell::new(0)),
+            status: Arc::new(AtomicU8::new(0)),
         }
     }
 
     pub fn status(&self) -> u8 {
-        self.status.get()
+        self.status.load(Ordering::Relaxed)
     }
 
     pub fn raise(&mut self, int_type: CpuIntType) {
-        let prev_status = self.status.get();
-        self.status.set(prev_status | int_type as u8);
+        let prev_status = self.status.load(Ordering::Relaxed);
+        self.status
+            .store(prev_status | int_type as u8, Ordering::Relaxed);
 
-        if self.status.get() != prev_status {
+        if self.status.load(Ordering::Relaxed) != prev_status {
             debug!("CPU Interrupt Raised: {:?}", int_type);
         }
     }
 
     pub fn clear(&mut self, int_type: CpuIntType) {
-        let prev_status = self.status.get();
-        self.status.set(prev_status & !(int_type as u8));
+        let prev_status = self.status.load(Ordering::Relaxed);
+        self.status
+            .store(prev_status & !(int_type as u8), Ordering::Relaxed);
 
-        if self.status.get() != prev_status {
+        if self.status.load(Ordering::Relaxed) != prev_status {
             debug!("CPU Interrupt Cleared: {:?}", int_type);
         }
     }
@@ -60,44 +62,45 @@ impl CpuInterrupt {
 #[derive(Clone)]
 pub struct RcpInterrupt {
     cpu_interrupt: CpuInterrupt,
-    mask: Rc<Cell<u8>>,
-    status: Rc<Cell<u8>>,
+    mask: Arc<AtomicU8>,
+    status: Arc<AtomicU8>,
 }
 
 impl RcpInterrupt {
     pub fn new(cpu_interrupt: CpuInterrupt) -> Self {
         Self {
             cpu_interrupt,
-            mask: Rc::new(Cell::new(0)),
-            status: Rc::new(Cell::new(0)),
+            mask: Arc::new(AtomicU8::new(0)),
+            status: Arc::new(AtomicU8::new(0)),
         }
     }
 
     pub fn mask(&self) -> u8 {
-        self.mask.get()
+        self.mask.load(Ordering::Relaxed)
     }
 
     pub fn set_mask(&mut self, mask: u8) {
-        self.mask.set(mask);
+        self.mask.store(mask, Ordering::Relaxed);
         debug!("RCP Interrupt Mask: {:06b}", mask);
         self.update();
     }
 
     pub fn status(&self) -> u8 {
-        self.status.get()
+        self.status.load(Ordering::Relaxed)
     }
 
     pub fn has(&self, int_type: RcpIntType) -> bool {
-        (self.status.get() & int_type as u8) != 0
+        (self.status.load(Ordering::Relaxed) & int_type as u8) != 0
     }
 
     pub fn raise(&mut self, int_type: RcpIntType) {
-        let prev_status = self.status.get();
-        self.status.set(prev_status | int_type as u8);
+        let prev_status = self.status.load(Ordering::Relaxed);
+        self.status
+            .store(prev_status | int_type as u8, Ordering::Relaxed);
 
         let _span = debug_span!("main").entered();
 
-        if self.status.get() != prev_status {
+        if self.status.load(Ordering::Relaxed) != prev_status {
             debug!("RCP Interrupt Raised: {:?}", int_type);
         }
 
@@ -105,12 +108,13 @@ impl RcpInterrupt {
     }
 
     pub fn clear(&mut self, int_type: RcpIntType) {
-        let prev_status = self.status.get();
-        self.status.set(prev_status & !(int_type as u8));
+        let prev_status = self.status.load(Ordering::Relaxed);
+        self.status
+            .store(prev_status & !(int_type as u8), Ordering::Relaxed);
 
         let _span = debug_span!("main").entered();
 
-        if self.status.get() != prev_status {
+        if self.status.load(Ordering::Relaxed) != prev_status {
             debug!("RCP Interrupt Cleared: {:?}", int_type);
         }
 
@@ -118,7 +122,7 @@ impl RcpInterrupt {
     }
 
     fn update(&mut self) {
-        let active = self.status.get() & self.mask.get();
+        let active = self.status.load(Ordering::Relaxed) & self.mask.load(Ordering::Relaxed);
 
         if active != 0 {
             self.cpu_interrupt.raise(CpuIntType::Rcp);
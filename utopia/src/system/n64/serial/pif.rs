@@ -1,3 +1,4 @@
+use crate::system;
 use crate::util::memory::{Memory, Value};
 use crate::JoypadState;
 use arrayvec::ArrayVec;
@@ -9,7 +10,7 @@ const PIF_RAM_START: usize = 0x7c0;
 pub struct Pif {
     data: Memory,
     input: [u8; 64],
-    joypads: [[u8; 4]; 4],
+    joypads: [Option<[u8; 4]>; 4],
 }
 
 impl Pif {
@@ -17,7 +18,7 @@ impl Pif {
         Self {
             data: Memory::new(PIF_SIZE),
             input: [0; 64],
-            joypads: [[0; 4]; 4],
+            joypads: [None; 4],
         }
     }
 
@@ -39,62 +40,10 @@ impl Pif {
         self.input.copy_from_slice(&self.data[PIF_RAM_START..]);
     }
 
-    pub fn update_joypad(&mut self, state: &JoypadState) {
-        let JoypadState { buttons, axes } = &state;
-        let joypad = &mut self.joypads[0];
-
-        joypad[0] = 0;
-
-        // A, B, Z, Start, D-Up, D-Down, D-Left, D-Right
-        joypad[0] |= if buttons[0] { 0x80 } else { 0 };
-        joypad[0] |= if buttons[2] { 0x40 } else { 0 };
-        joypad[0] |= if buttons[4] { 0x20 } else { 0 };
-        joypad[0] |= if buttons[9] { 0x10 } else { 0 };
-        joypad[0] |= if buttons[12] { 0x08 } else { 0 };
-        joypad[0] |= if buttons[13] { 0x04 } else { 0 };
-        joypad[0] |= if buttons[14] { 0x02 } else { 0 };
-        joypad[0] |= if buttons[15] { 0x01 } else { 0 };
-
-        // RST 'button' possibly doesn't need to be implemented?
-        joypad[1] = 0;
-
-        // L
-        joypad[1] |= if buttons[6] { 0x20 } else { 0 };
-
-        // R
-        joypad[1] |= if buttons[5] | buttons[7] { 0x10 } else { 0 };
-
-        // C-Up
-        joypad[1] |= if axes[3] > (i32::MAX / 4 * 3) {
-            0x08
-        } else {
-            0
-        };
-
-        // C-Down
-        joypad[1] |= if buttons[1] || axes[3] < (i32::MIN / 4 * 3) {
-            0x04
-        } else {
-            0
-        };
-
-        // C-Left
-        joypad[1] |= if buttons[3] || axes[2] < (i32::MIN / 4 * 3) {
-            0x02
-        } else {
-            0
-        };
-
-        // C-Right
-        joypad[1] |= if axes[2] > (i32::MAX / 4 * 3) {
-            0x01
-        } else {
-            0
-        };
-
-        // Joystick
-        joypad[2] = ((axes[0] / 20 * 13) >> 24) as u8;
-        joypad[3] = ((axes[1] / 20 * 13) >> 24) as u8;
+    pub fn update_joypads(&mut self, ports: &[Option<JoypadState>]) {
+        for (channel, joypad) in self.joypads.iter_mut().enumerate() {
+            *joypad = system::joypad(ports, channel).map(encode_joypad);
+        }
     }
 
     pub fn process(&mut self) {
@@ -169,18 +118,81 @@ impl Pif {
     }
 }
 
-fn query_joybus(joypads: &[[u8; 4]; 4], channel: usize, input: &[u8]) -> Option<ArrayVec<u8, 64>> {
+fn encode_joypad(state: &JoypadState) -> [u8; 4] {
+    let JoypadState { buttons, axes } = state;
+    let mut joypad = [0; 4];
+
+    // A, B, Z, Start, D-Up, D-Down, D-Left, D-Right
+    joypad[0] |= if buttons[0] { 0x80 } else { 0 };
+    joypad[0] |= if buttons[2] { 0x40 } else { 0 };
+    joypad[0] |= if buttons[4] { 0x20 } else { 0 };
+    joypad[0] |= if buttons[9] { 0x10 } else { 0 };
+    joypad[0] |= if buttons[12] { 0x08 } else { 0 };
+    joypad[0] |= if buttons[13] { 0x04 } else { 0 };
+    joypad[0] |= if buttons[14] { 0x02 } else { 0 };
+    joypad[0] |= if buttons[15] { 0x01 } else { 0 };
+
+    // RST 'button' possibly doesn't need to be implemented?
+    joypad[1] = 0;
+
+    // L
+    joypad[1] |= if buttons[6] { 0x20 } else { 0 };
+
+    // R
+    joypad[1] |= if buttons[5] | buttons[7] { 0x10 } else { 0 };
+
+    // C-Up
+    joypad[1] |= if axes[3] > (i32::MAX / 4 * 3) {
+        0x08
+    } else {
+        0
+    };
+
+    // C-Down
+    joypad[1] |= if buttons[1] || axes[3] < (i32::MIN / 4 * 3) {
+        0x04
+    } else {
+        0
+    };
+
+    // C-Left
+    joypad[1] |= if buttons[3] || axes[2] < (i32::MIN / 4 * 3) {
+        0x02
+    } else {
+        0
+    };
+
+    // C-Right
+    joypad[1] |= if axes[2] > (i32::MAX / 4 * 3) {
+        0x01
+    } else {
+        0
+    };
+
+    // Joystick
+    joypad[2] = ((axes[0] / 20 * 13) >> 24) as u8;
+    joypad[3] = ((axes[1] / 20 * 13) >> 24) as u8;
+
+    joypad
+}
+
+fn query_joybus(
+    joypads: &[Option<[u8; 4]>; 4],
+    channel: usize,
+    input: &[u8],
+) -> Option<ArrayVec<u8, 64>> {
     let mut output = ArrayVec::new();
 
     match input[0] {
         0x00 | 0xff => {
             match channel {
-                0 => {
+                0..=3 => {
+                    // Channels without a controller plugged in don't respond
+                    joypads[channel]?;
                     output.push(0x05);
                     output.push(0x00);
                     output.push(0x02); // TODO: Controller Pak
                 }
-                1..=3 => return None,
                 4 => {
                     // Provide 4 Kbit EEPROM by default
                     // TODO: Support other EEPROM sizes
@@ -196,7 +208,7 @@ fn query_joybus(joypads: &[[u8; 4]; 4], channel: usize, input: &[u8]) -> Option<
                 panic!("Invalid JoyBus channel: {}", channel);
             }
 
-            output.try_extend_from_slice(&joypads[channel]).unwrap();
+            output.try_extend_from_slice(&joypads[channel]?).unwrap();
         }
         0x02 => {
             if channel > 3 {
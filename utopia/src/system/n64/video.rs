@@ -25,6 +25,7 @@ pub struct VideoInterface {
     regs: Registers,
     rcp_int: RcpInterrupt,
     pixels: Vec<u8>,
+    source_size: Size,
     upscaler: Upscaler,
 }
 
@@ -47,6 +48,7 @@ impl VideoInterface {
             },
             rcp_int,
             pixels: Vec::new(),
+            source_size: Self::MIN_SOURCE_SIZE,
             upscaler: Upscaler::new(ctx, Self::MIN_SOURCE_SIZE, Self::DEFAULT_TARGET_SIZE, true),
         }
     }
@@ -117,6 +119,7 @@ impl VideoInterface {
         );
 
         self.upscaler.set_source_size(source_size);
+        self.source_size = source_size;
 
         self.pixels.resize(
             source_size.width as usize * source_size.height as usize * 4,
@@ -166,14 +169,17 @@ impl VideoInterface {
             }
         }
 
-        self.upscaler.update(&self.pixels);
-
         Ok(())
     }
 
     pub fn render(&self, canvas: &wgpu::Texture) {
+        self.upscaler.update(&self.pixels);
         self.upscaler.render(canvas);
     }
+
+    pub fn pixels(&self) -> (&[u8], Size) {
+        (&self.pixels, self.source_size)
+    }
 }
 
 impl Reader for VideoInterface {
@@ -1,8 +1,15 @@
+use crate::achievement::{Achievements, MemoryView};
+use crate::cheat::{Cheats, MemoryPoke};
 use crate::core::mos6502::{self, Bus, Core};
+use crate::database;
+use crate::util::audio::AudioFilter;
+use crate::util::ntsc::{NtscDecoder, NtscFilter};
+use crate::util::state::{self, Snapshot, State};
 use crate::util::upscaler::Upscaler;
 use crate::util::MirrorVec;
 use crate::{
-    AudioQueue, Error, InstanceOptions, JoypadState, Mapped, MemoryMapper, Size, SystemOptions,
+    AchievementQueue, AchievementSet, AudioQueue, CheatSet, Error, InstanceOptions, JoypadState,
+    Mapped, MemoryMapper, RomInfo, Size, SystemOptions, SystemType,
 };
 use apu::Apu;
 use bitflags::bitflags;
@@ -13,11 +20,16 @@ use ppu::Ppu;
 use std::fmt;
 use tracing::trace;
 
+pub use ppu::NesPalette;
+
 const WRAM_SIZE: usize = 2048;
 const CLIP_LINES: usize = 8;
 const WIDTH: u32 = ppu::WIDTH as u32;
 const HEIGHT: u32 = (ppu::HEIGHT - CLIP_LINES * 2) as u32;
 
+// A pixel lasts for four master clocks, or eight phases of the colour subcarrier
+const NTSC_SAMPLES_PER_PIXEL: usize = 8;
+
 mod apu;
 mod cartridge;
 mod interrupt;
@@ -26,12 +38,18 @@ mod ppu;
 
 pub struct System<'a, T: MemoryMapper + 'static> {
     memory_mapper: &'a T,
+    audio_filter: AudioFilter,
+    ntsc_filter: NtscFilter,
+    palette: NesPalette,
 }
 
 impl<'a, T: MemoryMapper> System<'a, T> {
     pub fn new(options: SystemOptions<'a, T>) -> Self {
         Self {
             memory_mapper: options.memory_mapper,
+            audio_filter: options.audio_filter,
+            ntsc_filter: options.ntsc_filter,
+            palette: options.nes_palette,
         }
     }
 }
@@ -46,31 +64,106 @@ impl<'a, T: MemoryMapper> crate::System<T> for System<'a, T> {
     }
 
     fn create_instance(&self, options: InstanceOptions) -> Result<Box<dyn crate::Instance>, Error> {
-        Ok(Box::new(Instance::new(self.memory_mapper, options)?))
+        Ok(Box::new(Instance::new(
+            self.memory_mapper,
+            self.audio_filter,
+            self.ntsc_filter,
+            &self.palette,
+            options,
+        )?))
+    }
+
+    fn rom_info(&self, rom_data: &[u8]) -> Result<RomInfo, Error> {
+        let mut info = RomInfo::new(SystemType::Nes, rom_data);
+        let header = cartridge::Header::parse(rom_data, info.overrides)?;
+
+        info.add("Mapper Number", header.mapper_number);
+        info.add("PRG ROM Size", header.prg_rom_size);
+
+        if header.chr_rom_size > 0 {
+            info.add("CHR ROM Size", header.chr_rom_size);
+        } else {
+            info.add("CHR RAM Size", cartridge::CHR_RAM_SIZE);
+        }
+
+        info.add("Mirror Mode", format!("{:?}", header.mirror_mode));
+        info.add("Battery Backed", header.battery_backed);
+        Ok(info)
     }
 }
 
 pub struct Instance<T: Mapped> {
     core: Core<Hardware<T>>,
-    upscaler: Upscaler,
+    upscaler: Option<Upscaler>,
+    achievements: Achievements,
+    cheats: Cheats,
+    ntsc: NtscDecoder,
+    ntsc_pixels: Vec<u8>,
 }
 
 impl<T: Mapped> Instance<T> {
     pub fn new(
         memory_mapper: &impl MemoryMapper<Mapped = T>,
+        audio_filter: AudioFilter,
+        ntsc_filter: NtscFilter,
+        palette: &NesPalette,
         options: InstanceOptions,
     ) -> Result<Self, Error> {
-        let hw = Hardware::new(options.rom_data, memory_mapper)?;
+        let hw = Hardware::new(options.rom_data, memory_mapper, audio_filter, palette)?;
         let core = Core::new(hw);
+        let ntsc = NtscDecoder::new(ntsc_filter);
+
+        let upscaler = options
+            .wgpu_context
+            .map(|ctx| Upscaler::new(ctx, output_size(&ntsc), options.output_resolution, false));
+
+        Ok(Instance {
+            core,
+            upscaler,
+            achievements: Achievements::new(),
+            cheats: Cheats::new(),
+            ntsc,
+            ntsc_pixels: Vec::new(),
+        })
+    }
+
+    fn output_pixels(&self) -> &[u8] {
+        if self.ntsc.is_enabled() {
+            &self.ntsc_pixels
+        } else {
+            self.clipped_pixels()
+        }
+    }
+
+    // Decoded lines are doubled up, to keep the aspect ratio the same
+    fn decode_ntsc(&mut self) {
+        let indices = self.core.bus().ppu.indices();
+        let pitch = NtscDecoder::output_width(ppu::WIDTH, NTSC_SAMPLES_PER_PIXEL) * 4;
+
+        self.ntsc_pixels.resize(pitch * HEIGHT as usize * 2, 0);
+        self.ntsc.next_frame();
+
+        for (index, rows) in self.ntsc_pixels.chunks_exact_mut(pitch * 2).enumerate() {
+            let line = index + CLIP_LINES;
+            let (row, next_row) = rows.split_at_mut(pitch);
 
-        let upscaler = Upscaler::new(
-            options.wgpu_context,
-            (WIDTH, HEIGHT).into(),
-            options.output_resolution,
-            false,
-        );
+            self.ntsc.decode_line(
+                line,
+                &indices[(line * ppu::WIDTH)..((line + 1) * ppu::WIDTH)],
+                NTSC_SAMPLES_PER_PIXEL,
+                ppu::signal,
+                row,
+            );
 
-        Ok(Instance { core, upscaler })
+            next_row.copy_from_slice(row);
+        }
+    }
+
+    fn clipped_pixels(&self) -> &[u8] {
+        let pixels = self.core.bus().ppu.pixels();
+        let start = CLIP_LINES * ppu::WIDTH * 4;
+        let end = pixels.len() - start;
+        &pixels[start..end]
     }
 }
 
@@ -83,10 +176,53 @@ impl<T: Mapped> crate::Instance for Instance<T> {
         Some(self.core.bus_mut().apu.audio_queue())
     }
 
-    fn run_frame(&mut self, joypad_state: &JoypadState) {
+    fn audio_channels(&self) -> &[&'static str] {
+        self.core.bus().apu.audio_channels().names()
+    }
+
+    fn set_audio_channel_muted(&mut self, index: usize, muted: bool) {
+        self.core
+            .bus_mut()
+            .apu
+            .audio_channels_mut()
+            .set_muted(index, muted);
+    }
+
+    fn audio_channel_muted(&self, index: usize) -> bool {
+        self.core.bus().apu.audio_channels().is_muted(index)
+    }
+
+    fn set_stems_enabled(&mut self, enabled: bool) {
+        self.core
+            .bus_mut()
+            .apu
+            .audio_channels_mut()
+            .set_stems_enabled(enabled);
+    }
+
+    fn stem_queues(&mut self) -> Option<&mut [AudioQueue]> {
+        Some(self.core.bus_mut().apu.audio_channels_mut().stem_queues())
+    }
+
+    fn load_achievements(&mut self, set: AchievementSet) -> Result<(), Error> {
+        self.achievements.load(set)
+    }
+
+    fn achievement_events(&mut self) -> Option<&mut AchievementQueue> {
+        Some(self.achievements.events())
+    }
+
+    fn load_cheats(&mut self, set: CheatSet) -> Result<(), Error> {
+        self.cheats.load(set);
+        Ok(())
+    }
+
+    fn run_frame(&mut self, ports: &[Option<JoypadState>]) {
+        self.cheats.apply(self.core.bus_mut());
+
         let core = &mut self.core;
 
-        core.bus_mut().joypad.update(joypad_state);
+        core.bus_mut().joypad.update(ports);
         core.bus_mut().ppu.start_frame();
 
         while !core.bus().ppu.ready() {
@@ -94,14 +230,39 @@ impl<T: Mapped> crate::Instance for Instance<T> {
             trace!("{}", core);
         }
 
-        let pixels = self.core.bus().ppu.pixels();
-        let start = CLIP_LINES * ppu::WIDTH * 4;
-        let end = pixels.len() - start;
-        self.upscaler.update(&pixels[start..end]);
+        self.achievements.evaluate(self.core.bus());
+
+        if self.ntsc.is_enabled() {
+            self.decode_ntsc();
+        }
     }
 
     fn present(&self, canvas: &wgpu::Texture) {
-        self.upscaler.render(canvas);
+        if let Some(upscaler) = &self.upscaler {
+            upscaler.update(self.output_pixels());
+            upscaler.render(canvas);
+        }
+    }
+
+    fn pixels(&self) -> Option<(&[u8], Size)> {
+        Some((self.output_pixels(), output_size(&self.ntsc)))
+    }
+
+    fn save_state(&mut self) -> Option<Vec<u8>> {
+        Some(state::save(&mut self.core))
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> Result<(), Error> {
+        state::load(&mut self.core, data)
+    }
+}
+
+fn output_size(ntsc: &NtscDecoder) -> Size {
+    if ntsc.is_enabled() {
+        let width = NtscDecoder::output_width(ppu::WIDTH, NTSC_SAMPLES_PER_PIXEL);
+        (width as u32, HEIGHT * 2).into()
+    } else {
+        (WIDTH, HEIGHT).into()
     }
 }
 
@@ -130,19 +291,25 @@ impl<T: Mapped> Hardware<T> {
     pub fn new(
         rom_data: Vec<u8>,
         memory_mapper: &impl MemoryMapper<Mapped = T>,
+        audio_filter: AudioFilter,
+        palette: &NesPalette,
     ) -> Result<Self, Error> {
         let interrupt = Interrupt::new();
+        let overrides = database::overrides(SystemType::Nes, &rom_data);
+        let colors = palette.colors(cartridge::Header::parse(&rom_data, overrides)?.arcade);
+        let cartridge = Cartridge::new(rom_data, memory_mapper, interrupt.clone(), overrides)?;
+        let apu = Apu::new(interrupt.clone(), audio_filter, cartridge.audio_name());
 
         Ok(Self {
             dma_request: DmaRequest::empty(),
             dma_oam_src: 0,
             cycles: 0,
             mdr: 0,
-            cartridge: Cartridge::new(rom_data, memory_mapper, interrupt.clone())?,
+            cartridge,
             wram: MirrorVec::new(WRAM_SIZE),
             joypad: Joypad::new(),
-            ppu: Ppu::new(interrupt.clone()),
-            apu: Apu::new(interrupt.clone()),
+            ppu: Ppu::new(interrupt.clone(), colors),
+            apu,
             interrupt,
         })
     }
@@ -264,6 +431,44 @@ impl<T: Mapped> Bus for Hardware<T> {
     }
 }
 
+impl<T: Mapped> Snapshot for Hardware<T> {
+    fn snapshot(&mut self, state: &mut State) {
+        let mut dma_request = self.dma_request.bits();
+        dma_request.snapshot(state);
+        self.dma_request = DmaRequest::from_bits_retain(dma_request);
+
+        self.dma_oam_src.snapshot(state);
+        self.cycles.snapshot(state);
+        self.mdr.snapshot(state);
+        self.interrupt.snapshot(state);
+        self.cartridge.snapshot(state);
+        self.wram.snapshot(state);
+        self.joypad.snapshot(state);
+        self.ppu.snapshot(state);
+        self.apu.snapshot(state);
+    }
+}
+
+impl<T: Mapped> MemoryView for Hardware<T> {
+    fn peek(&self, address: u32) -> u8 {
+        match address {
+            0x0000..=0x1fff => self.wram[address as usize],
+            0x6000..=0x7fff => self.cartridge.peek_prg_ram(address as u16),
+            _ => 0,
+        }
+    }
+}
+
+impl<T: Mapped> MemoryPoke for Hardware<T> {
+    fn poke(&mut self, address: u32, value: u8) {
+        match address {
+            0x0000..=0x1fff => self.wram[address as usize] = value,
+            0x6000..=0x7fff => self.cartridge.poke_prg_ram(address as u16, value),
+            _ => (),
+        }
+    }
+}
+
 impl<T: Mapped> fmt::Display for Hardware<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -275,3 +480,116 @@ impl<T: Mapped> fmt::Display for Hardware<T> {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DefaultMemoryMapper, Instance as _};
+
+    // Plays a square wave on the first pulse channel, then loops forever
+    fn pulse_instance() -> Instance<<DefaultMemoryMapper as MemoryMapper>::Mapped> {
+        let program = [
+            0xa9, 0x01, 0x8d, 0x15, 0x40, // LDA #$01; STA $4015
+            0xa9, 0xbf, 0x8d, 0x00, 0x40, // LDA #$BF; STA $4000
+            0xa9, 0xfd, 0x8d, 0x02, 0x40, // LDA #$FD; STA $4002
+            0xa9, 0x00, 0x8d, 0x03, 0x40, // LDA #$00; STA $4003
+            0x4c, 0x14, 0x80, // JMP $8014
+        ];
+
+        let mut rom_data = b"NES\x1a\x01\x01\x00\x00".to_vec();
+        rom_data.resize(16, 0);
+
+        let mut prg_rom = vec![0xea; 16384];
+        prg_rom[..program.len()].copy_from_slice(&program);
+        prg_rom[0x3ffa..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x00, 0x80]);
+        rom_data.extend(prg_rom);
+        rom_data.resize(rom_data.len() + 8192, 0);
+
+        Instance::new(
+            &DefaultMemoryMapper,
+            AudioFilter::Raw,
+            NtscFilter::None,
+            &NesPalette::default(),
+            InstanceOptions {
+                rom_data,
+                wgpu_context: None,
+                output_resolution: (WIDTH, HEIGHT).into(),
+            },
+        )
+        .unwrap()
+    }
+
+    fn variance(queue: &mut AudioQueue) -> f32 {
+        let samples: Vec<f32> = queue.drain(..).map(|(left, _)| left).collect();
+        let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+
+        samples
+            .iter()
+            .map(|sample| (sample - mean).powi(2))
+            .sum::<f32>()
+            / samples.len() as f32
+    }
+
+    fn run(instance: &mut impl crate::Instance) -> f32 {
+        // Skip the first few frames, while the program sets up the channel
+        for _ in 0..10 {
+            instance.run_frame(&[]);
+        }
+
+        instance.audio_queue().unwrap().clear();
+        instance.run_frame(&[]);
+        variance(instance.audio_queue().unwrap())
+    }
+
+    #[test]
+    fn mute_and_solo_channels() {
+        let mut instance = pulse_instance();
+        assert_eq!(instance.audio_channels().len(), 5);
+        assert!(run(&mut instance) > 1e-4);
+
+        instance.set_audio_channel_muted(0, true);
+        assert!(instance.audio_channel_muted(0));
+        assert!(run(&mut instance) < 1e-8);
+
+        instance.solo_audio_channel(0);
+        assert!(!instance.audio_channel_muted(0));
+        assert!(instance.audio_channel_muted(1));
+        assert!(run(&mut instance) > 1e-4);
+
+        instance.solo_audio_channel(2);
+        assert!(run(&mut instance) < 1e-8);
+    }
+
+    #[test]
+    fn stems_ignore_mute_state() {
+        let mut instance = pulse_instance();
+        instance.set_stems_enabled(true);
+        instance.set_audio_channel_muted(0, true);
+        run(&mut instance);
+
+        let stems = instance.stem_queues().unwrap();
+        assert_eq!(stems.len(), 5);
+        assert!(variance(&mut stems[0]) > 1e-4);
+        assert!(variance(&mut stems[1]) < 1e-8);
+    }
+
+    #[test]
+    fn save_and_load_state() {
+        let mut instance = pulse_instance();
+        run(&mut instance);
+
+        let saved = instance.save_state().unwrap();
+        run(&mut instance);
+        let expected = instance.save_state().unwrap();
+        assert_ne!(saved, expected);
+
+        instance.load_state(&saved).unwrap();
+        assert_eq!(instance.save_state().unwrap(), saved);
+        run(&mut instance);
+        assert_eq!(instance.save_state().unwrap(), expected);
+
+        // States from a different game are rejected without changing anything
+        assert!(instance.load_state(&saved[..(saved.len() - 1)]).is_err());
+        assert_eq!(instance.save_state().unwrap(), expected);
+    }
+}
@@ -1,6 +1,8 @@
 use super::cartridge::Cartridge;
 use super::interrupt::{Interrupt, InterruptType};
 use super::DmaRequest;
+use crate::util::audio::{AudioChannels, AudioFilter, BlipBuffer, OutputFilter};
+use crate::util::state::{Snapshot, State};
 use crate::{AudioQueue, Mapped};
 use dmc::Dmc;
 use frame::FrameCounter;
@@ -18,13 +20,15 @@ mod pulse;
 mod triangle;
 
 const CYCLES_PER_SECOND: u64 = 1789773;
-const CLOCK_SHIFT: u32 = 16;
-const SAMPLE_PERIOD: u64 = (CYCLES_PER_SECOND << CLOCK_SHIFT) / Apu::SAMPLE_RATE;
-const SAMPLES_PER_CYCLE: u64 = 1 << CLOCK_SHIFT;
 
 const PULSE_TABLE_SIZE: usize = 31;
 const TND_TABLE_SIZE: usize = 203;
 
+const CHANNEL_NAMES: [&str; 5] = ["Pulse 1", "Pulse 2", "Triangle", "Noise", "DMC"];
+
+// Expansion audio (if any) comes after the built-in channels
+const EXPANSION_CHANNEL: usize = CHANNEL_NAMES.len();
+
 pub struct Apu {
     pulse1: Pulse,
     pulse2: Pulse,
@@ -33,7 +37,9 @@ pub struct Apu {
     dmc: Dmc,
     frame_counter: FrameCounter,
     interrupt: Interrupt,
-    sample_clock: u64,
+    blip: BlipBuffer,
+    filter: OutputFilter,
+    audio_channels: AudioChannels,
     audio_queue: AudioQueue,
     pulse_table: [f32; PULSE_TABLE_SIZE],
     tnd_table: [f32; TND_TABLE_SIZE],
@@ -42,7 +48,15 @@ pub struct Apu {
 impl Apu {
     pub const SAMPLE_RATE: u64 = 44100;
 
-    pub fn new(interrupt: Interrupt) -> Self {
+    pub fn new(
+        interrupt: Interrupt,
+        audio_filter: AudioFilter,
+        expansion_name: Option<&'static str>,
+    ) -> Self {
+        let audio_filter = audio_filter.resolve(AudioFilter::Nes);
+        let mut channel_names = CHANNEL_NAMES.to_vec();
+        channel_names.extend(expansion_name);
+
         Self {
             pulse1: Pulse::new(true),
             pulse2: Pulse::new(false),
@@ -51,7 +65,14 @@ impl Apu {
             dmc: Dmc::new(interrupt.clone()),
             frame_counter: FrameCounter::new(interrupt.clone()),
             interrupt,
-            sample_clock: 0,
+            blip: BlipBuffer::new(CYCLES_PER_SECOND, Self::SAMPLE_RATE),
+            filter: OutputFilter::new(audio_filter, Self::SAMPLE_RATE),
+            audio_channels: AudioChannels::new(
+                channel_names,
+                audio_filter,
+                Some(CYCLES_PER_SECOND),
+                Self::SAMPLE_RATE,
+            ),
             audio_queue: AudioQueue::new(),
             pulse_table: create_pulse_table(),
             tnd_table: create_tnd_table(),
@@ -66,6 +87,14 @@ impl Apu {
         &mut self.audio_queue
     }
 
+    pub fn audio_channels(&self) -> &AudioChannels {
+        &self.audio_channels
+    }
+
+    pub fn audio_channels_mut(&mut self) -> &mut AudioChannels {
+        &mut self.audio_channels
+    }
+
     pub fn read_register(&mut self, address: u16, prev_value: u8) -> u8 {
         match address & 0x1f {
             0x15 => {
@@ -139,21 +168,54 @@ impl Apu {
             self.noise.on_frame_event(event);
         }
 
-        self.sample_clock += SAMPLES_PER_CYCLE;
+        // The mixed output is tracked every cycle (including any expansion audio), so that each
+        // change in level is placed at the exact cycle it happened
+        let levels = [
+            self.pulse1.output(),
+            self.pulse2.output(),
+            self.triangle.output(),
+            self.noise.output(),
+            self.dmc.output(),
+        ];
+
+        let expansion = cartridge.audio_output();
+        // The DC offset applies to the mix as a whole, rather than to each stem
+        let output = self.mix(levels, expansion, |index| {
+            !self.audio_channels.is_muted(index)
+        }) - 0.5;
+
+        self.blip.set_amplitude(output);
+
+        if let Some(sample) = self.blip.step() {
+            self.audio_queue
+                .push_back(self.filter.apply((sample, sample)));
+        }
 
-        if self.sample_clock >= SAMPLE_PERIOD {
-            self.sample_clock -= SAMPLE_PERIOD;
+        if self.audio_channels.stems_enabled() {
+            for index in 0..self.audio_channels.len() {
+                let output = self.mix(levels, expansion, |other| other == index);
+                self.audio_channels
+                    .set_stem_amplitude(index, (output, output));
+            }
 
-            let pulse = self.pulse1.output() + self.pulse2.output();
-            let tnd = self.triangle.output() * 3 + self.noise.output() * 2 + self.dmc.output();
+            self.audio_channels.step_stems();
+        }
+    }
 
-            let output = (self.pulse_table[pulse as usize]
-                + self.tnd_table[tnd as usize]
-                + cartridge.audio_output())
-                - 0.5;
+    // The NES mixer is non-linear, so even a single channel has to go through the lookup tables
+    fn mix(&self, levels: [u8; 5], expansion: f32, enabled: impl Fn(usize) -> bool) -> f32 {
+        let level = |index: usize| if enabled(index) { levels[index] } else { 0 };
 
-            self.audio_queue.push_back((output, output));
-        }
+        let pulse = level(0) + level(1);
+        let tnd = level(2) * 3 + level(3) * 2 + level(4);
+
+        let expansion = if enabled(EXPANSION_CHANNEL) {
+            expansion
+        } else {
+            0.0
+        };
+
+        self.pulse_table[pulse as usize] + self.tnd_table[tnd as usize] + expansion
     }
 }
 
@@ -176,3 +238,15 @@ fn create_tnd_table() -> [f32; TND_TABLE_SIZE] {
 
     table
 }
+
+// Output filters and queues are left as they are
+impl Snapshot for Apu {
+    fn snapshot(&mut self, state: &mut State) {
+        self.pulse1.snapshot(state);
+        self.pulse2.snapshot(state);
+        self.triangle.snapshot(state);
+        self.noise.snapshot(state);
+        self.dmc.snapshot(state);
+        self.frame_counter.snapshot(state);
+    }
+}
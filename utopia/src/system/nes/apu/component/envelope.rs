@@ -1,3 +1,5 @@
+use crate::util::state::{Snapshot, State};
+
 pub struct Envelope {
     constant_volume: bool,
     decay: u8,
@@ -59,3 +61,14 @@ impl Envelope {
         }
     }
 }
+
+impl Snapshot for Envelope {
+    fn snapshot(&mut self, state: &mut State) {
+        self.constant_volume.snapshot(state);
+        self.decay.snapshot(state);
+        self.divider.snapshot(state);
+        self.period.snapshot(state);
+        self.start_flag.snapshot(state);
+        self.loop_flag.snapshot(state);
+    }
+}
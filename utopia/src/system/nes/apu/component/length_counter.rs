@@ -1,3 +1,5 @@
+use crate::util::state::{Snapshot, State};
+
 #[rustfmt::skip]
 const PERIODS: [u32; 32] = [
      10, 254,  20,   2,  40,   4,  80,   6,
@@ -49,3 +51,11 @@ impl LengthCounter {
         }
     }
 }
+
+impl Snapshot for LengthCounter {
+    fn snapshot(&mut self, state: &mut State) {
+        self.enabled.snapshot(state);
+        self.halted.snapshot(state);
+        self.counter.snapshot(state);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::util::state::{Snapshot, State};
+
 pub struct LinearCounter {
     counter: u32,
     period: u32,
@@ -40,3 +42,12 @@ impl LinearCounter {
         }
     }
 }
+
+impl Snapshot for LinearCounter {
+    fn snapshot(&mut self, state: &mut State) {
+        self.counter.snapshot(state);
+        self.period.snapshot(state);
+        self.reload.snapshot(state);
+        self.control.snapshot(state);
+    }
+}
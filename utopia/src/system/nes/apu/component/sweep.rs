@@ -1,4 +1,5 @@
 use super::Timer;
+use crate::util::state::{Snapshot, State};
 
 pub struct Sweep {
     enabled: bool,
@@ -65,3 +66,17 @@ impl Sweep {
         }
     }
 }
+
+impl Snapshot for Sweep {
+    fn snapshot(&mut self, state: &mut State) {
+        self.enabled.snapshot(state);
+        self.divider_counter.snapshot(state);
+        self.divider_period.snapshot(state);
+        self.negate.snapshot(state);
+        self.shift.snapshot(state);
+        self.complement_mode.snapshot(state);
+        self.target.snapshot(state);
+        self.reload.snapshot(state);
+        self.muted.snapshot(state);
+    }
+}
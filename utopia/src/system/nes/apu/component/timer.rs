@@ -1,3 +1,5 @@
+use crate::util::state::{Snapshot, State};
+
 pub struct Timer {
     counter: u32,
     period: u32,
@@ -39,3 +41,11 @@ impl Timer {
         }
     }
 }
+
+impl Snapshot for Timer {
+    fn snapshot(&mut self, state: &mut State) {
+        self.counter.snapshot(state);
+        self.period.snapshot(state);
+        self.shift.snapshot(state);
+    }
+}
@@ -1,6 +1,7 @@
 use super::super::interrupt::{Interrupt, InterruptType};
 use super::super::DmaRequest;
 use super::component::Timer;
+use crate::util::state::{Snapshot, State};
 use tracing::trace;
 
 #[rustfmt::skip]
@@ -162,3 +163,20 @@ impl Dmc {
         );
     }
 }
+
+impl Snapshot for Dmc {
+    fn snapshot(&mut self, state: &mut State) {
+        self.output.snapshot(state);
+        self.timer.snapshot(state);
+        self.silence_flag.snapshot(state);
+        self.shifter.value.snapshot(state);
+        self.shifter.bits_remaining.snapshot(state);
+        self.sample_buffer.snapshot(state);
+        self.reader.address.snapshot(state);
+        self.reader.bytes_remaining.snapshot(state);
+        self.sample_address.snapshot(state);
+        self.sample_length.snapshot(state);
+        self.loop_flag.snapshot(state);
+        self.irq_enabled.snapshot(state);
+    }
+}
@@ -1,4 +1,5 @@
 use super::super::interrupt::{Interrupt, InterruptType};
+use crate::util::state::{Snapshot, State};
 use tracing::trace;
 
 const STEPS: [u64; 5] = [7458, 14914, 22372, 29830, 37282];
@@ -116,3 +117,13 @@ impl FrameCounter {
         frame
     }
 }
+
+impl Snapshot for FrameCounter {
+    fn snapshot(&mut self, state: &mut State) {
+        self.cycles.snapshot(state);
+        self.target_cycles.snapshot(state);
+        self.step.snapshot(state);
+        state.variant(&mut self.mode, &[Mode::Short, Mode::Long]);
+        self.irq_inhibit.snapshot(state);
+    }
+}
@@ -1,5 +1,6 @@
 use super::component::{Envelope, LengthCounter, Timer};
 use super::frame::FrameEvent;
+use crate::util::state::{Snapshot, State};
 
 #[rustfmt::skip]
 const PERIODS: [u32; 16] = [
@@ -80,3 +81,13 @@ impl Noise {
         }
     }
 }
+
+impl Snapshot for Noise {
+    fn snapshot(&mut self, state: &mut State) {
+        self.timer.snapshot(state);
+        self.envelope.snapshot(state);
+        self.length_counter.snapshot(state);
+        self.mode.snapshot(state);
+        self.shift.snapshot(state);
+    }
+}
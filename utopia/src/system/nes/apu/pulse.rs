@@ -1,6 +1,7 @@
 use super::component::{Envelope, LengthCounter, Sweep, Timer};
 use super::frame::FrameEvent;
 use crate::util::audio::Sequencer;
+use crate::util::state::{Snapshot, State};
 
 const DUTY_CYCLE: [[u8; 8]; 4] = [
     [0, 1, 0, 0, 0, 0, 0, 0],
@@ -83,3 +84,13 @@ impl Pulse {
         }
     }
 }
+
+impl Snapshot for Pulse {
+    fn snapshot(&mut self, state: &mut State) {
+        self.timer.snapshot(state);
+        self.sequencer.snapshot(state, &DUTY_CYCLE);
+        self.envelope.snapshot(state);
+        self.sweep.snapshot(state);
+        self.length_counter.snapshot(state);
+    }
+}
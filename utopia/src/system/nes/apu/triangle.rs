@@ -1,6 +1,7 @@
 use super::component::{LengthCounter, LinearCounter, Timer};
 use super::frame::FrameEvent;
 use crate::util::audio::Sequencer;
+use crate::util::state::{Snapshot, State};
 
 #[rustfmt::skip]
 const SEQUENCE: [u8; 32] = [
@@ -77,3 +78,12 @@ impl Triangle {
         }
     }
 }
+
+impl Snapshot for Triangle {
+    fn snapshot(&mut self, state: &mut State) {
+        self.timer.snapshot(state);
+        self.sequencer.snapshot(state, &[SEQUENCE]);
+        self.linear_counter.snapshot(state);
+        self.length_counter.snapshot(state);
+    }
+}
@@ -1,5 +1,7 @@
 use super::Interrupt;
+use crate::database::{Mirroring, Overrides};
 use crate::util::mirror::{Mirror, MirrorVec};
+use crate::util::state::{Snapshot, State};
 use crate::{Mapped, MemoryMapper};
 use mapper::{Mapper, MapperType, Mappings, MirrorMode, PrgRead, PrgWrite};
 use tracing::info;
@@ -10,6 +12,7 @@ const HEADER_SIZE: usize = 16;
 const TRAINER_SIZE: usize = 512;
 const PRG_ROM_MULTIPLIER: usize = 16384;
 const CHR_ROM_MULTIPLIER: usize = 8192;
+pub const CHR_RAM_SIZE: usize = 8192;
 const PRG_RAM_SIZE: usize = 8192;
 const CI_RAM_SIZE: usize = 2048;
 
@@ -23,20 +26,92 @@ pub struct Cartridge<T: Mapped> {
     mapper: MapperType,
 }
 
+pub struct Header {
+    pub mapper_number: u8,
+    pub prg_rom_size: usize,
+    pub chr_rom_size: usize,
+    pub trainer_present: bool,
+    pub mirror_mode: MirrorMode,
+    pub battery_backed: bool,
+    // Vs. System or PlayChoice-10, which use RGB PPUs
+    pub arcade: bool,
+}
+
+impl Header {
+    pub fn parse(data: &[u8], overrides: Overrides) -> Result<Self, crate::Error> {
+        if data.len() < HEADER_SIZE || !data.starts_with(b"NES\x1a") {
+            return Err("Invalid iNES header".into());
+        }
+
+        let mapper_number = overrides
+            .mapper
+            .unwrap_or(((data[6] & 0xf0) >> 4) | (data[7] & 0xf0));
+
+        let prg_rom_size = PRG_ROM_MULTIPLIER * (data[4] as usize);
+        let chr_rom_size = CHR_ROM_MULTIPLIER * (data[5] as usize);
+        let trainer_present = (data[6] & 0x04) != 0;
+
+        let expected_len = HEADER_SIZE
+            + if trainer_present { TRAINER_SIZE } else { 0 }
+            + prg_rom_size
+            + chr_rom_size;
+
+        if data.len() < expected_len {
+            return Err("ROM file is smaller than the size given in its header".into());
+        }
+
+        let vertical_mirroring = match overrides.mirroring {
+            Some(mirroring) => mirroring == Mirroring::Vertical,
+            None => (data[6] & 0x01) != 0,
+        };
+
+        let mirror_mode = if vertical_mirroring {
+            MirrorMode::Vertical
+        } else {
+            MirrorMode::Horizontal
+        };
+
+        let battery_backed = overrides.battery.unwrap_or((data[6] & 0x02) != 0);
+
+        // NES 2.0 headers use the 'extended' console type (3) to point at byte 13 instead
+        let arcade = match data[7] & 0x03 {
+            0 => false,
+            3 if (data[7] & 0x0c) == 0x08 => matches!(data[13] & 0x0f, 1 | 2),
+            _ => true,
+        };
+
+        Ok(Self {
+            mapper_number,
+            prg_rom_size,
+            chr_rom_size,
+            trainer_present,
+            mirror_mode,
+            battery_backed,
+            arcade,
+        })
+    }
+}
+
 impl<T: Mapped> Cartridge<T> {
     pub fn new<U: MemoryMapper<Mapped = T>>(
         data: Vec<u8>,
         memory_mapper: &U,
         interrupt: Interrupt,
+        overrides: Overrides,
     ) -> Result<Self, crate::Error> {
-        let mapper_number = ((data[6] & 0xf0) >> 4) | (data[7] & 0xf0);
-        let prg_rom_size = PRG_ROM_MULTIPLIER * (data[4] as usize);
-        let chr_rom_size = CHR_ROM_MULTIPLIER * (data[5] as usize);
+        let Header {
+            mapper_number,
+            prg_rom_size,
+            chr_rom_size,
+            trainer_present,
+            mirror_mode,
+            battery_backed,
+            ..
+        } = Header::parse(&data, overrides)?;
 
         info!("Mapper Number: {}", mapper_number);
         info!("PRG ROM Size: {}", prg_rom_size);
 
-        let trainer_present = (data[6] & 0x04) != 0;
         let prg_rom_start = HEADER_SIZE + if trainer_present { TRAINER_SIZE } else { 0 };
         let prg_rom_end = prg_rom_start + prg_rom_size;
         let prg_rom = Vec::from(&data[prg_rom_start..prg_rom_end]);
@@ -46,14 +121,8 @@ impl<T: Mapped> Cartridge<T> {
             let chr_rom_end = prg_rom_end + chr_rom_size;
             Vec::from(&data[prg_rom_end..chr_rom_end])
         } else {
-            info!("CHR RAM Size: {}", CHR_ROM_MULTIPLIER);
-            vec![0; CHR_ROM_MULTIPLIER]
-        };
-
-        let mirror_mode = if (data[6] & 0x01) != 0 {
-            MirrorMode::Vertical
-        } else {
-            MirrorMode::Horizontal
+            info!("CHR RAM Size: {}", CHR_RAM_SIZE);
+            vec![0; CHR_RAM_SIZE]
         };
 
         info!("Mirror Mode: {:?}", mirror_mode);
@@ -62,7 +131,6 @@ impl<T: Mapped> Cartridge<T> {
         let mut mapper = MapperType::new(mapper_number, prg_rom_size, interrupt);
         mapper.init_mappings(&mut mappings);
 
-        let battery_backed = (data[6] & 0x02) != 0;
         info!("Battery Backed: {}", battery_backed);
 
         Ok(Self {
@@ -87,6 +155,14 @@ impl<T: Mapped> Cartridge<T> {
         }
     }
 
+    pub fn peek_prg_ram(&self, address: u16) -> u8 {
+        self.prg_ram[address as usize & (PRG_RAM_SIZE - 1)]
+    }
+
+    pub fn poke_prg_ram(&mut self, address: u16, value: u8) {
+        self.prg_ram[address as usize & (PRG_RAM_SIZE - 1)] = value;
+    }
+
     pub fn write_prg(&mut self, address: u16, value: u8) {
         match self.mappings.prg_write[address as usize >> 12] {
             PrgWrite::Ram(offset) => {
@@ -129,7 +205,82 @@ impl<T: Mapped> Cartridge<T> {
         self.mapper.on_ppu_address_changed(ppu_address);
     }
 
+    pub fn audio_name(&self) -> Option<&'static str> {
+        self.mapper.audio_name()
+    }
+
     pub fn audio_output(&self) -> f32 {
         self.mapper.audio_output()
     }
 }
+
+impl<T: Mapped> Snapshot for Cartridge<T> {
+    fn snapshot(&mut self, state: &mut State) {
+        self.prg_ram.snapshot(state);
+
+        if self.chr_writable {
+            self.chr_data.snapshot(state);
+        }
+
+        self.ci_ram.snapshot(state);
+        self.mappings.snapshot(state);
+        self.mapper.snapshot(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{database, SystemType};
+
+    fn header(flags_7: u8, byte_13: u8) -> Header {
+        let mut data = b"NES\x1a\x01\x01\x00".to_vec();
+        data.push(flags_7);
+        data.resize(HEADER_SIZE, 0);
+        data[13] = byte_13;
+        data.resize(HEADER_SIZE + PRG_ROM_MULTIPLIER + CHR_ROM_MULTIPLIER, 0);
+        Header::parse(&data, Overrides::default()).unwrap()
+    }
+
+    #[test]
+    fn arcade_console_types() {
+        assert!(!header(0x00, 0x00).arcade);
+        assert!(header(0x01, 0x00).arcade);
+        assert!(header(0x02, 0x00).arcade);
+        assert!(header(0x09, 0x00).arcade);
+
+        // NES 2.0 extended console types
+        assert!(header(0x0b, 0x01).arcade);
+        assert!(header(0x0b, 0x02).arcade);
+        assert!(!header(0x0b, 0x03).arcade);
+        assert!(!header(0x0b, 0x05).arcade);
+    }
+
+    #[test]
+    fn garbage_header_is_corrected() {
+        let mut data = b"NES\x1a\x01\x01\x10DiskDude!".to_vec();
+        data.resize(HEADER_SIZE + PRG_ROM_MULTIPLIER + CHR_ROM_MULTIPLIER, 0);
+
+        let header = Header::parse(&data, Overrides::default()).unwrap();
+        assert_eq!(header.mapper_number, 0x41);
+
+        let overrides = database::correct(SystemType::Nes, &data, Overrides::default());
+        let header = Header::parse(&data, overrides).unwrap();
+        assert_eq!(header.mapper_number, 0x01);
+
+        // Database entries take priority
+        let overrides = Overrides {
+            mapper: Some(0x04),
+            ..Default::default()
+        };
+
+        let header = Header::parse(&data, database::correct(SystemType::Nes, &data, overrides));
+        assert_eq!(header.unwrap().mapper_number, 0x04);
+
+        // Clean headers are left alone
+        data[7..HEADER_SIZE].fill(0);
+        data[7] = 0x40;
+        let overrides = database::correct(SystemType::Nes, &data, Overrides::default());
+        assert_eq!(Header::parse(&data, overrides).unwrap().mapper_number, 0x41);
+    }
+}
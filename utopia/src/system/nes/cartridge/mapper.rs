@@ -1,4 +1,5 @@
 use super::super::interrupt::{Interrupt, InterruptType};
+use crate::util::state::{Snapshot, State};
 use crate::util::MirrorVec;
 use axrom::AxRom;
 use cnrom::CnRom;
@@ -60,9 +61,17 @@ pub trait Mapper {
 
     fn on_ppu_chr_fetch(&mut self, _mappings: &mut Mappings, _ppu_address: u16) {}
 
+    // Name of the expansion audio chip, if the cartridge has one
+    fn audio_name(&self) -> Option<&'static str> {
+        None
+    }
+
     fn audio_output(&self) -> f32 {
         0.0
     }
+
+    // Register state only, as the mappings are saved separately
+    fn snapshot(&mut self, _state: &mut State) {}
 }
 
 #[enum_dispatch(Mapper)]
@@ -244,3 +253,46 @@ impl Mappings {
         }
     }
 }
+
+impl Snapshot for Mappings {
+    fn snapshot(&mut self, state: &mut State) {
+        for read in &mut self.prg_read {
+            let (mut kind, mut offset) = match *read {
+                PrgRead::Rom(offset) => (0u8, offset),
+                PrgRead::Ram(offset) => (1, offset),
+                PrgRead::Register => (2, 0),
+                PrgRead::None => (3, 0),
+            };
+
+            kind.snapshot(state);
+            offset.snapshot(state);
+
+            *read = match kind {
+                0 => PrgRead::Rom(offset),
+                1 => PrgRead::Ram(offset),
+                2 => PrgRead::Register,
+                _ => PrgRead::None,
+            };
+        }
+
+        for write in &mut self.prg_write {
+            let (mut kind, mut offset) = match *write {
+                PrgWrite::Ram(offset) => (0u8, offset),
+                PrgWrite::Register => (1, 0),
+                PrgWrite::None => (2, 0),
+            };
+
+            kind.snapshot(state);
+            offset.snapshot(state);
+
+            *write = match kind {
+                0 => PrgWrite::Ram(offset),
+                1 => PrgWrite::Register,
+                _ => PrgWrite::None,
+            };
+        }
+
+        self.name.snapshot(state);
+        self.chr.snapshot(state);
+    }
+}
@@ -2,6 +2,7 @@ use super::{
     Interrupt, InterruptType, Mapper, Mappings, NameTable, CHR_PAGE_SIZE, MIRROR_HORIZONTAL,
     MIRROR_VERTICAL,
 };
+use crate::util::state::{Snapshot, State};
 use tracing::trace;
 
 const PRG_BANK_SIZE: usize = 8192;
@@ -104,4 +105,11 @@ impl Mapper for Fme7 {
             }
         }
     }
+
+    fn snapshot(&mut self, state: &mut State) {
+        self.command.snapshot(state);
+        self.irq_enable.snapshot(state);
+        self.irq_counter_enable.snapshot(state);
+        self.irq_counter.snapshot(state);
+    }
 }
@@ -1,4 +1,5 @@
 use super::{Mapper, Mappings, NameTable, MIRROR_HORIZONTAL, MIRROR_VERTICAL};
+use crate::util::state::{Snapshot, State};
 use tracing::trace;
 
 const PRG_BANK_SIZE: usize = 16384;
@@ -131,4 +132,14 @@ impl Mapper for Mmc1 {
 
         trace!("MMC1 Shift: {:02X}", self.shift);
     }
+
+    fn snapshot(&mut self, state: &mut State) {
+        self.shift.snapshot(state);
+        self.mirror_mode.snapshot(state);
+        self.prg_rom_mode.snapshot(state);
+        self.chr_mode.snapshot(state);
+        self.chr_bank.snapshot(state);
+        self.prg_bank.snapshot(state);
+        self.prg_ram_enabled.snapshot(state);
+    }
 }
@@ -1,4 +1,5 @@
 use super::{Mapper, Mappings, MirrorMode};
+use crate::util::state::{Snapshot, State};
 use tracing::trace;
 
 const PRG_BANK_SIZE: usize = 8192;
@@ -108,4 +109,9 @@ impl Mapper for Mmc2 {
             _ => (),
         }
     }
+
+    fn snapshot(&mut self, state: &mut State) {
+        self.chr_bank.snapshot(state);
+        self.chr_latch.snapshot(state);
+    }
 }
@@ -1,4 +1,5 @@
 use super::{Interrupt, InterruptType, Mapper, Mappings, MirrorMode, CHR_PAGE_SIZE};
+use crate::util::state::{Snapshot, State};
 use tracing::trace;
 
 const PRG_BANK_SIZE: usize = 8192;
@@ -180,4 +181,17 @@ impl Mapper for Mmc3 {
 
         self.prev_a12 = a12;
     }
+
+    fn snapshot(&mut self, state: &mut State) {
+        self.registers.snapshot(state);
+        self.register_select.snapshot(state);
+        self.prg_rom_mode.snapshot(state);
+        self.chr_mode.snapshot(state);
+        self.irq_latch.snapshot(state);
+        self.irq_counter.snapshot(state);
+        self.irq_enabled.snapshot(state);
+        self.irq_reload.snapshot(state);
+        self.cycle_counter.snapshot(state);
+        self.prev_a12.snapshot(state);
+    }
 }
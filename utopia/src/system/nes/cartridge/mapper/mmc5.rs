@@ -1,4 +1,5 @@
 use super::{Interrupt, InterruptType, Mapper, Mappings, CHR_PAGE_SIZE};
+use crate::util::state::{Snapshot, State};
 use crate::util::MirrorVec;
 use bitflags::bitflags;
 use num_derive::FromPrimitive;
@@ -402,6 +403,45 @@ impl Mapper for Mmc5 {
             self.update_chr_mappings(mappings);
         }
     }
+
+    fn snapshot(&mut self, state: &mut State) {
+        self.prg_mode.snapshot(state);
+        self.prg_bank.snapshot(state);
+        self.chr_mode.snapshot(state);
+        self.chr_bank.snapshot(state);
+
+        let name_tables = [
+            NameTable::Low,
+            NameTable::High,
+            NameTable::Eram,
+            NameTable::Fill,
+        ];
+
+        for name_bank in &mut self.name_bank {
+            state.variant(name_bank, &name_tables);
+        }
+
+        self.ctrl.sprite_mode.snapshot(state);
+        self.ctrl.scanline_count.snapshot(state);
+        self.ctrl.no_read_count.snapshot(state);
+        self.ctrl.prev_address.snapshot(state);
+        self.ctrl.same_address_count.snapshot(state);
+        self.ctrl.same_line_reads.snapshot(state);
+        self.eram.snapshot(state);
+
+        let mut eram_flags = self.eram_flags.bits();
+        eram_flags.snapshot(state);
+        self.eram_flags = EramFlags::from_bits_retain(eram_flags);
+
+        self.fill_mode_name.snapshot(state);
+        self.fill_mode_attr.snapshot(state);
+        self.scanline_irq_compare.snapshot(state);
+        self.scanline_irq_enable.snapshot(state);
+
+        let mut scanline_irq_status = self.scanline_irq_status.bits();
+        scanline_irq_status.snapshot(state);
+        self.scanline_irq_status = ScanlineIrqStatus::from_bits_retain(scanline_irq_status);
+    }
 }
 
 fn map_prg(mappings: &mut Mappings, start: usize, len: usize, bank: u8) {
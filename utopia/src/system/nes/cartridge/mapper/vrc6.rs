@@ -2,6 +2,7 @@ use super::{
     Interrupt, InterruptType, Mapper, Mappings, NameTable, CHR_PAGE_SIZE, MIRROR_HORIZONTAL,
     MIRROR_VERTICAL,
 };
+use crate::util::state::{Snapshot, State};
 use pulse::Pulse;
 use saw::Saw;
 use tracing::trace;
@@ -192,9 +193,26 @@ impl Mapper for Vrc6 {
         self.interrupt.raise(InterruptType::MapperIrq);
     }
 
+    fn audio_name(&self) -> Option<&'static str> {
+        Some("VRC6")
+    }
+
     fn audio_output(&self) -> f32 {
         let output = self.pulse1.output() + self.pulse2.output() + self.saw.output();
 
         output as f32 * VOLUME_MULTIPLIER
     }
+
+    fn snapshot(&mut self, state: &mut State) {
+        self.irq_mode.snapshot(state);
+        self.irq_divider.snapshot(state);
+        self.irq_counter.snapshot(state);
+        self.irq_latch.snapshot(state);
+        self.irq_enable.snapshot(state);
+        self.irq_enable_after_ack.snapshot(state);
+        self.audio_halted.snapshot(state);
+        self.pulse1.snapshot(state);
+        self.pulse2.snapshot(state);
+        self.saw.snapshot(state);
+    }
 }
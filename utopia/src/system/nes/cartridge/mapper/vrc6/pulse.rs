@@ -1,3 +1,5 @@
+use crate::util::state::{Snapshot, State};
+
 pub struct Pulse {
     freq_counter: u16,
     freq_period: u16,
@@ -69,3 +71,15 @@ impl Pulse {
         }
     }
 }
+
+impl Snapshot for Pulse {
+    fn snapshot(&mut self, state: &mut State) {
+        self.freq_counter.snapshot(state);
+        self.freq_period.snapshot(state);
+        self.freq_shift.snapshot(state);
+        self.enabled.snapshot(state);
+        self.duty_counter.snapshot(state);
+        self.duty_threshold.snapshot(state);
+        self.volume.snapshot(state);
+    }
+}
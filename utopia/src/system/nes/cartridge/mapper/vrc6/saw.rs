@@ -1,3 +1,5 @@
+use crate::util::state::{Snapshot, State};
+
 const ACCUMULATOR_STEPS: u8 = 14;
 
 pub struct Saw {
@@ -77,3 +79,15 @@ impl Saw {
         }
     }
 }
+
+impl Snapshot for Saw {
+    fn snapshot(&mut self, state: &mut State) {
+        self.freq_counter.snapshot(state);
+        self.freq_period.snapshot(state);
+        self.freq_shift.snapshot(state);
+        self.enabled.snapshot(state);
+        self.accum_step.snapshot(state);
+        self.accum_value.snapshot(state);
+        self.accum_rate.snapshot(state);
+    }
+}
@@ -1,6 +1,7 @@
 use crate::core::mos6502;
-use std::cell::Cell;
-use std::rc::Rc;
+use crate::util::state::{Snapshot, State};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
 use tracing::trace;
 
 #[repr(u32)]
@@ -16,7 +17,7 @@ pub enum InterruptType {
 
 #[derive(Clone)]
 pub struct Interrupt {
-    inner: Rc<Cell<mos6502::Interrupt>>,
+    inner: Arc<AtomicU32>,
 }
 
 impl Interrupt {
@@ -27,28 +28,37 @@ impl Interrupt {
     }
 
     pub fn poll(&self) -> mos6502::Interrupt {
-        self.inner.get()
+        self.inner.load(Ordering::Relaxed)
     }
 
     pub fn has(&self, int_type: InterruptType) -> bool {
-        self.inner.get() & (int_type as mos6502::Interrupt) != 0
+        self.inner.load(Ordering::Relaxed) & (int_type as mos6502::Interrupt) != 0
     }
 
     pub fn clear(&mut self, int_type: InterruptType) {
-        let mut value = self.inner.get();
+        let mut value = self.inner.load(Ordering::Relaxed);
         value &= !(int_type as mos6502::Interrupt);
-        self.inner.set(value);
+        self.inner.store(value, Ordering::Relaxed);
         trace!("Interrupt Cleared: {:?}", int_type);
     }
 
     pub fn raise(&mut self, int_type: InterruptType) {
-        let mut value = self.inner.get();
+        let mut value = self.inner.load(Ordering::Relaxed);
         value |= int_type as mos6502::Interrupt;
-        self.inner.set(value);
+        self.inner.store(value, Ordering::Relaxed);
         trace!("Interrupt Raised: {:?}", int_type);
     }
 }
 
+// Clones share the same line, so only the owner should save it
+impl Snapshot for Interrupt {
+    fn snapshot(&mut self, state: &mut State) {
+        let mut value = self.inner.load(Ordering::Relaxed);
+        value.snapshot(state);
+        self.inner.store(value, Ordering::Relaxed);
+    }
+}
+
 impl From<mos6502::Interrupt> for InterruptType {
     fn from(value: mos6502::Interrupt) -> InterruptType {
         match value {
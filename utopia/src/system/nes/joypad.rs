@@ -1,3 +1,5 @@
+use crate::system;
+use crate::util::state::{Snapshot, State};
 use crate::JoypadState;
 use tracing::trace;
 
@@ -16,18 +18,10 @@ impl Joypad {
         }
     }
 
-    pub fn update(&mut self, new_state: &JoypadState) {
-        let JoypadState { buttons, .. } = &new_state;
-
-        self.current_state[0] = 0;
-        self.current_state[0] |= if buttons[1] { 0x01 } else { 0 };
-        self.current_state[0] |= if buttons[0] { 0x02 } else { 0 };
-        self.current_state[0] |= if buttons[8] { 0x04 } else { 0 };
-        self.current_state[0] |= if buttons[9] { 0x08 } else { 0 };
-        self.current_state[0] |= if buttons[12] { 0x10 } else { 0 };
-        self.current_state[0] |= if buttons[13] { 0x20 } else { 0 };
-        self.current_state[0] |= if buttons[14] { 0x40 } else { 0 };
-        self.current_state[0] |= if buttons[15] { 0x80 } else { 0 };
+    pub fn update(&mut self, ports: &[Option<JoypadState>]) {
+        for (index, state) in self.current_state.iter_mut().enumerate() {
+            *state = system::joypad(ports, index).map_or(0, encode);
+        }
     }
 
     pub fn read_register(&mut self, address: u16, prev_value: u8) -> u8 {
@@ -55,3 +49,26 @@ impl Joypad {
         self.latch = latch;
     }
 }
+
+impl Snapshot for Joypad {
+    fn snapshot(&mut self, state: &mut State) {
+        self.current_state.snapshot(state);
+        self.polled_state.snapshot(state);
+        self.latch.snapshot(state);
+    }
+}
+
+fn encode(joypad_state: &JoypadState) -> u8 {
+    let JoypadState { buttons, .. } = joypad_state;
+
+    let mut state = 0;
+    state |= if buttons[1] { 0x01 } else { 0 };
+    state |= if buttons[0] { 0x02 } else { 0 };
+    state |= if buttons[8] { 0x04 } else { 0 };
+    state |= if buttons[9] { 0x08 } else { 0 };
+    state |= if buttons[12] { 0x10 } else { 0 };
+    state |= if buttons[13] { 0x20 } else { 0 };
+    state |= if buttons[14] { 0x40 } else { 0 };
+    state |= if buttons[15] { 0x80 } else { 0 };
+    state
+}
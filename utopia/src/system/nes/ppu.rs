@@ -1,7 +1,11 @@
+pub use color::NesPalette;
 pub use screen::{HEIGHT, WIDTH};
+pub use signal::signal;
 
 use super::cartridge::Cartridge;
 use super::interrupt::{Interrupt, InterruptType};
+use crate::util::state::{Snapshot, State};
+use crate::util::Rgb;
 use crate::Mapped;
 use oam::Oam;
 use palette::Palette;
@@ -9,10 +13,12 @@ use render::RenderState;
 use screen::Screen;
 use tracing::{trace, warn};
 
+mod color;
 mod oam;
 mod palette;
 mod render;
 mod screen;
+mod signal;
 
 const PRE_RENDER_LINE: i32 = -1;
 const TOTAL_VISIBLE_LINES: i32 = 240;
@@ -38,6 +44,8 @@ struct Mask {
     render_enabled: bool,
     bg_start: i32,
     sprite_start: i32,
+    greyscale: u8,
+    emphasis: u8,
 }
 struct Status {
     nmi_occurred: bool,
@@ -63,7 +71,7 @@ pub struct Ppu {
 }
 
 impl Ppu {
-    pub fn new(interrupt: Interrupt) -> Self {
+    pub fn new(interrupt: Interrupt, colors: Vec<Rgb>) -> Self {
         Self {
             ready: false,
             line: 0,
@@ -93,10 +101,12 @@ impl Ppu {
                 render_enabled: false,
                 bg_start: 0,
                 sprite_start: 0,
+                greyscale: 0x3f,
+                emphasis: 0,
             },
             render: RenderState::new(),
             palette: Palette::new(),
-            screen: Screen::new(),
+            screen: Screen::new(colors),
             oam: Oam::new(),
         }
     }
@@ -121,6 +131,10 @@ impl Ppu {
         self.screen.pixels()
     }
 
+    pub fn indices(&self) -> &[u16] {
+        self.screen.indices()
+    }
+
     pub fn read(&mut self, cartridge: &mut Cartridge<impl Mapped>, address: u16) -> u8 {
         match address & 7 {
             2 => {
@@ -214,9 +228,15 @@ impl Ppu {
                     _ => i32::MAX,
                 };
 
+                // Greyscale mode limits colours to the grey column of the palette
+                self.mask.greyscale = if (value & 0x01) != 0 { 0x30 } else { 0x3f };
+                self.mask.emphasis = value >> 5;
+
                 trace!("PPU Render Enabled: {}", self.mask.render_enabled);
                 trace!("PPU BG Start: {}", self.mask.bg_start);
                 trace!("PPU Sprite Start: {}", self.mask.sprite_start);
+                trace!("PPU Greyscale: {}", self.mask.greyscale != 0x3f);
+                trace!("PPU Emphasis: {:03b}", self.mask.emphasis);
             }
             3 => self.oam.set_address(value),
             4 => self.oam.write(value),
@@ -277,7 +297,7 @@ impl Ppu {
                 self.render(cartridge);
             } else if self.line != PRE_RENDER_LINE && self.dot < 256 {
                 // TODO: The backdrop colour can apparently be set using palette address?
-                self.screen.draw(self.palette.color(0));
+                self.draw(self.palette.color(0));
             }
         }
 
@@ -288,6 +308,11 @@ impl Ppu {
         }
     }
 
+    fn draw(&mut self, color: u8) {
+        self.screen
+            .draw(color & self.mask.greyscale, self.mask.emphasis);
+    }
+
     fn next_line(&mut self) {
         self.dot = 0;
         self.line += 1;
@@ -315,3 +340,35 @@ impl Ppu {
         }
     }
 }
+
+// The interrupt line is saved by the owner
+impl Snapshot for Ppu {
+    fn snapshot(&mut self, state: &mut State) {
+        self.ready.snapshot(state);
+        self.line.snapshot(state);
+        self.dot.snapshot(state);
+        self.read_buffer.snapshot(state);
+        self.sprites_selected.snapshot(state);
+        self.sprite_zero_selected.snapshot(state);
+        self.regs.v.snapshot(state);
+        self.regs.t.snapshot(state);
+        self.regs.x.snapshot(state);
+        self.regs.w.snapshot(state);
+        self.control.nmi_active.snapshot(state);
+        self.control.bg_chr_offset.snapshot(state);
+        self.control.sprite_size.snapshot(state);
+        self.control.sprite_chr_offset.snapshot(state);
+        self.control.vram_increment.snapshot(state);
+        self.status.nmi_occurred.snapshot(state);
+        self.status.sprite_zero_hit.snapshot(state);
+        self.mask.render_enabled.snapshot(state);
+        self.mask.bg_start.snapshot(state);
+        self.mask.sprite_start.snapshot(state);
+        self.mask.greyscale.snapshot(state);
+        self.mask.emphasis.snapshot(state);
+        self.render.snapshot(state);
+        self.palette.snapshot(state);
+        self.screen.snapshot(state);
+        self.oam.snapshot(state);
+    }
+}
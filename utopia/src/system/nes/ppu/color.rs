@@ -0,0 +1,331 @@
+use super::signal::signal;
+use crate::util::ntsc::PHASE_COUNT;
+use crate::util::Rgb;
+use crate::Error;
+use std::f32::consts::PI;
+
+// Emphasis bits, as they appear in bits 5-7 of PPUMASK (on NTSC PPUs)
+const EMPHASIS_RED: usize = 0x01;
+const EMPHASIS_GREEN: usize = 0x02;
+const EMPHASIS_BLUE: usize = 0x04;
+
+// How much each emphasis bit darkens the other two channels (on composite PPUs)
+const ATTENUATION: f32 = 0.816;
+
+// Half of the 30 degree step between hues, which is how far the 2C07's colour phases are offset
+// from those of the 2C02
+const RP2C07_HUE_SHIFT: f32 = -15.0;
+
+const PAL_FILE_SIZE: usize = 64 * 3;
+const PAL_FILE_SIZE_WITH_EMPHASIS: usize = 512 * 3;
+
+// Which palette to convert the PPU's colour indices (plus emphasis bits) to RGB with
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum NesPalette {
+    // The 2C03 for Vs. System and PlayChoice-10 ROMs, otherwise the 2C02
+    #[default]
+    Auto,
+    // NTSC
+    Rp2c02,
+    // PAL
+    Rp2c07,
+    // RGB PPUs used in the Vs. System and PlayChoice-10 (identical colours)
+    Rp2c03,
+    Rp2c05,
+    // Contents of a .pal file, with either 64 or 512 (64 for each combination of emphasis bits)
+    // entries
+    Custom(Vec<u8>),
+}
+
+impl NesPalette {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().trim_start_matches("rp") {
+            "auto" => Some(Self::Auto),
+            "2c02" => Some(Self::Rp2c02),
+            "2c07" => Some(Self::Rp2c07),
+            "2c03" => Some(Self::Rp2c03),
+            "2c05" => Some(Self::Rp2c05),
+            _ => None,
+        }
+    }
+
+    pub fn from_pal(data: Vec<u8>) -> Result<Self, Error> {
+        if data.len() != PAL_FILE_SIZE && data.len() != PAL_FILE_SIZE_WITH_EMPHASIS {
+            return Err(format!(
+                "Palette files must be {} or {} bytes long (found {})",
+                PAL_FILE_SIZE,
+                PAL_FILE_SIZE_WITH_EMPHASIS,
+                data.len()
+            )
+            .into());
+        }
+
+        Ok(Self::Custom(data))
+    }
+
+    // Builds a table of 512 colours, with the emphasis bits in bits 6-8 of the index
+    pub fn colors(&self, arcade: bool) -> Vec<Rgb> {
+        match self {
+            Self::Auto if arcade => rgb_ppu_colors(),
+            Self::Auto | Self::Rp2c02 => composite_colors(&RP2C02),
+            Self::Rp2c07 => rp2c07_colors(),
+            Self::Rp2c03 | Self::Rp2c05 => rgb_ppu_colors(),
+            Self::Custom(data) => {
+                let colors: Vec<Rgb> = data
+                    .chunks_exact(3)
+                    .map(|color| Rgb(color[0], color[1], color[2]))
+                    .collect();
+
+                if colors.len() == 512 {
+                    colors
+                } else {
+                    composite_colors(&colors)
+                }
+            }
+        }
+    }
+}
+
+// Each emphasis bit darkens the channels it does not emphasise
+fn composite_colors(base: &[Rgb]) -> Vec<Rgb> {
+    (0..512)
+        .map(|index| {
+            let Rgb(mut red, mut green, mut blue) = base[index & 63];
+            let emphasis = index >> 6;
+
+            let attenuate = |value: &mut u8| *value = (*value as f32 * ATTENUATION).round() as u8;
+
+            if (emphasis & EMPHASIS_RED) != 0 {
+                attenuate(&mut green);
+                attenuate(&mut blue);
+            }
+
+            if (emphasis & EMPHASIS_GREEN) != 0 {
+                attenuate(&mut red);
+                attenuate(&mut blue);
+            }
+
+            if (emphasis & EMPHASIS_BLUE) != 0 {
+                attenuate(&mut red);
+                attenuate(&mut green);
+            }
+
+            Rgb(red, green, blue)
+        })
+        .collect()
+}
+
+// On the RGB PPUs, each emphasis bit instead drives its channel to full brightness
+fn rgb_ppu_colors() -> Vec<Rgb> {
+    (0..512)
+        .map(|index| {
+            let color = RP2C03[index & 63] as usize;
+            let emphasis = index >> 6;
+
+            let channel = |shift: usize, bit: usize| {
+                let level = if (emphasis & bit) != 0 {
+                    7
+                } else {
+                    (color >> shift) & 7
+                };
+
+                (level * 255 / 7) as u8
+            };
+
+            Rgb(
+                channel(6, EMPHASIS_RED),
+                channel(3, EMPHASIS_GREEN),
+                channel(0, EMPHASIS_BLUE),
+            )
+        })
+        .collect()
+}
+
+// The 2C07 palette, decoded from the PPU's output signal (see 'signal') in the same way as a PAL
+// TV. The 2C07 generates the same levels as the 2C02, but its colour phases sit half a hue step
+// away from those of the 2C02, relative to the colour burst. Line-to-line phase alternation
+// cancels out once decoded, so it does not affect the palette.
+fn rp2c07_colors() -> Vec<Rgb> {
+    let (shift_sin, shift_cos) = RP2C07_HUE_SHIFT.to_radians().sin_cos();
+
+    (0..512)
+        .map(|index| {
+            // Red and green emphasis are swapped relative to the 2C02
+            let emphasis = index >> 6;
+            let emphasis = (emphasis & EMPHASIS_BLUE)
+                | ((emphasis & EMPHASIS_RED) << 1)
+                | ((emphasis & EMPHASIS_GREEN) >> 1);
+
+            let pixel = ((emphasis << 6) | (index & 63)) as u16;
+
+            let (mut y, mut i, mut q) = (0.0, 0.0, 0.0);
+
+            for phase in 0..PHASE_COUNT {
+                let (luma, chroma) = signal(pixel, phase);
+                let (sin, cos) = (2.0 * PI * phase as f32 / PHASE_COUNT as f32).sin_cos();
+                y += luma;
+                i += chroma * cos;
+                q += chroma * sin;
+            }
+
+            let y = y / PHASE_COUNT as f32;
+            let (i, q) = [i, q].map(|value| 2.0 * value / PHASE_COUNT as f32).into();
+            let (i, q) = (i * shift_cos - q * shift_sin, i * shift_sin + q * shift_cos);
+
+            let [red, green, blue] = [
+                y + 0.956 * i + 0.621 * q,
+                y - 0.272 * i - 0.647 * q,
+                y - 1.106 * i + 1.703 * q,
+            ]
+            .map(|value| (value * 255.0).round().clamp(0.0, 255.0) as u8);
+
+            Rgb(red, green, blue)
+        })
+        .collect()
+}
+
+// A commonly used approximation of the 2C02 (NTSC) palette
+const RP2C02: [Rgb; 64] = [
+    // Dark
+    Rgb(84, 84, 84),
+    Rgb(0, 30, 116),
+    Rgb(8, 16, 144),
+    Rgb(48, 0, 136),
+    Rgb(68, 0, 100),
+    Rgb(92, 0, 48),
+    Rgb(84, 4, 0),
+    Rgb(60, 24, 0),
+    Rgb(32, 42, 0),
+    Rgb(8, 58, 0),
+    Rgb(0, 64, 0),
+    Rgb(0, 60, 0),
+    Rgb(0, 50, 60),
+    Rgb(0, 0, 0),
+    Rgb(0, 0, 0),
+    Rgb(0, 0, 0),
+    // Medium
+    Rgb(152, 150, 152),
+    Rgb(8, 76, 196),
+    Rgb(48, 50, 236),
+    Rgb(92, 30, 228),
+    Rgb(136, 20, 176),
+    Rgb(160, 20, 100),
+    Rgb(152, 34, 32),
+    Rgb(120, 60, 0),
+    Rgb(84, 90, 0),
+    Rgb(40, 114, 0),
+    Rgb(8, 124, 0),
+    Rgb(0, 118, 40),
+    Rgb(0, 102, 120),
+    Rgb(0, 0, 0),
+    Rgb(0, 0, 0),
+    Rgb(0, 0, 0),
+    // Light
+    Rgb(236, 238, 236),
+    Rgb(76, 154, 236),
+    Rgb(120, 124, 236),
+    Rgb(176, 98, 236),
+    Rgb(228, 84, 236),
+    Rgb(236, 88, 180),
+    Rgb(236, 106, 100),
+    Rgb(212, 136, 32),
+    Rgb(160, 170, 0),
+    Rgb(116, 196, 0),
+    Rgb(76, 208, 32),
+    Rgb(56, 204, 108),
+    Rgb(56, 180, 204),
+    Rgb(60, 60, 60),
+    Rgb(0, 0, 0),
+    Rgb(0, 0, 0),
+    // Pale
+    Rgb(236, 238, 236),
+    Rgb(168, 204, 236),
+    Rgb(188, 188, 236),
+    Rgb(212, 178, 236),
+    Rgb(236, 174, 236),
+    Rgb(236, 174, 212),
+    Rgb(236, 180, 176),
+    Rgb(228, 196, 144),
+    Rgb(204, 210, 120),
+    Rgb(180, 222, 120),
+    Rgb(168, 226, 144),
+    Rgb(152, 226, 180),
+    Rgb(160, 214, 228),
+    Rgb(160, 162, 160),
+    Rgb(0, 0, 0),
+    Rgb(0, 0, 0),
+];
+
+// The RGB PPUs (2C03 and 2C05) output each channel at one of eight levels, given here in octal
+const RP2C03: [u16; 64] = [
+    0o333, 0o014, 0o006, 0o326, 0o403, 0o503, 0o510, 0o420, 0o320, 0o120, 0o031, 0o040, 0o022,
+    0o000, 0o000, 0o000, 0o555, 0o036, 0o027, 0o407, 0o507, 0o704, 0o700, 0o630, 0o430, 0o140,
+    0o040, 0o053, 0o044, 0o000, 0o000, 0o000, 0o777, 0o357, 0o447, 0o637, 0o707, 0o737, 0o740,
+    0o750, 0o660, 0o360, 0o070, 0o276, 0o077, 0o000, 0o000, 0o000, 0o777, 0o567, 0o657, 0o757,
+    0o747, 0o755, 0o764, 0o772, 0o773, 0o572, 0o473, 0o276, 0o467, 0o000, 0o000, 0o000,
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pal_file_sizes() {
+        assert!(NesPalette::from_pal(vec![0; 192]).is_ok());
+        assert!(NesPalette::from_pal(vec![0; 1536]).is_ok());
+        assert!(NesPalette::from_pal(vec![0; 200]).is_err());
+    }
+
+    #[test]
+    fn emphasis_darkens_other_channels() {
+        let colors = NesPalette::Rp2c02.colors(false);
+        let Rgb(red, green, blue) = colors[0x30];
+        let Rgb(emphasis_red, emphasis_green, emphasis_blue) = colors[0x30 | (EMPHASIS_RED << 6)];
+        assert_eq!(emphasis_red, red);
+        assert!(emphasis_green < green && emphasis_blue < blue);
+
+        // The 2C07 swaps the red and green bits
+        let colors = NesPalette::Rp2c07.colors(false);
+        let Rgb(red, _, _) = colors[0x30];
+        let Rgb(emphasis_red, emphasis_green, emphasis_blue) = colors[0x30 | (EMPHASIS_RED << 6)];
+        assert!(emphasis_red < red);
+        assert!(emphasis_green > emphasis_red && emphasis_green > emphasis_blue);
+    }
+
+    #[test]
+    fn rgb_ppu_emphasis_maximises_channel() {
+        let colors = NesPalette::Auto.colors(true);
+        let Rgb(red, green, blue) = colors[0x0f | (EMPHASIS_BLUE << 6)];
+        assert_eq!((red, green, blue), (0, 0, 255));
+    }
+
+    #[test]
+    fn rp2c07_is_decoded_from_signal() {
+        let colors = NesPalette::Rp2c07.colors(false);
+
+        // The grey column stays grey, and black stays black
+        for index in [0x00, 0x10, 0x20, 0x2d, 0x3d] {
+            let Rgb(red, green, blue) = colors[index];
+            assert!(red == green && green == blue, "{:02X}", index);
+        }
+
+        let Rgb(red, green, blue) = colors[0x0f];
+        assert_eq!((red, green, blue), (0, 0, 0));
+
+        let Rgb(red, green, blue) = colors[0x20];
+        assert_eq!((red, green, blue), (255, 255, 255));
+
+        // Every emphasis bit darkens white, and all three together keep it grey
+        for emphasis in 1..8 {
+            let Rgb(red, green, blue) = colors[0x20 | (emphasis << 6)];
+            assert!(red < 255 || green < 255 || blue < 255);
+        }
+
+        let Rgb(red, green, blue) = colors[0x20 | (7 << 6)];
+        assert!(red == green && green == blue && red < 255);
+
+        // Hues are offset from the 2C02's, so red leans towards orange
+        let Rgb(red, green, blue) = colors[0x16];
+        assert!(red > green && green > blue);
+    }
+}
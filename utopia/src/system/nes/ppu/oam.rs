@@ -1,3 +1,4 @@
+use crate::util::state::{Snapshot, State};
 use tracing::trace;
 
 pub struct Oam {
@@ -86,3 +87,11 @@ impl Oam {
         (sprites_selected, sprite_zero_selected)
     }
 }
+
+impl Snapshot for Oam {
+    fn snapshot(&mut self, state: &mut State) {
+        self.address.snapshot(state);
+        self.primary.snapshot(state);
+        self.secondary.snapshot(state);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::util::state::{Snapshot, State};
 use tracing::trace;
 
 pub struct Palette {
@@ -27,3 +28,9 @@ impl Palette {
         trace!("Palette Write: {:02X} <= {:02X}", index, self.data[index]);
     }
 }
+
+impl Snapshot for Palette {
+    fn snapshot(&mut self, state: &mut State) {
+        self.data.snapshot(state);
+    }
+}
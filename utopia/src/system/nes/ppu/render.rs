@@ -1,4 +1,5 @@
 use super::super::cartridge::Cartridge;
+use crate::util::state::{Snapshot, State};
 use crate::Mapped;
 use tracing::trace;
 
@@ -152,7 +153,7 @@ impl super::Ppu {
             sprite_present = true;
         }
 
-        self.screen.draw(color);
+        self.draw(color);
     }
 
     fn load_bg_tiles(&mut self, cartridge: &mut Cartridge<impl Mapped>) {
@@ -323,3 +324,27 @@ impl super::Ppu {
         }
     }
 }
+
+impl Snapshot for Sprite {
+    fn snapshot(&mut self, state: &mut State) {
+        self.x.snapshot(state);
+        self.chr_low.snapshot(state);
+        self.chr_high.snapshot(state);
+        self.attr.snapshot(state);
+    }
+}
+
+impl Snapshot for RenderState {
+    fn snapshot(&mut self, state: &mut State) {
+        self.address.snapshot(state);
+        self.name.snapshot(state);
+        self.attr_latch.snapshot(state);
+        self.chr_latch.snapshot(state);
+        self.chr_low.snapshot(state);
+        self.chr_high.snapshot(state);
+        self.attr_shift.snapshot(state);
+        self.sprite_y.snapshot(state);
+        self.sprite_name.snapshot(state);
+        self.sprites.snapshot(state);
+    }
+}
@@ -0,0 +1,116 @@
+use crate::util::ntsc::PHASE_COUNT;
+use std::array;
+use std::sync::OnceLock;
+
+// Output voltages relative to sync (as measured on the NESdev wiki)
+const BLACK: f32 = 0.518;
+const WHITE: f32 = 1.962;
+const ATTENUATION: f32 = 0.746;
+const LOW_LEVELS: [f32; 4] = [0.350, 0.518, 0.962, 1.550];
+const HIGH_LEVELS: [f32; 4] = [1.094, 1.506, 1.962, 1.962];
+
+// Shifts the colour wheel so that the decoded hues line up with the usual NES palettes
+const HUE_OFFSET: usize = 8;
+
+// Chroma is attenuated slightly relative to a pure square wave, as in a real decoder
+const SATURATION: f32 = 0.7;
+
+type SignalTable = [[(f32, f32); PHASE_COUNT]; 512];
+
+static SIGNALS: OnceLock<SignalTable> = OnceLock::new();
+
+// Returns the luma and chroma levels of a pixel (a 6-bit palette index plus the three emphasis
+// bits from PPUMASK) at the given subcarrier phase
+pub fn signal(pixel: u16, phase: usize) -> (f32, f32) {
+    SIGNALS.get_or_init(create_signals)[pixel as usize & 511][phase]
+}
+
+fn create_signals() -> SignalTable {
+    array::from_fn(|pixel| {
+        let levels: [f32; PHASE_COUNT] = array::from_fn(|phase| level(pixel, phase));
+        let luma = levels.iter().sum::<f32>() / PHASE_COUNT as f32;
+        levels.map(|level| (luma, (level - luma) * SATURATION))
+    })
+}
+
+// The PPU generates a square wave, switching between two voltages for the hue (colour) and
+// brightness (level) of the pixel. Emphasis attenuates the signal over part of each cycle.
+fn level(pixel: usize, phase: usize) -> f32 {
+    let color = pixel & 0x0f;
+    let emphasis = pixel >> 6;
+    let level = if color > 13 { 1 } else { (pixel >> 4) & 3 };
+
+    let in_color_phase = |color: usize| (color + phase + HUE_OFFSET) % PHASE_COUNT < 6;
+
+    let high = if color > 12 {
+        LOW_LEVELS[level]
+    } else {
+        HIGH_LEVELS[level]
+    };
+
+    let low = if color == 0 { high } else { LOW_LEVELS[level] };
+
+    let mut signal = if in_color_phase(color) { high } else { low };
+
+    if ((emphasis & 1) != 0 && in_color_phase(0))
+        || ((emphasis & 2) != 0 && in_color_phase(4))
+        || ((emphasis & 4) != 0 && in_color_phase(8))
+    {
+        signal *= ATTENUATION;
+    }
+
+    (signal - BLACK) / (WHITE - BLACK)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn luma(pixel: u16) -> f32 {
+        signal(pixel, 0).0
+    }
+
+    fn has_chroma(pixel: u16) -> bool {
+        (0..PHASE_COUNT).any(|phase| signal(pixel, phase).1.abs() > 1e-6)
+    }
+
+    #[test]
+    fn grey_column_has_no_chroma() {
+        for level in 0..4 {
+            for color in [0x00, 0x0d, 0x0e, 0x0f] {
+                assert!(
+                    !has_chroma(level << 4 | color),
+                    "{:02X}",
+                    level << 4 | color
+                );
+            }
+
+            for color in 0x01..=0x0c {
+                assert!(has_chroma(level << 4 | color), "{:02X}", level << 4 | color);
+            }
+        }
+
+        assert!((luma(0x0f) - 0.0).abs() < 1e-6);
+        assert!((luma(0x20) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn emphasis_darkens() {
+        for pixel in 0x00..0x40 {
+            for emphasis in 1..8 {
+                let emphasised = emphasis << 6 | pixel;
+
+                assert!(
+                    luma(emphasised) < luma(pixel),
+                    "{:02X} with emphasis {}",
+                    pixel,
+                    emphasis
+                );
+            }
+        }
+
+        // Emphasising all three colours darkens greys evenly, so they stay grey
+        assert!(!has_chroma(0x1c0 | 0x30));
+        assert!(has_chroma(0x040 | 0x30));
+    }
+}
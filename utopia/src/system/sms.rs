@@ -1,4 +1,7 @@
-use super::{InstanceOptions, JoypadState, MemoryMapper, Size, SystemOptions, WgpuContext};
+use super::{
+    InstanceOptions, JoypadState, MemoryMapper, RomInfo, Size, SystemOptions, SystemType,
+    WgpuContext,
+};
 use crate::core::z80::{self, Core};
 use crate::util::mirror::Mirror;
 use interrupt::Interrupt;
@@ -37,11 +40,17 @@ impl<T: MemoryMapper> crate::System<T> for System<T> {
     ) -> Result<Box<dyn crate::Instance>, crate::Error> {
         Ok(Box::new(Instance::new(options)))
     }
+
+    fn rom_info(&self, rom_data: &[u8]) -> Result<RomInfo, crate::Error> {
+        let mut info = RomInfo::new(SystemType::SegaMasterSystem, rom_data);
+        info.add("ROM Size", rom_data.len());
+        Ok(info)
+    }
 }
 
 pub struct Instance {
     core: Core<Bus>,
-    _wgpu_context: WgpuContext,
+    _wgpu_context: Option<WgpuContext>,
 }
 
 impl Instance {
@@ -57,7 +66,7 @@ impl Instance {
 }
 
 impl crate::Instance for Instance {
-    fn run_frame(&mut self, _joypad_state: &JoypadState) {
+    fn run_frame(&mut self, _ports: &[Option<JoypadState>]) {
         let core = &mut self.core;
 
         loop {
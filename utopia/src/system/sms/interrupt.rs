@@ -1,5 +1,5 @@
-use std::cell::Cell;
-use std::rc::Rc;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
 use tracing::trace;
 
 #[repr(u8)]
@@ -14,7 +14,7 @@ pub enum InterruptType {
 
 #[derive(Clone)]
 pub struct Interrupt {
-    inner: Rc<Cell<u8>>,
+    inner: Arc<AtomicU8>,
 }
 
 impl Interrupt {
@@ -25,24 +25,24 @@ impl Interrupt {
     }
 
     pub fn poll(&self) -> u8 {
-        self.inner.get()
+        self.inner.load(Ordering::Relaxed)
     }
 
     pub fn has(&self, int_type: InterruptType) -> bool {
-        self.inner.get() & (int_type as u8) != 0
+        self.inner.load(Ordering::Relaxed) & (int_type as u8) != 0
     }
 
     pub fn clear(&mut self, int_type: InterruptType) {
-        let mut value = self.inner.get();
+        let mut value = self.inner.load(Ordering::Relaxed);
         value &= !(int_type as u8);
-        self.inner.set(value);
+        self.inner.store(value, Ordering::Relaxed);
         trace!("Interrupt Cleared: {:?}", int_type);
     }
 
     pub fn raise(&mut self, int_type: InterruptType) {
-        let mut value = self.inner.get();
+        let mut value = self.inner.load(Ordering::Relaxed);
         value |= int_type as u8;
-        self.inner.set(value);
+        self.inner.store(value, Ordering::Relaxed);
         trace!("Interrupt Raised: {:?}", int_type);
     }
 }
@@ -1,7 +1,15 @@
+use crate::achievement::{Achievements, MemoryView};
+use crate::cheat::{Cheats, MemoryPoke};
 use crate::core::wdc65c816::{Bus, Core, Interrupt, INT_NMI};
+use crate::database::{self, Overrides};
+use crate::util::audio::AudioFilter;
 use crate::util::mirror::{Mirror, MirrorVec};
+use crate::util::ntsc::{self, NtscDecoder, NtscFilter};
 use crate::util::upscaler::Upscaler;
-use crate::{BiosLoader, InstanceOptions, JoypadState, Mapped, MemoryMapper, Size, SystemOptions};
+use crate::{
+    AchievementQueue, AchievementSet, BiosLoader, CheatSet, InstanceOptions, JoypadState, Mapped,
+    MemoryMapper, RomInfo, Size, SystemOptions, SystemType,
+};
 use apu::Apu;
 use clock::{Clock, Event, FAST_CYCLES, TIMER_IRQ};
 use dma::Dma;
@@ -12,7 +20,7 @@ use registers::Registers;
 use std::error::Error;
 use std::fmt;
 use tracing::{info, trace, warn};
-use wram::Wram;
+use wram::{Wram, WRAM_SIZE};
 
 mod apu;
 mod clock;
@@ -24,11 +32,19 @@ mod ppu;
 mod registers;
 mod wram;
 
+pub(crate) use header::detect;
+
 const SAMPLE_RATE: u64 = 32000;
 
+// Output pixels are hi-res, each lasting for two master clocks (four phases of the colour
+// subcarrier)
+const NTSC_SAMPLES_PER_PIXEL: usize = 4;
+
 pub struct System<'a, U: MemoryMapper + 'static> {
     bios_loader: &'a dyn BiosLoader,
     memory_mapper: &'a U,
+    audio_filter: AudioFilter,
+    ntsc_filter: NtscFilter,
 }
 
 impl<'a, T: MemoryMapper> System<'a, T> {
@@ -36,6 +52,8 @@ impl<'a, T: MemoryMapper> System<'a, T> {
         Self {
             bios_loader: options.bios_loader,
             memory_mapper: options.memory_mapper,
+            audio_filter: options.audio_filter,
+            ntsc_filter: options.ntsc_filter,
         }
     }
 }
@@ -53,36 +71,111 @@ impl<'a, T: MemoryMapper> crate::System<T> for System<'a, T> {
         &self,
         options: InstanceOptions,
     ) -> Result<Box<dyn crate::Instance>, crate::Error> {
-        let result = Instance::new(self.bios_loader, self.memory_mapper, options);
+        let result = Instance::new(
+            self.bios_loader,
+            self.memory_mapper,
+            self.audio_filter,
+            self.ntsc_filter,
+            options,
+        );
 
         Ok(Box::new(
             result.map_err(|err| crate::Error(err.to_string()))?,
         ))
     }
+
+    fn rom_info(&self, rom_data: &[u8]) -> Result<RomInfo, crate::Error> {
+        let mut info = RomInfo::new(SystemType::Snes, rom_data);
+        let header = header::parse(rom_data, info.overrides.layout);
+
+        info.add("Title", header.title.trim_end());
+        info.add("Map Mode", format!("{:02X}", header.map_mode));
+        info.add("Cartridge Type", format!("{:02X}", header.cartridge_type));
+        info.add("ROM Size", header.rom_size);
+        info.add("SRAM Size", header.sram_size);
+        info.add("Battery Backed", battery_backed(&header, info.overrides));
+        Ok(info)
+    }
 }
 
 pub struct Instance<T: Mapped> {
     core: Core<Hardware<T>>,
-    upscaler: Upscaler,
+    upscaler: Option<Upscaler>,
+    achievements: Achievements,
+    cheats: Cheats,
+    ntsc: NtscDecoder,
+    ntsc_pixels: Vec<u8>,
+    ntsc_line: Vec<[u8; 3]>,
 }
 
 impl<T: Mapped> Instance<T> {
     pub fn new<U: MemoryMapper<Mapped = T>>(
         bios_loader: &dyn BiosLoader,
         memory_mapper: &U,
+        audio_filter: AudioFilter,
+        ntsc_filter: NtscFilter,
         options: InstanceOptions,
     ) -> Result<Self, Box<dyn Error>> {
-        let hw = Hardware::new(bios_loader, memory_mapper, options.rom_data)?;
+        let hw = Hardware::new(bios_loader, memory_mapper, options.rom_data, audio_filter)?;
         let core = Core::new(hw);
 
-        let upscaler = Upscaler::new(
-            options.wgpu_context,
-            (ppu::WIDTH as u32, ppu::HEIGHT as u32).into(),
-            options.output_resolution,
-            false,
-        );
+        let upscaler = options.wgpu_context.map(|ctx| {
+            Upscaler::new(
+                ctx,
+                (ppu::WIDTH as u32, ppu::HEIGHT as u32).into(),
+                options.output_resolution,
+                false,
+            )
+        });
+
+        Ok(Instance {
+            core,
+            upscaler,
+            achievements: Achievements::new(),
+            cheats: Cheats::new(),
+            ntsc: NtscDecoder::new(ntsc_filter),
+            ntsc_pixels: Vec::new(),
+            ntsc_line: Vec::with_capacity(ppu::WIDTH),
+        })
+    }
+
+    fn output_pixels(&self) -> &[u8] {
+        if self.ntsc.is_enabled() {
+            &self.ntsc_pixels
+        } else {
+            self.core.bus().ppu.pixels()
+        }
+    }
 
-        Ok(Instance { core, upscaler })
+    // Each scanline occupies two rows of output, so the signal is encoded from the RGB output
+    // one row at a time
+    fn decode_ntsc(&mut self) {
+        let pixels = self.core.bus().ppu.pixels();
+        let pitch = ppu::WIDTH * 4;
+
+        self.ntsc_pixels.resize(pixels.len(), 0);
+        self.ntsc.next_frame();
+
+        for (row, (input, output)) in pixels
+            .chunks_exact(pitch)
+            .zip(self.ntsc_pixels.chunks_exact_mut(pitch))
+            .enumerate()
+        {
+            self.ntsc_line.clear();
+            self.ntsc_line.extend(
+                input
+                    .chunks_exact(4)
+                    .map(|pixel| [pixel[0], pixel[1], pixel[2]]),
+            );
+
+            self.ntsc.decode_line(
+                row / 2,
+                &self.ntsc_line,
+                NTSC_SAMPLES_PER_PIXEL,
+                ntsc::encode_rgb,
+                output,
+            );
+        }
     }
 }
 
@@ -95,9 +188,52 @@ impl<T: Mapped> crate::Instance for Instance<T> {
         Some(self.core.bus_mut().apu.audio_queue())
     }
 
-    fn run_frame(&mut self, joypad_state: &JoypadState) {
+    fn audio_channels(&self) -> &[&'static str] {
+        self.core.bus().apu.audio_channels().names()
+    }
+
+    fn set_audio_channel_muted(&mut self, index: usize, muted: bool) {
+        self.core
+            .bus_mut()
+            .apu
+            .audio_channels_mut()
+            .set_muted(index, muted);
+    }
+
+    fn audio_channel_muted(&self, index: usize) -> bool {
+        self.core.bus().apu.audio_channels().is_muted(index)
+    }
+
+    fn set_stems_enabled(&mut self, enabled: bool) {
+        self.core
+            .bus_mut()
+            .apu
+            .audio_channels_mut()
+            .set_stems_enabled(enabled);
+    }
+
+    fn stem_queues(&mut self) -> Option<&mut [crate::AudioQueue]> {
+        Some(self.core.bus_mut().apu.audio_channels_mut().stem_queues())
+    }
+
+    fn load_achievements(&mut self, set: AchievementSet) -> Result<(), crate::Error> {
+        self.achievements.load(set)
+    }
+
+    fn achievement_events(&mut self) -> Option<&mut AchievementQueue> {
+        Some(self.achievements.events())
+    }
+
+    fn load_cheats(&mut self, set: CheatSet) -> Result<(), crate::Error> {
+        self.cheats.load(set);
+        Ok(())
+    }
+
+    fn run_frame(&mut self, ports: &[Option<JoypadState>]) {
+        self.cheats.apply(self.core.bus_mut());
+
         let core = &mut self.core;
-        core.bus_mut().joypad.update(joypad_state);
+        core.bus_mut().joypad.update(ports);
         core.bus_mut().ready = false;
 
         while !core.bus().ready {
@@ -108,11 +244,25 @@ impl<T: Mapped> crate::Instance for Instance<T> {
         let cpu_cycles = core.bus().clock.cycles();
         core.bus_mut().apu.run_until(cpu_cycles);
 
-        self.upscaler.update(self.core.bus().ppu.pixels());
+        self.achievements.evaluate(self.core.bus());
+
+        if self.ntsc.is_enabled() {
+            self.decode_ntsc();
+        }
     }
 
     fn present(&self, canvas: &wgpu::Texture) {
-        self.upscaler.render(canvas);
+        if let Some(upscaler) = &self.upscaler {
+            upscaler.update(self.output_pixels());
+            upscaler.render(canvas);
+        }
+    }
+
+    fn pixels(&self) -> Option<(&[u8], Size)> {
+        Some((
+            self.output_pixels(),
+            (ppu::WIDTH as u32, ppu::HEIGHT as u32).into(),
+        ))
     }
 }
 
@@ -137,17 +287,19 @@ impl<T: Mapped> Hardware<T> {
         bios_loader: &dyn BiosLoader,
         memory_mapper: &U,
         rom_data: Vec<u8>,
+        audio_filter: AudioFilter,
     ) -> Result<Self, Box<dyn Error>> {
         let ipl_rom = bios_loader.load("ipl_rom")?;
 
-        let header = header::parse(&rom_data);
+        let overrides = database::overrides(SystemType::Snes, &rom_data);
+        let header = header::parse(&rom_data, overrides.layout);
         info!("Title: {}", header.title);
         info!("Map Mode: {:02X}", header.map_mode);
         info!("Cartridge Type: {:02X}", header.cartridge_type);
         info!("ROM Size: {}", header.rom_size);
         info!("SRAM Size: {}", header.sram_size);
 
-        let battery_backed = [0x02, 0x05].contains(&(header.cartridge_type & 0x0f));
+        let battery_backed = battery_backed(&header, overrides);
         info!("Battery Backed: {}", battery_backed);
 
         let pages = memory::map(&header);
@@ -164,7 +316,7 @@ impl<T: Mapped> Hardware<T> {
             regs: Registers::new(),
             dma: Dma::new(),
             ppu: Ppu::new(),
-            apu: Apu::new(ipl_rom),
+            apu: Apu::new(ipl_rom, audio_filter),
             joypad: Joypad::new(),
         })
     }
@@ -338,8 +490,41 @@ impl<T: Mapped> Bus for Hardware<T> {
     }
 }
 
+impl<T: Mapped> MemoryView for Hardware<T> {
+    fn peek(&self, address: u32) -> u8 {
+        // WRAM first, followed immediately by SRAM
+        let address = address as usize;
+
+        if address < WRAM_SIZE {
+            self.wram[address]
+        } else if (address - WRAM_SIZE) < self.sram.len() {
+            self.sram[address - WRAM_SIZE]
+        } else {
+            0
+        }
+    }
+}
+
+impl<T: Mapped> MemoryPoke for Hardware<T> {
+    fn poke(&mut self, address: u32, value: u8) {
+        let address = address as usize;
+
+        if address < WRAM_SIZE {
+            self.wram[address] = value;
+        } else if (address - WRAM_SIZE) < self.sram.len() {
+            self.sram[address - WRAM_SIZE] = value;
+        }
+    }
+}
+
 impl<T: Mapped> fmt::Display for Hardware<T> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "{}", self.clock)
     }
 }
+
+fn battery_backed(header: &header::Header, overrides: Overrides) -> bool {
+    overrides
+        .battery
+        .unwrap_or([0x02, 0x05].contains(&(header.cartridge_type & 0x0f)))
+}
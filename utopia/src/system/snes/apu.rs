@@ -1,4 +1,5 @@
 use crate::core::spc700::{Bus, Core};
+use crate::util::audio::{AudioChannels, AudioFilter};
 use crate::util::MirrorVec;
 use crate::AudioQueue;
 use dsp::Dsp;
@@ -20,8 +21,8 @@ pub struct Apu {
 }
 
 impl Apu {
-    pub fn new(ipl_rom: Vec<u8>) -> Self {
-        let hw = Hardware::new(ipl_rom);
+    pub fn new(ipl_rom: Vec<u8>, audio_filter: AudioFilter) -> Self {
+        let hw = Hardware::new(ipl_rom, audio_filter);
         let core = Core::new(hw);
 
         Self {
@@ -34,6 +35,14 @@ impl Apu {
         self.core.bus_mut().dsp.audio_queue()
     }
 
+    pub fn audio_channels(&self) -> &AudioChannels {
+        self.core.bus().dsp.audio_channels()
+    }
+
+    pub fn audio_channels_mut(&mut self) -> &mut AudioChannels {
+        self.core.bus_mut().dsp.audio_channels_mut()
+    }
+
     pub fn read(&self, address: u8) -> u8 {
         self.core.bus().output_ports[address as usize & 3]
     }
@@ -76,7 +85,7 @@ struct Hardware {
 }
 
 impl Hardware {
-    pub fn new(ipl_rom: Vec<u8>) -> Self {
+    pub fn new(ipl_rom: Vec<u8>, audio_filter: AudioFilter) -> Self {
         Self {
             time_remaining: 0,
             cycles: 0,
@@ -86,7 +95,7 @@ impl Hardware {
             timers: [Timer::new(0), Timer::new(1), Timer::new(2)],
             ram: MirrorVec::new(RAM_SIZE),
             ipl_rom: ipl_rom.into(),
-            dsp: Dsp::new(),
+            dsp: Dsp::new(audio_filter),
         }
     }
 
@@ -1,3 +1,5 @@
+use super::super::SAMPLE_RATE;
+use crate::util::audio::{AudioChannels, AudioFilter, OutputFilter};
 use crate::util::MirrorVec;
 use crate::AudioQueue;
 use directory::Directory;
@@ -14,6 +16,10 @@ mod voice;
 
 const TOTAL_REGISTERS: usize = 128;
 
+const VOICE_NAMES: [&str; 8] = [
+    "Voice 1", "Voice 2", "Voice 3", "Voice 4", "Voice 5", "Voice 6", "Voice 7", "Voice 8",
+];
+
 pub struct Dsp {
     address: u8,
     poll_key_state: bool,
@@ -23,12 +29,16 @@ pub struct Dsp {
     noise: NoiseGenerator,
     dir: Directory,
     voices: [Voice; 8],
+    filter: OutputFilter,
+    audio_channels: AudioChannels,
     audio_queue: AudioQueue,
     data: [u8; TOTAL_REGISTERS],
 }
 
 impl Dsp {
-    pub fn new() -> Self {
+    pub fn new(audio_filter: AudioFilter) -> Self {
+        let audio_filter = audio_filter.resolve(AudioFilter::Snes);
+
         Self {
             address: 0,
             poll_key_state: false,
@@ -47,6 +57,13 @@ impl Dsp {
                 Voice::new(6),
                 Voice::new(7),
             ],
+            filter: OutputFilter::new(audio_filter, SAMPLE_RATE),
+            audio_channels: AudioChannels::new(
+                VOICE_NAMES.to_vec(),
+                audio_filter,
+                None,
+                SAMPLE_RATE,
+            ),
             audio_queue: AudioQueue::new(),
             data: [0; TOTAL_REGISTERS],
         }
@@ -60,6 +77,14 @@ impl Dsp {
         &mut self.audio_queue
     }
 
+    pub fn audio_channels(&self) -> &AudioChannels {
+        &self.audio_channels
+    }
+
+    pub fn audio_channels_mut(&mut self) -> &mut AudioChannels {
+        &mut self.audio_channels
+    }
+
     pub fn set_address(&mut self, value: u8) {
         self.address = value;
         trace!("DSP Address: {:02X}", self.address);
@@ -159,8 +184,15 @@ impl Dsp {
         let mut dsp_out = (0, 0);
         let mut echo_in = (0, 0);
 
-        for voice in &mut self.voices {
+        let mut voice_out = [(0, 0); 8];
+
+        for (index, voice) in self.voices.iter_mut().enumerate() {
             let sample = voice.step(&self.dir, ram, self.noise.level(), self.poll_key_state);
+            voice_out[index] = sample;
+
+            if self.audio_channels.is_muted(index) {
+                continue;
+            }
 
             dsp_out.0 = clamp16(dsp_out.0 + sample.0);
             dsp_out.1 = clamp16(dsp_out.1 + sample.1);
@@ -180,8 +212,19 @@ impl Dsp {
 
         // TODO: Mute
 
-        self.audio_queue
-            .push_back((!dsp_out.0 as f32 / 32768.0, !dsp_out.1 as f32 / 32768.0));
+        let output = (!dsp_out.0 as f32 / 32768.0, !dsp_out.1 as f32 / 32768.0);
+        self.audio_queue.push_back(self.filter.apply(output));
+
+        // Stems only contain each voice's dry signal, as echo is shared between all voices
+        if self.audio_channels.stems_enabled() {
+            for (index, sample) in voice_out.into_iter().enumerate() {
+                let left = clamp16((sample.0 * self.volume_left) >> 7);
+                let right = clamp16((sample.1 * self.volume_right) >> 7);
+
+                self.audio_channels
+                    .push_stem(index, (!left as f32 / 32768.0, !right as f32 / 32768.0));
+            }
+        }
 
         self.poll_key_state = !self.poll_key_state;
 
@@ -1,3 +1,4 @@
+use crate::database::RomLayout;
 use tracing::trace;
 
 const BASE_SIZE: usize = 0x0400;
@@ -14,18 +15,19 @@ pub struct Header {
     pub cartridge_type: u8,
     pub rom_size: usize,
     pub sram_size: usize,
+    checksum: u16,
+    checksum_complement: u16,
+    reset_opcode: u8,
 }
 
-pub fn parse(rom: &[u8]) -> Header {
-    let lo_rom = try_parse(HeaderLocation::LoRom, &rom[0x0000..]);
-
-    let hi_rom = if rom.len() > 0x0001_0000 {
-        try_parse(HeaderLocation::HiRom, &rom[0x8000..])
-    } else {
-        None
+pub fn parse(rom: &[u8], layout: Option<RomLayout>) -> Header {
+    let headers = match (find_headers(rom), layout) {
+        ((Some(lo_rom), _), Some(RomLayout::LoRom)) => (Some(lo_rom), None),
+        ((_, Some(hi_rom)), Some(RomLayout::HiRom)) => (None, Some(hi_rom)),
+        (headers, _) => headers,
     };
 
-    match (lo_rom, hi_rom) {
+    match headers {
         (Some(lo_rom), Some(hi_rom)) => {
             let lo_rom_score = score(&lo_rom);
             let hi_rom_score = score(&hi_rom);
@@ -54,12 +56,43 @@ pub fn parse(rom: &[u8]) -> Header {
                 cartridge_type: 0x00,
                 rom_size: rom.len(),
                 sram_size: 0,
+                checksum: 0,
+                checksum_complement: 0,
+                reset_opcode: 0,
             }
         }
     }
 }
 
+pub fn detect(rom: &[u8]) -> bool {
+    let (lo_rom, hi_rom) = find_headers(rom);
+
+    // A header that merely parses isn't enough to go on, as plenty of non-SNES ROMs will pass
+    // the basic checks by chance
+    lo_rom
+        .into_iter()
+        .chain(hi_rom)
+        .any(|header| plausibility(&header) > 0)
+}
+
+fn find_headers(rom: &[u8]) -> (Option<Header>, Option<Header>) {
+    let lo_rom = try_parse(HeaderLocation::LoRom, &rom[0x0000..]);
+
+    let hi_rom = if rom.len() > 0x0001_0000 {
+        try_parse(HeaderLocation::HiRom, &rom[0x8000..])
+    } else {
+        None
+    };
+
+    (lo_rom, hi_rom)
+}
+
 fn try_parse(id: HeaderLocation, rom: &[u8]) -> Option<Header> {
+    if rom.len() < 0x8000 {
+        trace!("{:?}: ROM too small to contain header", id);
+        return None;
+    }
+
     let reset_vector = u16::from_le_bytes([rom[0x7ffc], rom[0x7ffd]]);
 
     if reset_vector < 0x8000 || reset_vector >= 0xffc0 {
@@ -110,6 +143,11 @@ fn try_parse(id: HeaderLocation, rom: &[u8]) -> Option<Header> {
     let title = String::from_utf8_lossy(&rom[0x7fc0..=0x7fd4]).into_owned();
 
     let cartridge_type = rom[0x7fd6];
+    let checksum_complement = u16::from_le_bytes([rom[0x7fdc], rom[0x7fdd]]);
+    let checksum = u16::from_le_bytes([rom[0x7fde], rom[0x7fdf]]);
+
+    // Bank $00 is mapped to the same offset in both layouts (relative to the header location)
+    let reset_opcode = rom[reset_vector as usize - 0x8000];
 
     Some(Header {
         title,
@@ -117,6 +155,9 @@ fn try_parse(id: HeaderLocation, rom: &[u8]) -> Option<Header> {
         cartridge_type,
         rom_size,
         sram_size,
+        checksum,
+        checksum_complement,
+        reset_opcode,
     })
 }
 
@@ -124,3 +165,29 @@ fn score(_header: &Header) -> i32 {
     // TODO
     0
 }
+
+// How likely it is that a header belongs to a real SNES ROM. This is only used to detect the
+// system type, not to choose between LoROM and HiROM headers.
+fn plausibility(header: &Header) -> i32 {
+    let mut score = 0;
+
+    if (header.checksum ^ header.checksum_complement) == 0xffff {
+        score += 4;
+    }
+
+    score += match header.reset_opcode {
+        // SEI, CLC, SEC, STZ, JMP, JML
+        0x78 | 0x18 | 0x38 | 0x9c | 0x4c | 0x5c => 2,
+        // REP, SEP, LDA, LDX, LDY, JSR, JSL
+        0xc2 | 0xe2 | 0xa9 | 0xad | 0xaf | 0xa2 | 0xae | 0xa0 | 0xac | 0x20 | 0x22 => 1,
+        // BRK, COP, RTI, RTS, RTL, WAI, STP, or unprogrammed ROM
+        0x00 | 0x02 | 0x40 | 0x60 | 0x6b | 0xcb | 0xdb | 0xff => -4,
+        _ => 0,
+    };
+
+    if header.title.chars().all(|ch| (' '..='~').contains(&ch)) {
+        score += 1;
+    }
+
+    score
+}
@@ -1,3 +1,4 @@
+use crate::system;
 use crate::JoypadState;
 use tracing::{trace, warn};
 
@@ -28,22 +29,11 @@ impl Joypad {
         (self.auto_read_state[index] >> 8) as u8
     }
 
-    pub fn update(&mut self, new_state: &JoypadState) {
-        let JoypadState { buttons, .. } = &new_state;
-
-        self.current_state[0] = 0;
-        self.current_state[0] |= if buttons[0] { 0x8000 } else { 0 };
-        self.current_state[0] |= if buttons[2] { 0x4000 } else { 0 };
-        self.current_state[0] |= if buttons[8] { 0x2000 } else { 0 };
-        self.current_state[0] |= if buttons[9] { 0x1000 } else { 0 };
-        self.current_state[0] |= if buttons[12] { 0x0800 } else { 0 };
-        self.current_state[0] |= if buttons[13] { 0x0400 } else { 0 };
-        self.current_state[0] |= if buttons[14] { 0x0200 } else { 0 };
-        self.current_state[0] |= if buttons[15] { 0x0100 } else { 0 };
-        self.current_state[0] |= if buttons[1] { 0x0080 } else { 0 };
-        self.current_state[0] |= if buttons[3] { 0x0040 } else { 0 };
-        self.current_state[0] |= if buttons[4] { 0x0020 } else { 0 };
-        self.current_state[0] |= if buttons[5] { 0x0010 } else { 0 };
+    pub fn update(&mut self, ports: &[Option<JoypadState>]) {
+        // Only two ports for now, as there is no multitap support
+        for (index, state) in self.current_state.iter_mut().take(2).enumerate() {
+            *state = system::joypad(ports, index).map_or(0, encode);
+        }
     }
 
     pub fn read_serial(&mut self, address: u8, prev_value: u8) -> u8 {
@@ -113,3 +103,22 @@ impl Joypad {
         }
     }
 }
+
+fn encode(joypad_state: &JoypadState) -> u16 {
+    let JoypadState { buttons, .. } = joypad_state;
+
+    let mut state = 0;
+    state |= if buttons[0] { 0x8000 } else { 0 };
+    state |= if buttons[2] { 0x4000 } else { 0 };
+    state |= if buttons[8] { 0x2000 } else { 0 };
+    state |= if buttons[9] { 0x1000 } else { 0 };
+    state |= if buttons[12] { 0x0800 } else { 0 };
+    state |= if buttons[13] { 0x0400 } else { 0 };
+    state |= if buttons[14] { 0x0200 } else { 0 };
+    state |= if buttons[15] { 0x0100 } else { 0 };
+    state |= if buttons[1] { 0x0080 } else { 0 };
+    state |= if buttons[3] { 0x0040 } else { 0 };
+    state |= if buttons[4] { 0x0020 } else { 0 };
+    state |= if buttons[5] { 0x0010 } else { 0 };
+    state
+}
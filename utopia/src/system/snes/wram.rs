@@ -2,7 +2,7 @@ use crate::util::MirrorVec;
 use std::ops::{Index, IndexMut};
 use tracing::{trace, warn};
 
-const WRAM_SIZE: usize = 131072;
+pub const WRAM_SIZE: usize = 131072;
 
 pub struct Wram {
     data: MirrorVec<u8>,
@@ -5,7 +5,10 @@ pub use size::Size;
 pub mod audio;
 pub mod memory;
 pub mod mirror;
+pub mod ntsc;
+pub mod scaler;
 pub mod size;
+pub mod state;
 pub mod upscaler;
 
 mod color;
@@ -1,3 +1,9 @@
+pub use blip::BlipBuffer;
+pub use channels::AudioChannels;
+pub use filter::{AudioFilter, OutputFilter};
 pub use sequencer::Sequencer;
 
+mod blip;
+mod channels;
+mod filter;
 mod sequencer;
@@ -0,0 +1,159 @@
+use std::array;
+use std::f64::consts::PI;
+use std::sync::OnceLock;
+
+// Sub-sample positions at which a step can be placed
+const PHASE_COUNT: usize = 64;
+
+// Number of output samples affected by each step
+const KERNEL_SIZE: usize = 16;
+
+// Must be a power of two large enough to hold a kernel plus any samples not yet read
+const BUFFER_SIZE: usize = 32;
+
+// Fraction of the output Nyquist frequency that is allowed through
+const CUTOFF: f64 = 0.9;
+
+const TIME_BITS: u32 = 32;
+const AMPLITUDE_BITS: u32 = 20;
+const KERNEL_BITS: u32 = 15;
+const OUTPUT_SCALE: f32 = 1.0 / (1u64 << (AMPLITUDE_BITS + KERNEL_BITS)) as f32;
+
+type Kernel = [i64; KERNEL_SIZE];
+
+// Band-limited step synthesis, in the style of blargg's blip_buf. Rather than point-sampling a
+// channel's output, each change in amplitude is recorded at the exact clock cycle it happens, as
+// a band-limited step spread over the surrounding output samples. Integrating these steps gives
+// output free of the aliasing that point-sampling square waves and noise produces.
+//
+// Deltas are accumulated as integers, so the output settles on exactly the requested amplitude.
+pub struct BlipBuffer {
+    factor: u64,
+    time: u64,
+    amplitude: i64,
+    integrator: i64,
+    buffer: [i64; BUFFER_SIZE],
+    read_pos: usize,
+    kernels: &'static [Kernel; PHASE_COUNT],
+}
+
+impl BlipBuffer {
+    pub fn new(clock_rate: u64, sample_rate: u64) -> Self {
+        Self {
+            factor: (sample_rate << TIME_BITS) / clock_rate,
+            time: 0,
+            amplitude: 0,
+            integrator: 0,
+            buffer: [0; BUFFER_SIZE],
+            read_pos: 0,
+            kernels: KERNELS.get_or_init(create_kernels),
+        }
+    }
+
+    // Records a change in output level at the current clock cycle
+    pub fn set_amplitude(&mut self, amplitude: f32) {
+        let amplitude = (amplitude as f64 * (1u64 << AMPLITUDE_BITS) as f64).round() as i64;
+        let delta = amplitude - self.amplitude;
+
+        if delta == 0 {
+            return;
+        }
+
+        self.amplitude = amplitude;
+
+        let offset = (self.time >> TIME_BITS) as usize;
+        let phase = ((self.time >> (TIME_BITS - PHASE_COUNT.trailing_zeros())) as usize)
+            & (PHASE_COUNT - 1);
+
+        for (index, weight) in self.kernels[phase].iter().enumerate() {
+            self.buffer[(self.read_pos + offset + index) & (BUFFER_SIZE - 1)] += delta * weight;
+        }
+    }
+
+    // Advances by a single clock cycle. Returns the next output sample once it is complete.
+    pub fn step(&mut self) -> Option<f32> {
+        self.time += self.factor;
+
+        if self.time < (1 << TIME_BITS) {
+            return None;
+        }
+
+        self.time -= 1 << TIME_BITS;
+        self.integrator += std::mem::take(&mut self.buffer[self.read_pos]);
+        self.read_pos = (self.read_pos + 1) & (BUFFER_SIZE - 1);
+
+        Some(self.integrator as f32 * OUTPUT_SCALE)
+    }
+}
+
+static KERNELS: OnceLock<[Kernel; PHASE_COUNT]> = OnceLock::new();
+
+// Windowed sinc impulses (one for each phase), each summing to exactly 1 << KERNEL_BITS. The
+// impulse is delayed by half the kernel size so that it never reaches back into samples that have
+// already been read.
+fn create_kernels() -> [Kernel; PHASE_COUNT] {
+    array::from_fn(|phase| {
+        let offset = (KERNEL_SIZE / 2) as f64 + phase as f64 / PHASE_COUNT as f64;
+
+        let impulse: [f64; KERNEL_SIZE] = array::from_fn(|index| {
+            let x = index as f64 - offset;
+            let sinc = if x == 0.0 {
+                CUTOFF
+            } else {
+                (PI * CUTOFF * x).sin() / (PI * x)
+            };
+
+            // Blackman window, centred on the impulse and wide enough to cover every tap
+            let t = 0.5 + x / (KERNEL_SIZE + 2) as f64;
+            let window = 0.42 - 0.5 * (2.0 * PI * t).cos() + 0.08 * (4.0 * PI * t).cos();
+
+            sinc * window
+        });
+
+        let total: f64 = impulse.iter().sum();
+        let unit = (1u64 << KERNEL_BITS) as f64;
+        let mut kernel: Kernel =
+            array::from_fn(|index| (impulse[index] / total * unit).round() as i64);
+
+        // Put any rounding error into the centre of the impulse
+        let error = (1 << KERNEL_BITS) - kernel.iter().sum::<i64>();
+        kernel[KERNEL_SIZE / 2] += error;
+
+        kernel
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_rate_conversion() {
+        let mut blip = BlipBuffer::new(1789773, 44100);
+        let samples = (0..1789773).filter_map(|_| blip.step()).count();
+        assert!(samples.abs_diff(44100) <= 1, "{}", samples);
+    }
+
+    #[test]
+    fn steps_settle_on_exact_amplitude() {
+        let mut blip = BlipBuffer::new(1048576, 44100);
+        let mut output = Vec::new();
+
+        for cycle in 0..10000 {
+            // A square wave well above the output Nyquist frequency, followed by a held level
+            if cycle < 5000 {
+                blip.set_amplitude(if (cycle & 4) != 0 { 0.25 } else { -0.25 });
+            } else {
+                blip.set_amplitude(0.125);
+            }
+
+            output.extend(blip.step());
+        }
+
+        assert_eq!(*output.last().unwrap(), 0.125);
+
+        // The square wave itself should be almost entirely filtered out
+        let settled = &output[KERNEL_SIZE..(output.len() / 2 - KERNEL_SIZE)];
+        assert!(settled.iter().all(|sample| sample.abs() < 0.05));
+    }
+}
@@ -0,0 +1,166 @@
+use super::{AudioFilter, BlipBuffer, OutputFilter};
+use crate::AudioQueue;
+
+// Mute state for each of a sound chip's channels. When stems are enabled, each channel is also
+// rendered on its own (regardless of whether it is muted) into a queue of its own.
+pub struct AudioChannels {
+    names: Vec<&'static str>,
+    muted: Vec<bool>,
+    filter: AudioFilter,
+    clock_rate: Option<u64>,
+    sample_rate: u64,
+    stems: Vec<Stem>,
+    stem_queues: Vec<AudioQueue>,
+}
+
+struct Stem {
+    blip: Option<[BlipBuffer; 2]>,
+    filter: OutputFilter,
+}
+
+impl AudioChannels {
+    // Stems go through band-limited synthesis if a clock rate is given. Otherwise, samples are
+    // expected to be pushed at the output sample rate.
+    pub fn new(
+        names: Vec<&'static str>,
+        filter: AudioFilter,
+        clock_rate: Option<u64>,
+        sample_rate: u64,
+    ) -> Self {
+        Self {
+            muted: vec![false; names.len()],
+            names,
+            filter,
+            clock_rate,
+            sample_rate,
+            stems: Vec::new(),
+            stem_queues: Vec::new(),
+        }
+    }
+
+    pub fn names(&self) -> &[&'static str] {
+        &self.names
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_muted(&self, index: usize) -> bool {
+        self.muted.get(index).copied().unwrap_or(false)
+    }
+
+    pub fn set_muted(&mut self, index: usize, muted: bool) {
+        if let Some(entry) = self.muted.get_mut(index) {
+            *entry = muted;
+        }
+    }
+
+    pub fn stems_enabled(&self) -> bool {
+        !self.stems.is_empty()
+    }
+
+    pub fn set_stems_enabled(&mut self, enabled: bool) {
+        if !enabled {
+            self.stems.clear();
+            self.stem_queues.clear();
+            return;
+        }
+
+        if self.stems_enabled() {
+            return;
+        }
+
+        self.stems = (0..self.len())
+            .map(|_| Stem {
+                blip: self.clock_rate.map(|clock_rate| {
+                    [
+                        BlipBuffer::new(clock_rate, self.sample_rate),
+                        BlipBuffer::new(clock_rate, self.sample_rate),
+                    ]
+                }),
+                filter: OutputFilter::new(self.filter, self.sample_rate),
+            })
+            .collect();
+
+        self.stem_queues = vec![AudioQueue::new(); self.len()];
+    }
+
+    pub fn stem_queues(&mut self) -> &mut [AudioQueue] {
+        &mut self.stem_queues
+    }
+
+    // Records the output level of a stem at the current clock cycle
+    pub fn set_stem_amplitude(&mut self, index: usize, (left, right): (f32, f32)) {
+        if let Some(blip) = &mut self.stems[index].blip {
+            blip[0].set_amplitude(left);
+            blip[1].set_amplitude(right);
+        }
+    }
+
+    // Advances all stems by a single clock cycle
+    pub fn step_stems(&mut self) {
+        for (stem, queue) in self.stems.iter_mut().zip(&mut self.stem_queues) {
+            let Some(blip) = &mut stem.blip else {
+                continue;
+            };
+
+            if let (Some(left), Some(right)) = (blip[0].step(), blip[1].step()) {
+                queue.push_back(stem.filter.apply((left, right)));
+            }
+        }
+    }
+
+    pub fn push_stem(&mut self, index: usize, sample: (f32, f32)) {
+        let sample = self.stems[index].filter.apply(sample);
+        self.stem_queues[index].push_back(sample);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channels() -> AudioChannels {
+        AudioChannels::new(vec!["A", "B", "C"], AudioFilter::Raw, None, 32000)
+    }
+
+    #[test]
+    fn mute_state() {
+        let mut channels = channels();
+        assert_eq!(channels.names(), ["A", "B", "C"]);
+        assert!(!channels.is_muted(1));
+
+        channels.set_muted(1, true);
+        assert!(channels.is_muted(1));
+        assert!(!channels.is_muted(0));
+
+        // Out of range channels are never muted
+        channels.set_muted(3, true);
+        assert!(!channels.is_muted(3));
+    }
+
+    #[test]
+    fn one_stem_per_channel() {
+        let mut channels = channels();
+        assert!(!channels.stems_enabled());
+        assert!(channels.stem_queues().is_empty());
+
+        channels.set_stems_enabled(true);
+        channels.set_muted(2, true);
+        assert_eq!(channels.stem_queues().len(), 3);
+
+        // Muted channels still get a stem
+        channels.push_stem(0, (0.25, 0.25));
+        channels.push_stem(2, (0.5, -0.5));
+        channels.push_stem(2, (0.5, -0.5));
+
+        let queues = channels.stem_queues();
+        assert_eq!(queues[0].len(), 1);
+        assert!(queues[1].is_empty());
+        assert_eq!(queues[2].len(), 2);
+
+        channels.set_stems_enabled(false);
+        assert!(channels.stem_queues().is_empty());
+    }
+}
@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+
+// Which console's analog output stage to model. 'Auto' picks the one matching the system (and,
+// where it matters, the hardware model) being emulated.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFilter {
+    #[default]
+    Auto,
+    // Mixer output, unfiltered (for audio analysis)
+    Raw,
+    // NES front-loader (NES-001)
+    Nes,
+    Famicom,
+    Dmg,
+    Cgb,
+    // Game Boy Advance running Game Boy software
+    Agb,
+    Snes,
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Stage {
+    // Cutoff frequencies in Hz
+    HighPass(f32),
+    LowPass(f32),
+}
+
+impl AudioFilter {
+    pub fn resolve(self, auto: AudioFilter) -> AudioFilter {
+        if self == Self::Auto {
+            auto
+        } else {
+            self
+        }
+    }
+
+    fn stages(self) -> &'static [Stage] {
+        use Stage::*;
+
+        match self {
+            Self::Auto | Self::Raw => &[],
+            // As documented on the NESdev wiki
+            Self::Nes => &[HighPass(90.0), HighPass(440.0), LowPass(14000.0)],
+            Self::Famicom => &[HighPass(37.0)],
+            // Equivalent to the capacitor charge factors in Pan Docs (0.999958 and 0.998943 per
+            // 4 MHz clock respectively)
+            Self::Dmg => &[HighPass(28.0)],
+            Self::Cgb => &[HighPass(706.0)],
+            // Approximations, as these have not been measured as thoroughly
+            Self::Agb => &[HighPass(28.0), LowPass(16000.0)],
+            Self::Snes => &[HighPass(20.0), LowPass(14000.0)],
+        }
+    }
+}
+
+// A chain of first-order filters, applied to both channels
+pub struct OutputFilter {
+    stages: Vec<FilterStage>,
+}
+
+struct FilterStage {
+    high_pass: bool,
+    coefficient: f32,
+    prev_input: (f32, f32),
+    prev_output: (f32, f32),
+}
+
+impl OutputFilter {
+    pub fn new(filter: AudioFilter, sample_rate: u64) -> Self {
+        let dt = 1.0 / sample_rate as f32;
+
+        let stages = filter
+            .stages()
+            .iter()
+            .map(|&stage| {
+                let (high_pass, cutoff) = match stage {
+                    Stage::HighPass(cutoff) => (true, cutoff),
+                    Stage::LowPass(cutoff) => (false, cutoff),
+                };
+
+                let rc = 1.0 / (2.0 * PI * cutoff);
+
+                FilterStage {
+                    high_pass,
+                    coefficient: if high_pass {
+                        rc / (rc + dt)
+                    } else {
+                        dt / (rc + dt)
+                    },
+                    prev_input: (0.0, 0.0),
+                    prev_output: (0.0, 0.0),
+                }
+            })
+            .collect();
+
+        Self { stages }
+    }
+
+    pub fn apply(&mut self, sample: (f32, f32)) -> (f32, f32) {
+        self.stages
+            .iter_mut()
+            .fold(sample, |input, stage| stage.apply(input))
+    }
+}
+
+impl FilterStage {
+    fn apply(&mut self, input: (f32, f32)) -> (f32, f32) {
+        let filter = |input: f32, prev_input: f32, prev_output: f32| {
+            if self.high_pass {
+                self.coefficient * (prev_output + input - prev_input)
+            } else {
+                prev_output + self.coefficient * (input - prev_output)
+            }
+        };
+
+        let output = (
+            filter(input.0, self.prev_input.0, self.prev_output.0),
+            filter(input.1, self.prev_input.1, self.prev_output.1),
+        );
+
+        self.prev_input = input;
+        self.prev_output = output;
+
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_passes_through() {
+        let mut filter = OutputFilter::new(AudioFilter::Raw, 44100);
+        assert_eq!(filter.apply((0.25, -0.5)), (0.25, -0.5));
+    }
+
+    #[test]
+    fn high_pass_removes_dc_offset() {
+        for model in [AudioFilter::Nes, AudioFilter::Dmg, AudioFilter::Snes] {
+            let mut filter = OutputFilter::new(model, 32000);
+            let mut output = (0.0, 0.0);
+
+            for _ in 0..32000 {
+                output = filter.apply((-0.5, 0.5));
+            }
+
+            assert!(
+                output.0.abs() < 0.001 && output.1.abs() < 0.001,
+                "{:?}",
+                model
+            );
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::util::state::{Snapshot, State};
+
 pub struct Sequencer<const SIZE: usize> {
     sequence: &'static [u8; SIZE],
     index: usize,
@@ -20,6 +22,13 @@ impl<const SIZE: usize> Sequencer<SIZE> {
         self.index = 0;
     }
 
+    // The current sequence is saved as its position in the given list
+    pub fn snapshot(&mut self, state: &mut State, sequences: &'static [[u8; SIZE]]) {
+        let sequences: Vec<&'static [u8; SIZE]> = sequences.iter().collect();
+        state.variant(&mut self.sequence, &sequences);
+        self.index.snapshot(state);
+    }
+
     pub fn step(&mut self) {
         self.index += 1;
 
@@ -0,0 +1,252 @@
+use serde::{Deserialize, Serialize};
+use std::array;
+use std::f32::consts::PI;
+
+// Signal samples per cycle of the colour subcarrier. Both the NES and SNES have a master clock of
+// six times the subcarrier frequency, so this is enough to represent every phase they can output
+// (one sample per half master clock).
+pub const PHASE_COUNT: usize = 12;
+
+// Each output pixel covers this many signal samples
+const SAMPLES_PER_OUTPUT_PIXEL: usize = 4;
+
+// The signal for each scanline starts this many phases after the previous one (as a scanline is
+// 1364 master clocks long)
+const LINE_PHASE_STEP: usize = 4;
+
+// Decoding window for chroma (and composite luma), in samples. This is a full subcarrier cycle.
+const CHROMA_WINDOW: usize = PHASE_COUNT;
+
+// Decoding window for S-Video luma, which is not band-limited by the subcarrier
+const LUMA_WINDOW: usize = 4;
+
+const PADDING: usize = CHROMA_WINDOW / 2;
+
+// Simulation of an NTSC video signal, in the style of blargg's nes_ntsc and snes_ntsc
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NtscFilter {
+    #[default]
+    None,
+    // Luma and chroma share one signal, giving colour fringing, artifacts and dot crawl
+    Composite,
+    // Luma and chroma are separate, so only colour bleed remains
+    SVideo,
+    // Each pixel is decoded on its own, giving the palette an NTSC decoder would produce, but
+    // with no signal artifacts
+    Rgb,
+}
+
+// Converts signals into RGB output. The encoding side is up to each system: 'encode' returns the
+// luma and chroma levels for a pixel at a given subcarrier phase, where luma is 0.0 for black
+// and 1.0 for white.
+pub struct NtscDecoder {
+    filter: NtscFilter,
+    frame_phase: usize,
+    signal: Vec<Sample>,
+    carrier: [(f32, f32); PHASE_COUNT],
+}
+
+#[derive(Copy, Clone)]
+struct Sample {
+    phase: usize,
+    luma: f32,
+    chroma: f32,
+}
+
+impl NtscDecoder {
+    pub fn new(filter: NtscFilter) -> Self {
+        Self {
+            filter,
+            frame_phase: 0,
+            signal: Vec::new(),
+            carrier: array::from_fn(|phase| {
+                let angle = 2.0 * PI * phase as f32 / PHASE_COUNT as f32;
+                (angle.cos(), angle.sin())
+            }),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.filter != NtscFilter::None
+    }
+
+    // Width of the decoded output for a line of the given number of pixels
+    pub fn output_width(width: usize, samples_per_pixel: usize) -> usize {
+        width * samples_per_pixel / SAMPLES_PER_OUTPUT_PIXEL
+    }
+
+    // Advances the subcarrier phase, as successive frames do not start on the same phase
+    pub fn next_frame(&mut self) {
+        self.frame_phase = (self.frame_phase + LINE_PHASE_STEP) % PHASE_COUNT;
+    }
+
+    // Only to be called when the filter is enabled
+    pub fn decode_line<T: Copy + PartialEq>(
+        &mut self,
+        line: usize,
+        pixels: &[T],
+        samples_per_pixel: usize,
+        encode: impl Fn(T, usize) -> (f32, f32),
+        output: &mut [u8],
+    ) {
+        let line_phase = (self.frame_phase + line * LINE_PHASE_STEP) % PHASE_COUNT;
+        let sample_count = pixels.len() * samples_per_pixel;
+
+        // Pixels beyond either edge of the line are padded out by the edge pixels
+        self.signal.clear();
+
+        self.signal
+            .extend((0..(sample_count + PADDING * 2)).map(|index| {
+                let pixel =
+                    (index.saturating_sub(PADDING) / samples_per_pixel).min(pixels.len() - 1);
+                let phase = (line_phase + index + PHASE_COUNT - PADDING) % PHASE_COUNT;
+                let (luma, chroma) = encode(pixels[pixel], phase);
+                Sample {
+                    phase,
+                    luma,
+                    chroma,
+                }
+            }));
+
+        let mut cached: Option<(T, [f32; 3])> = None;
+
+        for (index, output) in output
+            .chunks_exact_mut(4)
+            .take(sample_count / SAMPLES_PER_OUTPUT_PIXEL)
+            .enumerate()
+        {
+            // Index of the centre sample of this output pixel (in the padded signal)
+            let centre = PADDING + index * SAMPLES_PER_OUTPUT_PIXEL + SAMPLES_PER_OUTPUT_PIXEL / 2;
+
+            let yiq = match self.filter {
+                NtscFilter::None => unreachable!(),
+                NtscFilter::Composite => {
+                    let window = self.window(centre, CHROMA_WINDOW);
+                    let signal = |sample: &Sample| sample.luma + sample.chroma;
+                    let (i, q) =
+                        self.demodulate(window.iter().map(|sample| (sample.phase, signal(sample))));
+                    [average(window.iter().map(signal)), i, q]
+                }
+                NtscFilter::SVideo => {
+                    let luma = self
+                        .window(centre, LUMA_WINDOW)
+                        .iter()
+                        .map(|sample| sample.luma);
+                    let chroma = self.window(centre, CHROMA_WINDOW).iter();
+                    let (i, q) =
+                        self.demodulate(chroma.map(|sample| (sample.phase, sample.chroma)));
+                    [average(luma), i, q]
+                }
+                NtscFilter::Rgb => {
+                    let pixel = pixels[(centre - PADDING) / samples_per_pixel];
+
+                    match cached {
+                        Some((cached_pixel, yiq)) if cached_pixel == pixel => yiq,
+                        _ => {
+                            let chroma =
+                                (0..PHASE_COUNT).map(|phase| (phase, encode(pixel, phase).1));
+                            let (i, q) = self.demodulate(chroma);
+                            let yiq = [encode(pixel, 0).0, i, q];
+                            cached = Some((pixel, yiq));
+                            yiq
+                        }
+                    }
+                }
+            };
+
+            let [red, green, blue] = yiq_to_rgb(yiq);
+            output[0] = red;
+            output[1] = green;
+            output[2] = blue;
+            output[3] = 0xff;
+        }
+    }
+
+    fn window(&self, centre: usize, size: usize) -> &[Sample] {
+        let start = centre - size / 2;
+        &self.signal[start..(start + size)]
+    }
+
+    // Recovers I and Q from a whole number of subcarrier cycles
+    fn demodulate(&self, samples: impl Iterator<Item = (usize, f32)>) -> (f32, f32) {
+        let (mut i, mut q, mut count) = (0.0, 0.0, 0);
+
+        for (phase, level) in samples {
+            let (cos, sin) = self.carrier[phase];
+            i += level * cos;
+            q += level * sin;
+            count += 1;
+        }
+
+        (2.0 * i / count as f32, 2.0 * q / count as f32)
+    }
+}
+
+fn average(values: impl Iterator<Item = f32>) -> f32 {
+    let (sum, count) = values.fold((0.0, 0), |(sum, count), value| (sum + value, count + 1));
+    sum / count as f32
+}
+
+// Encodes an 8-bit RGB colour as luma and chroma levels at the given phase
+pub fn encode_rgb([red, green, blue]: [u8; 3], phase: usize) -> (f32, f32) {
+    let [red, green, blue] = [red, green, blue].map(|value| value as f32 / 255.0);
+    let y = 0.299 * red + 0.587 * green + 0.114 * blue;
+    let i = 0.596 * red - 0.274 * green - 0.322 * blue;
+    let q = 0.211 * red - 0.523 * green + 0.312 * blue;
+    let angle = 2.0 * PI * phase as f32 / PHASE_COUNT as f32;
+    (y, i * angle.cos() + q * angle.sin())
+}
+
+fn yiq_to_rgb([y, i, q]: [f32; 3]) -> [u8; 3] {
+    [
+        y + 0.956 * i + 0.621 * q,
+        y - 0.272 * i - 0.647 * q,
+        y - 1.106 * i + 1.703 * q,
+    ]
+    .map(|value| (value * 255.0).round().clamp(0.0, 255.0) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTERS: [NtscFilter; 3] = [NtscFilter::Composite, NtscFilter::SVideo, NtscFilter::Rgb];
+
+    fn decode(filter: NtscFilter, pixels: &[[u8; 3]]) -> Vec<u8> {
+        let mut decoder = NtscDecoder::new(filter);
+        let mut output = vec![0; NtscDecoder::output_width(pixels.len(), 8) * 4];
+        decoder.decode_line(0, pixels, 8, encode_rgb, &mut output);
+        output
+    }
+
+    #[test]
+    fn flat_colours_are_preserved() {
+        for filter in FILTERS {
+            let output = decode(filter, &[[200, 100, 50]; 16]);
+
+            for pixel in output.chunks_exact(4) {
+                assert!(pixel[0].abs_diff(200) <= 2, "{:?}: {:?}", filter, pixel);
+                assert!(pixel[1].abs_diff(100) <= 2, "{:?}: {:?}", filter, pixel);
+                assert!(pixel[2].abs_diff(50) <= 2, "{:?}: {:?}", filter, pixel);
+            }
+        }
+    }
+
+    #[test]
+    fn only_composite_has_colour_fringing() {
+        let pixels: Vec<[u8; 3]> = (0..16)
+            .map(|index| if index < 8 { [0; 3] } else { [255; 3] })
+            .collect();
+
+        let has_colour = |output: &[u8]| {
+            output
+                .chunks_exact(4)
+                .any(|pixel| pixel[0].abs_diff(pixel[1]) > 8 || pixel[1].abs_diff(pixel[2]) > 8)
+        };
+
+        assert!(has_colour(&decode(NtscFilter::Composite, &pixels)));
+        assert!(!has_colour(&decode(NtscFilter::SVideo, &pixels)));
+        assert!(!has_colour(&decode(NtscFilter::Rgb, &pixels)));
+    }
+}
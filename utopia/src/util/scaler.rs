@@ -0,0 +1,360 @@
+use super::size::Size;
+use serde::{Deserialize, Serialize};
+
+mod hqx;
+mod scalex;
+mod xbr;
+
+// CPU-side pixel art scalers, applied to RGBA frames before they are uploaded (or saved).
+// These are entirely deterministic, so the same frame always scales to the same output.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scaler {
+    #[default]
+    None,
+    Scale2x,
+    Scale3x,
+    Hq2x,
+    Hq3x,
+    Hq4x,
+    Xbr,
+}
+
+impl Scaler {
+    pub fn scale_factor(self) -> u32 {
+        match self {
+            Self::None => 1,
+            Self::Scale2x | Self::Hq2x | Self::Xbr => 2,
+            Self::Scale3x | Self::Hq3x => 3,
+            Self::Hq4x => 4,
+        }
+    }
+
+    pub fn output_size(self, size: Size) -> Size {
+        let factor = self.scale_factor();
+        Size::new(size.width * factor, size.height * factor)
+    }
+}
+
+// Applies a scaler to each frame in turn. The YUV conversion of the source image is kept
+// between frames, so that no memory needs to be allocated once the frame size is settled.
+pub struct FrameScaler {
+    scaler: Scaler,
+    yuv: Vec<[i32; 3]>,
+}
+
+impl FrameScaler {
+    pub fn new(scaler: Scaler) -> Self {
+        Self {
+            scaler,
+            yuv: Vec::new(),
+        }
+    }
+
+    pub fn output_size(&self, size: Size) -> Size {
+        self.scaler.output_size(size)
+    }
+
+    // Scales an RGBA image, replacing the contents of 'output'
+    pub fn apply(&mut self, pixels: &[u8], size: Size, output: &mut Vec<u8>) {
+        let scaler = self.scaler;
+
+        if scaler == Scaler::None {
+            output.clear();
+            output.extend_from_slice(pixels);
+            return;
+        }
+
+        self.yuv.clear();
+
+        self.yuv.extend(
+            pixels
+                .chunks_exact(4)
+                .map(|pixel| to_yuv(u32::from_le_bytes(pixel.try_into().unwrap()))),
+        );
+
+        let image = Image {
+            pixels,
+            yuv: &self.yuv,
+            width: size.width as usize,
+            height: size.height as usize,
+        };
+
+        let factor = scaler.scale_factor() as usize;
+        let pitch = image.width * factor * 4;
+        output.resize(pitch * image.height * factor, 0);
+
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let block = match scaler {
+                    Scaler::None => unreachable!(),
+                    Scaler::Scale2x => scalex::scale2x(&image, x, y),
+                    Scaler::Scale3x => scalex::scale3x(&image, x, y),
+                    Scaler::Hq2x => hqx::hq2x(&image, x, y),
+                    Scaler::Hq3x => hqx::hq3x(&image, x, y),
+                    Scaler::Hq4x => hqx::hq4x(&image, x, y),
+                    Scaler::Xbr => xbr::xbr2x(&image, x, y),
+                };
+
+                for (sub_y, row) in block[..(factor * factor)].chunks_exact(factor).enumerate() {
+                    let start = (y * factor + sub_y) * pitch + x * factor * 4;
+                    let line = &mut output[start..(start + factor * 4)];
+
+                    for (target, pixel) in line.chunks_exact_mut(4).zip(row) {
+                        target.copy_from_slice(&pixel.to_le_bytes());
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Each scaler returns an NxN block of output pixels (row by row) for every source pixel
+type Block = [u32; 16];
+
+struct Image<'a> {
+    pixels: &'a [u8],
+    yuv: &'a [[i32; 3]],
+    width: usize,
+    height: usize,
+}
+
+impl Image<'_> {
+    // Pixels beyond the edge of the image repeat the nearest edge pixel
+    fn index(&self, x: usize, y: usize, dx: isize, dy: isize) -> usize {
+        let x = x.saturating_add_signed(dx).min(self.width - 1);
+        let y = y.saturating_add_signed(dy).min(self.height - 1);
+        y * self.width + x
+    }
+
+    fn get(&self, x: usize, y: usize, dx: isize, dy: isize) -> u32 {
+        let index = self.index(x, y, dx, dy) * 4;
+        u32::from_le_bytes(self.pixels[index..(index + 4)].try_into().unwrap())
+    }
+
+    fn yuv(&self, x: usize, y: usize, dx: isize, dy: isize) -> [i32; 3] {
+        self.yuv[self.index(x, y, dx, dy)]
+    }
+}
+
+// The same conversion as hqx, so that its thresholds apply as intended
+fn to_yuv(pixel: u32) -> [i32; 3] {
+    let [r, g, b, _] = pixel.to_le_bytes().map(i32::from);
+    let y = (r * 299 + g * 587 + b * 114) / 1000;
+    let u = (-r * 169 - g * 331 + b * 500) / 1000 + 128;
+    let v = (r * 500 - g * 419 - b * 81) / 1000 + 128;
+    [y, u, v]
+}
+
+// The colour similarity thresholds used by hqx
+fn similar(a: [i32; 3], b: [i32; 3]) -> bool {
+    (a[0] - b[0]).abs() <= 48 && (a[1] - b[1]).abs() <= 7 && (a[2] - b[2]).abs() <= 6
+}
+
+// Mixes colours (per channel, including alpha) using integer weights
+fn mix(colors: &[(u32, u32)]) -> u32 {
+    let total: u32 = colors.iter().map(|(_, weight)| weight).sum();
+
+    let channels: [u8; 4] = std::array::from_fn(|channel| {
+        let sum: u32 = colors
+            .iter()
+            .map(|&(color, weight)| ((color >> (channel * 8)) & 0xff) * weight)
+            .sum();
+
+        ((sum + total / 2) / total) as u8
+    });
+
+    u32::from_le_bytes(channels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCALERS: [Scaler; 7] = [
+        Scaler::None,
+        Scaler::Scale2x,
+        Scaler::Scale3x,
+        Scaler::Hq2x,
+        Scaler::Hq3x,
+        Scaler::Hq4x,
+        Scaler::Xbr,
+    ];
+
+    fn image(rows: &[&str]) -> (Vec<u8>, Size) {
+        let pixels = rows
+            .iter()
+            .flat_map(|row| row.chars())
+            .flat_map(|ch| match ch {
+                '#' => [0x00, 0x00, 0x00, 0xff],
+                _ => [0xff, 0xff, 0xff, 0xff],
+            })
+            .collect();
+
+        (pixels, Size::new(rows[0].len() as u32, rows.len() as u32))
+    }
+
+    #[test]
+    fn flat_images_are_unchanged() {
+        let pixels: Vec<u8> = [0x12, 0x34, 0x56, 0xff].repeat(12);
+        let size = Size::new(4, 3);
+
+        for scaler in SCALERS {
+            let mut output = Vec::new();
+            FrameScaler::new(scaler).apply(&pixels, size, &mut output);
+
+            let output_size = scaler.output_size(size);
+            assert_eq!(
+                output.len(),
+                (output_size.width * output_size.height * 4) as usize
+            );
+            assert_eq!(output, [0x12, 0x34, 0x56, 0xff].repeat(output.len() / 4));
+        }
+    }
+
+    #[test]
+    fn scale2x_rounds_corners() {
+        let (pixels, size) = image(&["...", ".##", ".#."]);
+        let mut output = Vec::new();
+        FrameScaler::new(Scaler::Scale2x).apply(&pixels, size, &mut output);
+
+        let pixel = |x: usize, y: usize| output[(y * 6 + x) * 4];
+        assert_eq!(pixel(2, 2), 0xff);
+        assert_eq!(pixel(3, 2), 0x00);
+        assert_eq!(pixel(2, 3), 0x00);
+        assert_eq!(pixel(3, 3), 0x00);
+    }
+
+    #[test]
+    fn scalers_are_deterministic() {
+        let (pixels, size) = image(&["#..#.", ".##..", "#.#.#", "..##."]);
+
+        for scaler in SCALERS {
+            let mut frame_scaler = FrameScaler::new(scaler);
+            let mut first = Vec::new();
+            let mut second = Vec::new();
+            frame_scaler.apply(&pixels, size, &mut first);
+            frame_scaler.apply(&pixels, size, &mut second);
+            assert_eq!(first, second);
+        }
+    }
+
+    // Red channel of each output pixel, row by row
+    fn scale(scaler: Scaler, rows: &[&str]) -> Vec<Vec<u8>> {
+        let (pixels, size) = image(rows);
+        let mut output = Vec::new();
+        FrameScaler::new(scaler).apply(&pixels, size, &mut output);
+
+        let width = scaler.output_size(size).width as usize;
+
+        output
+            .chunks_exact(width * 4)
+            .map(|row| row.chunks_exact(4).map(|pixel| pixel[0]).collect())
+            .collect()
+    }
+
+    #[test]
+    fn straight_edges_stay_sharp() {
+        let rows = ["....", "....", "####", "####"];
+
+        for scaler in SCALERS {
+            let factor = scaler.scale_factor() as usize;
+            let output = scale(scaler, &rows);
+
+            for (y, row) in output.iter().enumerate() {
+                let expected = if y < 2 * factor { 0xff } else { 0x00 };
+                assert_eq!(*row, vec![expected; 4 * factor], "{:?}", scaler);
+            }
+        }
+    }
+
+    #[test]
+    fn hq2x_diagonal() {
+        assert_eq!(
+            scale(Scaler::Hq2x, &["....", "#...", "##..", "###."]),
+            [
+                [255, 255, 255, 255, 255, 255, 255, 255],
+                [255, 255, 255, 255, 255, 255, 255, 255],
+                [63, 191, 255, 255, 255, 255, 255, 255],
+                [0, 0, 127, 255, 255, 255, 255, 255],
+                [0, 0, 0, 127, 255, 255, 255, 255],
+                [0, 0, 0, 0, 127, 255, 255, 255],
+                [0, 0, 0, 0, 0, 191, 255, 255],
+                [0, 0, 0, 0, 0, 63, 255, 255],
+            ]
+        );
+    }
+
+    #[test]
+    fn hq3x_diagonal() {
+        let output = scale(Scaler::Hq3x, &["....", "#...", "##..", "###."]);
+
+        assert_eq!(
+            output[3..9],
+            [
+                [63, 191, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+                [0, 0, 63, 223, 255, 255, 255, 255, 255, 255, 255, 255],
+                [0, 0, 0, 31, 223, 255, 255, 255, 255, 255, 255, 255],
+                [0, 0, 0, 0, 31, 223, 255, 255, 255, 255, 255, 255],
+                [0, 0, 0, 0, 0, 31, 223, 255, 255, 255, 255, 255],
+                [0, 0, 0, 0, 0, 0, 31, 223, 255, 255, 255, 255],
+            ]
+        );
+    }
+
+    #[test]
+    fn hqx_isolated_pixel() {
+        // Only the block for the centre pixel changes, with its corners rounded off
+        let expected: [&[&[u8]]; 3] = [
+            &[&[31, 31], &[31, 31]],
+            &[&[127, 0, 127], &[0, 0, 0], &[127, 0, 127]],
+            &[
+                &[127, 0, 0, 127],
+                &[0, 0, 0, 0],
+                &[0, 0, 0, 0],
+                &[127, 0, 0, 127],
+            ],
+        ];
+
+        for (scaler, expected) in [Scaler::Hq2x, Scaler::Hq3x, Scaler::Hq4x]
+            .into_iter()
+            .zip(expected)
+        {
+            let factor = scaler.scale_factor() as usize;
+            let output = scale(scaler, &["...", ".#.", "..."]);
+
+            for (y, row) in output.iter().enumerate() {
+                for (x, &pixel) in row.iter().enumerate() {
+                    let centre =
+                        (factor..(2 * factor)).contains(&x) && (factor..(2 * factor)).contains(&y);
+
+                    let expected = if centre {
+                        expected[y - factor][x - factor]
+                    } else {
+                        0xff
+                    };
+
+                    assert_eq!(pixel, expected, "{:?} ({}, {})", scaler, x, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn xbr_diagonal() {
+        // The staircase becomes a straight line, blended half way across
+        assert_eq!(
+            scale(Scaler::Xbr, &["....", "#...", "##..", "###."]),
+            [
+                [255, 255, 255, 255, 255, 255, 255, 255],
+                [255, 255, 255, 255, 255, 255, 255, 255],
+                [64, 191, 255, 255, 255, 255, 255, 255],
+                [0, 0, 128, 255, 255, 255, 255, 255],
+                [0, 0, 0, 128, 255, 255, 255, 255],
+                [0, 0, 0, 0, 128, 255, 255, 255],
+                [0, 0, 0, 0, 0, 191, 255, 255],
+                [0, 0, 0, 0, 0, 64, 255, 255],
+            ]
+        );
+    }
+}
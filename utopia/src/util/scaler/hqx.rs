@@ -0,0 +1,468 @@
+use super::{similar, Block, Image};
+
+// hq2x, hq3x and hq4x, by Maxim Stepin. Each source pixel is compared with its eight neighbours
+// in YUV space, and the resulting pattern decides how each output pixel is interpolated. Rather
+// than the original 256-case tables, the rules are written as masked patterns for the top left
+// of the output block (as in FFmpeg's vf_hqx), and the other corners are produced by rotating
+// the neighbourhood a quarter turn at a time.
+pub fn hq2x(image: &Image, x: usize, y: usize) -> Block {
+    let mut block = [0; 16];
+    let mut window = Window::new(image, x, y);
+
+    for rotation in 0..4 {
+        block[position(2, rotation, 0, 0)] = hq2x_corner(&window);
+        window = window.rotate();
+    }
+
+    block
+}
+
+pub fn hq3x(image: &Image, x: usize, y: usize) -> Block {
+    let mut block = [0; 16];
+    let mut window = Window::new(image, x, y);
+
+    for rotation in 0..4 {
+        block[position(3, rotation, 0, 0)] = hq3x_corner(&window);
+        block[position(3, rotation, 1, 0)] = hq3x_edge(&window);
+        window = window.rotate();
+    }
+
+    block[4] = window.w[4];
+    block
+}
+
+pub fn hq4x(image: &Image, x: usize, y: usize) -> Block {
+    let mut block = [0; 16];
+    let mut window = Window::new(image, x, y);
+
+    for rotation in 0..4 {
+        let quadrant = hq4x_quadrant(&window);
+
+        for (index, pixel) in quadrant.into_iter().enumerate() {
+            block[position(4, rotation, index & 1, index >> 1)] = pixel;
+        }
+
+        window = window.rotate();
+    }
+
+    block
+}
+
+// Index within the output block of a pixel given relative to a rotated window
+fn position(factor: usize, rotation: usize, x: usize, y: usize) -> usize {
+    let (mut x, mut y) = (x, y);
+
+    for _ in 0..rotation {
+        (x, y) = (factor - 1 - y, x);
+    }
+
+    y * factor + x
+}
+
+// Masked patterns shared between the scalers. Bits are set for each neighbour that differs from
+// the centre, in the order 0, 1, 2, 3, 5, 6, 7, 8 (the centre being pixel 4).
+type Patterns = [(u8, u8)];
+
+const EDGE_TOP: &Patterns = &[(0xbf, 0x37), (0xdb, 0x13)];
+const EDGE_LEFT: &Patterns = &[(0xdb, 0x49), (0xef, 0x6d)];
+const SHARP: &Patterns = &[(0x0b, 0x0b), (0xfe, 0x4a), (0xfe, 0x1a)];
+
+const CORNER: &Patterns = &[
+    (0x6f, 0x2a),
+    (0x5b, 0x0a),
+    (0xbf, 0x3a),
+    (0xdf, 0x5a),
+    (0x9f, 0x8a),
+    (0xcf, 0x8a),
+    (0xef, 0x4e),
+    (0x3f, 0x0e),
+    (0xfb, 0x5a),
+    (0xbb, 0x8a),
+    (0x7f, 0x5a),
+    (0xaf, 0x8a),
+    (0xeb, 0x8a),
+];
+
+const LINE_TOP: &Patterns = &[(0x1b, 0x03), (0x4f, 0x43), (0x8b, 0x83), (0x6b, 0x43)];
+const LINE_LEFT: &Patterns = &[(0x4b, 0x09), (0x8b, 0x89), (0x1f, 0x19), (0x3b, 0x19)];
+
+const CONCAVE: &Patterns = &[
+    (0x0b, 0x08),
+    (0xf9, 0x68),
+    (0xf3, 0x62),
+    (0x6d, 0x6c),
+    (0x67, 0x66),
+    (0x3d, 0x3c),
+    (0x37, 0x36),
+    (0xf9, 0xf8),
+    (0xdd, 0xdc),
+    (0xf3, 0xf2),
+    (0xd7, 0xd6),
+    (0xdd, 0x1c),
+    (0xd7, 0x16),
+    (0x0b, 0x02),
+];
+
+const DIAGONAL_TOP: &Patterns = &[(0x7e, 0x2a), (0xef, 0xab)];
+const DIAGONAL_LEFT: &Patterns = &[(0xbf, 0x8f), (0x7e, 0x0e)];
+
+const ROUND: &Patterns = &[
+    (0x4f, 0x4b),
+    (0x9f, 0x1b),
+    (0x2f, 0x0b),
+    (0xbe, 0x0a),
+    (0xee, 0x0a),
+    (0x7e, 0x0a),
+    (0xeb, 0x4b),
+    (0x3b, 0x1b),
+];
+
+const FLAT: &Patterns = &[(0x0a, 0x00)];
+
+fn hq2x_corner(window: &Window) -> u32 {
+    let w = &window.w;
+
+    if window.matches(EDGE_TOP) && window.differ(1, 5) {
+        interpolate(&[(w[4], 3), (w[3], 1)])
+    } else if window.matches(EDGE_LEFT) && window.differ(7, 3) {
+        interpolate(&[(w[4], 3), (w[1], 1)])
+    } else if window.matches(SHARP) && window.differ(3, 1) {
+        w[4]
+    } else if window.matches(CORNER) && window.differ(3, 1) {
+        interpolate(&[(w[4], 3), (w[0], 1)])
+    } else if window.matches(&[(0x0b, 0x08)]) {
+        interpolate(&[(w[4], 2), (w[0], 1), (w[1], 1)])
+    } else if window.matches(&[(0x0b, 0x02)]) {
+        interpolate(&[(w[4], 2), (w[0], 1), (w[3], 1)])
+    } else if window.matches(&[(0x2f, 0x2f)]) {
+        interpolate(&[(w[4], 14), (w[3], 1), (w[1], 1)])
+    } else if window.matches(EDGE_TOP) {
+        interpolate(&[(w[4], 5), (w[1], 2), (w[3], 1)])
+    } else if window.matches(EDGE_LEFT) {
+        interpolate(&[(w[4], 5), (w[3], 2), (w[1], 1)])
+    } else if window.matches(LINE_TOP) {
+        interpolate(&[(w[4], 3), (w[3], 1)])
+    } else if window.matches(LINE_LEFT) {
+        interpolate(&[(w[4], 3), (w[1], 1)])
+    } else if window.matches(DIAGONAL_TOP) || window.matches(DIAGONAL_LEFT) {
+        interpolate(&[(w[4], 2), (w[3], 3), (w[1], 3)])
+    } else if window.matches(&[
+        (0xfb, 0x6a),
+        (0x6f, 0x6e),
+        (0x3f, 0x3e),
+        (0xfb, 0xfa),
+        (0xdf, 0xde),
+        (0xdf, 0x1e),
+    ]) {
+        interpolate(&[(w[4], 3), (w[0], 1)])
+    } else if window.matches(FLAT) || window.matches(ROUND) {
+        interpolate(&[(w[4], 2), (w[3], 1), (w[1], 1)])
+    } else {
+        interpolate(&[(w[4], 6), (w[3], 1), (w[1], 1)])
+    }
+}
+
+fn hq3x_corner(window: &Window) -> u32 {
+    let w = &window.w;
+
+    if window.matches(EDGE_TOP) && window.differ(1, 5) {
+        interpolate(&[(w[4], 3), (w[3], 1)])
+    } else if window.matches(EDGE_LEFT) && window.differ(7, 3) {
+        interpolate(&[(w[4], 3), (w[1], 1)])
+    } else if window.matches(SHARP) && window.differ(3, 1) {
+        w[4]
+    } else if window.matches(CORNER) && window.differ(3, 1) {
+        interpolate(&[(w[4], 3), (w[0], 1)])
+    } else if window.matches(LINE_TOP) {
+        interpolate(&[(w[4], 3), (w[3], 1)])
+    } else if window.matches(LINE_LEFT) {
+        interpolate(&[(w[4], 3), (w[1], 1)])
+    } else if window.matches(DIAGONAL_TOP) || window.matches(DIAGONAL_LEFT) {
+        interpolate(&[(w[3], 1), (w[1], 1)])
+    } else if window.matches(ROUND) {
+        interpolate(&[(w[4], 2), (w[3], 7), (w[1], 7)])
+    } else if window.matches(CONCAVE) {
+        interpolate(&[(w[4], 3), (w[0], 1)])
+    } else {
+        interpolate(&[(w[4], 2), (w[3], 1), (w[1], 1)])
+    }
+}
+
+// The pixel between the top left and top right corners
+fn hq3x_edge(window: &Window) -> u32 {
+    let w = &window.w;
+
+    let sharp_right = window.matches(&[
+        (0xfe, 0xde),
+        (0x9e, 0x16),
+        (0xda, 0x12),
+        (0x17, 0x16),
+        (0x5b, 0x12),
+        (0xbb, 0x12),
+    ]) && window.differ(1, 5);
+
+    let sharp_left = window.matches(&[
+        (0x0f, 0x0b),
+        (0x5e, 0x0a),
+        (0xfb, 0x7b),
+        (0x3b, 0x0b),
+        (0xbe, 0x0a),
+        (0x7a, 0x0a),
+    ]) && window.differ(3, 1);
+
+    if sharp_right || sharp_left {
+        w[4]
+    } else if window.matches(DIAGONAL_LEFT) || window.matches(EDGE_TOP) {
+        interpolate(&[(w[1], 3), (w[4], 1)])
+    } else if window.matches(&[
+        (0x02, 0x00),
+        (0x7c, 0x28),
+        (0xed, 0xa9),
+        (0xf5, 0xb4),
+        (0xd9, 0x90),
+    ]) {
+        interpolate(&[(w[4], 3), (w[1], 1)])
+    } else if window.matches(&[
+        (0x4f, 0x4b),
+        (0xfb, 0x7b),
+        (0xfe, 0x7e),
+        (0x9f, 0x1b),
+        (0x2f, 0x0b),
+        (0xbe, 0x0a),
+        (0x7e, 0x0a),
+        (0xfb, 0x4b),
+        (0xfb, 0xdb),
+        (0xfe, 0xde),
+        (0xfe, 0x56),
+        (0x57, 0x56),
+        (0x97, 0x16),
+        (0x3f, 0x1e),
+        (0xdb, 0x12),
+        (0xbb, 0x12),
+    ]) {
+        interpolate(&[(w[4], 7), (w[1], 1)])
+    } else {
+        w[4]
+    }
+}
+
+// The top left 2x2 pixels of the output block, row by row
+fn hq4x_quadrant(window: &Window) -> [u32; 4] {
+    let w = &window.w;
+
+    let edge_top = window.matches(EDGE_TOP);
+    let edge_left = window.matches(EDGE_LEFT);
+    let sharp_top = edge_top && window.differ(1, 5);
+    let sharp_left = edge_left && window.differ(7, 3);
+    let corner = window.matches(CORNER) && window.differ(3, 1);
+    let line_top = window.matches(LINE_TOP);
+    let line_left = window.matches(LINE_LEFT);
+    let concave = window.matches(CONCAVE);
+    let diagonal_top = window.matches(DIAGONAL_TOP);
+    let diagonal_left = window.matches(DIAGONAL_LEFT);
+    let round = window.matches(ROUND);
+    let flat = window.matches(FLAT);
+    let inside = window.matches(&[(0x0f, 0x0b), (0x2b, 0x0b), (0xfe, 0x4a), (0xfe, 0x1a)])
+        && window.differ(3, 1);
+    let solid = window.matches(&[(0x2f, 0x2f)]);
+    let end_top = window.matches(&[(0x0b, 0x03)]);
+    let end_left = window.matches(&[(0x0b, 0x09)]);
+
+    let top_left = if sharp_top {
+        interpolate(&[(w[4], 5), (w[3], 3)])
+    } else if sharp_left {
+        interpolate(&[(w[4], 5), (w[1], 3)])
+    } else if window.matches(SHARP) && window.differ(3, 1) {
+        w[4]
+    } else if corner {
+        interpolate(&[(w[4], 5), (w[0], 3)])
+    } else if edge_left {
+        interpolate(&[(w[4], 3), (w[3], 1)])
+    } else if edge_top {
+        interpolate(&[(w[4], 3), (w[1], 1)])
+    } else if line_top {
+        interpolate(&[(w[4], 5), (w[3], 3)])
+    } else if line_left {
+        interpolate(&[(w[4], 5), (w[1], 3)])
+    } else if window.matches(&[
+        (0x0f, 0x0b),
+        (0x5e, 0x0a),
+        (0x2b, 0x0b),
+        (0xbe, 0x0a),
+        (0x7a, 0x0a),
+        (0xee, 0x0a),
+    ]) {
+        interpolate(&[(w[1], 1), (w[3], 1)])
+    } else if concave {
+        interpolate(&[(w[4], 5), (w[0], 3)])
+    } else {
+        interpolate(&[(w[4], 2), (w[1], 1), (w[3], 1)])
+    };
+
+    let top_right = if sharp_top {
+        interpolate(&[(w[4], 7), (w[3], 1)])
+    } else if inside {
+        w[4]
+    } else if corner {
+        interpolate(&[(w[4], 3), (w[0], 1)])
+    } else if solid {
+        w[4]
+    } else if flat {
+        interpolate(&[(w[4], 5), (w[1], 2), (w[3], 1)])
+    } else if window.matches(&[(0x0b, 0x08)]) {
+        interpolate(&[(w[4], 5), (w[1], 2), (w[0], 1)])
+    } else if end_left {
+        interpolate(&[(w[4], 5), (w[1], 3)])
+    } else if edge_top {
+        interpolate(&[(w[1], 3), (w[4], 1)])
+    } else if diagonal_top {
+        interpolate(&[(w[1], 2), (w[4], 1), (w[3], 1)])
+    } else if diagonal_left {
+        interpolate(&[(w[1], 5), (w[3], 3)])
+    } else if line_top {
+        interpolate(&[(w[4], 7), (w[3], 1)])
+    } else if window.matches(&[
+        (0xf3, 0x62),
+        (0x67, 0x66),
+        (0x37, 0x36),
+        (0xf3, 0xf2),
+        (0xd7, 0xd6),
+        (0xd7, 0x16),
+        (0x0b, 0x02),
+    ]) {
+        interpolate(&[(w[4], 3), (w[0], 1)])
+    } else if round {
+        interpolate(&[(w[1], 1), (w[4], 1)])
+    } else {
+        interpolate(&[(w[4], 3), (w[1], 1)])
+    };
+
+    let bottom_left = if sharp_left {
+        interpolate(&[(w[4], 7), (w[1], 1)])
+    } else if inside {
+        w[4]
+    } else if corner {
+        interpolate(&[(w[4], 3), (w[0], 1)])
+    } else if solid {
+        w[4]
+    } else if flat {
+        interpolate(&[(w[4], 5), (w[3], 2), (w[1], 1)])
+    } else if window.matches(&[(0x0b, 0x02)]) {
+        interpolate(&[(w[4], 5), (w[3], 2), (w[0], 1)])
+    } else if end_top {
+        interpolate(&[(w[4], 5), (w[3], 3)])
+    } else if edge_left {
+        interpolate(&[(w[3], 3), (w[4], 1)])
+    } else if diagonal_left {
+        interpolate(&[(w[3], 2), (w[4], 1), (w[1], 1)])
+    } else if diagonal_top {
+        interpolate(&[(w[3], 5), (w[1], 3)])
+    } else if line_left {
+        interpolate(&[(w[4], 7), (w[1], 1)])
+    } else if window.matches(&[
+        (0x0b, 0x08),
+        (0xf9, 0x68),
+        (0x6d, 0x6c),
+        (0x3d, 0x3c),
+        (0xf9, 0xf8),
+        (0xdd, 0xdc),
+        (0xdd, 0x1c),
+    ]) {
+        interpolate(&[(w[4], 3), (w[0], 1)])
+    } else if round {
+        interpolate(&[(w[3], 1), (w[4], 1)])
+    } else {
+        interpolate(&[(w[4], 3), (w[3], 1)])
+    };
+
+    let bottom_right = if window.matches(&[(0x7f, 0x2b), (0xef, 0xab), (0xbf, 0x8f), (0x7f, 0x0f)])
+        && window.differ(3, 1)
+    {
+        w[4]
+    } else if corner {
+        interpolate(&[(w[4], 7), (w[0], 1)])
+    } else if end_top {
+        interpolate(&[(w[4], 7), (w[3], 1)])
+    } else if end_left {
+        interpolate(&[(w[4], 7), (w[1], 1)])
+    } else if flat || diagonal_top || diagonal_left {
+        interpolate(&[(w[4], 6), (w[3], 1), (w[1], 1)])
+    } else if concave {
+        interpolate(&[(w[4], 7), (w[0], 1)])
+    } else {
+        w[4]
+    };
+
+    [top_left, top_right, bottom_left, bottom_right]
+}
+
+// The 3x3 neighbourhood of a source pixel, row by row
+struct Window {
+    w: [u32; 9],
+    yuv: [[i32; 3]; 9],
+    pattern: u8,
+}
+
+impl Window {
+    fn new(image: &Image, x: usize, y: usize) -> Self {
+        let offset = |index: usize| (index as isize % 3 - 1, index as isize / 3 - 1);
+
+        Self::with_pixels(
+            std::array::from_fn(|index| {
+                let (dx, dy) = offset(index);
+                image.get(x, y, dx, dy)
+            }),
+            std::array::from_fn(|index| {
+                let (dx, dy) = offset(index);
+                image.yuv(x, y, dx, dy)
+            }),
+        )
+    }
+
+    fn with_pixels(w: [u32; 9], yuv: [[i32; 3]; 9]) -> Self {
+        let pattern = [0, 1, 2, 3, 5, 6, 7, 8]
+            .into_iter()
+            .enumerate()
+            .fold(0, |pattern, (bit, index)| {
+                pattern | ((!similar(yuv[4], yuv[index]) as u8) << bit)
+            });
+
+        Self { w, yuv, pattern }
+    }
+
+    // Turns the window a quarter turn anticlockwise, so the top right corner becomes the top left
+    fn rotate(&self) -> Self {
+        const SOURCE: [usize; 9] = [2, 5, 8, 1, 4, 7, 0, 3, 6];
+
+        Self::with_pixels(
+            SOURCE.map(|index| self.w[index]),
+            SOURCE.map(|index| self.yuv[index]),
+        )
+    }
+
+    fn matches(&self, patterns: &Patterns) -> bool {
+        patterns
+            .iter()
+            .any(|&(mask, pattern)| self.pattern & mask == pattern)
+    }
+
+    fn differ(&self, first: usize, second: usize) -> bool {
+        !similar(self.yuv[first], self.yuv[second])
+    }
+}
+
+// Weights always add up to a power of two, and the result is truncated, as in the original
+fn interpolate(colors: &[(u32, u32)]) -> u32 {
+    let total: u32 = colors.iter().map(|(_, weight)| weight).sum();
+
+    let channels: [u8; 4] = std::array::from_fn(|channel| {
+        let sum: u32 = colors
+            .iter()
+            .map(|&(color, weight)| ((color >> (channel * 8)) & 0xff) * weight)
+            .sum();
+
+        (sum / total) as u8
+    });
+
+    u32::from_le_bytes(channels)
+}
@@ -0,0 +1,88 @@
+use super::{Block, Image};
+
+// AdvMAME2x/3x (otherwise known as Scale2x/Scale3x). Output pixels are only ever copies of
+// source pixels, so no new colours are introduced.
+pub fn scale2x(image: &Image, x: usize, y: usize) -> Block {
+    let a = image.get(x, y, 0, -1);
+    let c = image.get(x, y, -1, 0);
+    let p = image.get(x, y, 0, 0);
+    let b = image.get(x, y, 1, 0);
+    let d = image.get(x, y, 0, 1);
+
+    let mut block = [p; 16];
+
+    if c == a && c != d && a != b {
+        block[0] = a;
+    }
+
+    if a == b && a != c && b != d {
+        block[1] = b;
+    }
+
+    if d == c && d != b && c != a {
+        block[2] = c;
+    }
+
+    if b == d && b != a && d != c {
+        block[3] = d;
+    }
+
+    block
+}
+
+pub fn scale3x(image: &Image, x: usize, y: usize) -> Block {
+    let a = image.get(x, y, -1, -1);
+    let b = image.get(x, y, 0, -1);
+    let c = image.get(x, y, 1, -1);
+    let d = image.get(x, y, -1, 0);
+    let e = image.get(x, y, 0, 0);
+    let f = image.get(x, y, 1, 0);
+    let g = image.get(x, y, -1, 1);
+    let h = image.get(x, y, 0, 1);
+    let i = image.get(x, y, 1, 1);
+
+    let mut block = [e; 16];
+
+    if b == h || d == f {
+        return block;
+    }
+
+    let top_left = d == b;
+    let top_right = b == f;
+    let bottom_left = d == h;
+    let bottom_right = h == f;
+
+    if top_left {
+        block[0] = d;
+    }
+
+    if (top_left && e != c) || (top_right && e != a) {
+        block[1] = b;
+    }
+
+    if top_right {
+        block[2] = f;
+    }
+
+    if (top_left && e != g) || (bottom_left && e != a) {
+        block[3] = d;
+    }
+
+    if (top_right && e != i) || (bottom_right && e != c) {
+        block[5] = f;
+    }
+
+    if bottom_left {
+        block[6] = d;
+    }
+
+    if (bottom_right && e != g) || (bottom_left && e != i) {
+        block[7] = h;
+    }
+
+    if bottom_right {
+        block[8] = f;
+    }
+
+    block
+}
@@ -0,0 +1,108 @@
+use super::{mix, similar, Block, Image};
+
+// Rotations that map the bottom-right corner onto each of the four corners in turn
+const ROTATIONS: [[isize; 4]; 4] = [[1, 0, 0, 1], [0, 1, -1, 0], [-1, 0, 0, -1], [0, -1, 1, 0]];
+
+// Hyllian's xBR, at 2x. Edges are detected by comparing the weighted colour differences along
+// both diagonals of a 5x5 window, and the corners of each output block are blended along the
+// dominant edge direction.
+//
+// Neighbours are named as in the reference implementation, relative to the centre pixel E:
+//
+//        A1 B1 C1
+//     A0 A  B  C  C4
+//     D0 D  E  F  F4
+//     G0 G  H  I  I4
+//        G5 H5 I5
+pub fn xbr2x(image: &Image, x: usize, y: usize) -> Block {
+    let mut block = [image.get(x, y, 0, 0); 16];
+
+    for [a, b, c, d] in ROTATIONS {
+        let rotate = |dx: isize, dy: isize| (a * dx + b * dy, c * dx + d * dy);
+
+        let get = |dx, dy| {
+            let (dx, dy) = rotate(dx, dy);
+            image.get(x, y, dx, dy)
+        };
+
+        let yuv = |(dx, dy)| {
+            let (dx, dy) = rotate(dx, dy);
+            image.yuv(x, y, dx, dy)
+        };
+
+        let df = |first, second| distance(yuv(first), yuv(second));
+        let eq = |first, second| similar(yuv(first), yuv(second));
+
+        let (pb, pc) = ((0, -1), (1, -1));
+        let (pd, pe, pf) = ((-1, 0), (0, 0), (1, 0));
+        let (pg, ph, pi) = ((-1, 1), (0, 1), (1, 1));
+        let (f4, i4, h5, i5) = ((2, 0), (2, 1), (0, 2), (1, 2));
+
+        let e = get(0, 0);
+
+        if e == get(0, 1) || e == get(1, 0) {
+            continue;
+        }
+
+        let weight_e = df(pe, pc) + df(pe, pg) + df(pi, h5) + df(pi, f4) + 4 * df(ph, pf);
+        let weight_i = df(ph, pd) + df(ph, i5) + df(pf, i4) + df(pf, pb) + 4 * df(pe, pi);
+
+        let pixel = if df(pe, pf) <= df(pe, ph) {
+            get(1, 0)
+        } else {
+            get(0, 1)
+        };
+
+        // Indices of the bottom right, bottom left and top right pixels of the output block
+        let index = |(dx, dy)| {
+            let (dx, dy): (isize, isize) = rotate(dx, dy);
+            (dy > 0) as usize * 2 + (dx > 0) as usize
+        };
+
+        let (n3, n2, n1) = (index((1, 1)), index((-1, 1)), index((1, -1)));
+
+        let mut blend = |index: usize, amount: u32| {
+            block[index] = mix(&[(block[index], 256 - amount), (pixel, amount)]);
+        };
+
+        let is_edge = (!eq(pf, pb) && !eq(ph, pd))
+            || (eq(pe, pi) && !eq(pf, i4) && !eq(ph, i5))
+            || eq(pe, pg)
+            || eq(pe, pc);
+
+        if weight_e < weight_i && is_edge {
+            let ke = df(pf, pg);
+            let ki = df(ph, pc);
+            let ex2 = e != get(1, -1) && get(0, -1) != get(1, -1);
+            let ex3 = e != get(-1, 1) && get(-1, 0) != get(-1, 1);
+
+            // Shallow edges extend into the neighbouring pixel horizontally, steep edges
+            // extend into it vertically
+            let shallow = 2 * ke <= ki && ex3;
+            let steep = ke >= 2 * ki && ex2;
+
+            if shallow && steep {
+                blend(n3, 224);
+                blend(n2, 64);
+                block[n1] = block[n2];
+            } else if shallow {
+                blend(n3, 192);
+                blend(n2, 64);
+            } else if steep {
+                blend(n3, 192);
+                blend(n1, 64);
+            } else {
+                blend(n3, 128);
+            }
+        } else if weight_e <= weight_i {
+            blend(n3, 128);
+        }
+    }
+
+    block
+}
+
+// Differences are scaled by the hqx similarity thresholds, so each component counts equally
+fn distance(a: [i32; 3], b: [i32; 3]) -> u32 {
+    ((a[0] - b[0]).abs() * 7 + (a[1] - b[1]).abs() * 48 + (a[2] - b[2]).abs() * 56) as u32
+}
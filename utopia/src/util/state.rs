@@ -0,0 +1,223 @@
+use super::mirror::{Mirror, MirrorableMut};
+use crate::Error;
+
+const MAGIC: &[u8; 4] = b"UTST";
+const VERSION: u8 = 1;
+
+// Components implement a single method that is used both to save and to restore their state, so
+// the order of fields can never differ between the two. State is restored in place, so anything
+// that isn't saved (ROM data, output buffers, etc.) is left as it was.
+pub trait Snapshot {
+    fn snapshot(&mut self, state: &mut State);
+}
+
+pub struct State<'a> {
+    output: Vec<u8>,
+    input: Option<&'a [u8]>,
+    overrun: bool,
+}
+
+impl<'a> State<'a> {
+    pub fn bytes(&mut self, bytes: &mut [u8]) {
+        let Some(input) = &mut self.input else {
+            self.output.extend_from_slice(bytes);
+            return;
+        };
+
+        if let Some((head, tail)) = input.split_at_checked(bytes.len()) {
+            bytes.copy_from_slice(head);
+            *input = tail;
+        } else {
+            self.overrun = true;
+        }
+    }
+
+    // Saves the position of the value within the given list
+    pub fn variant<T: Copy + PartialEq>(&mut self, value: &mut T, variants: &[T]) {
+        let mut index = variants
+            .iter()
+            .position(|variant| variant == value)
+            .expect("Value should be one of the given variants") as u8;
+
+        index.snapshot(self);
+
+        match variants.get(index as usize) {
+            Some(variant) => *value = *variant,
+            None => self.overrun = true,
+        }
+    }
+}
+
+pub fn save(value: &mut impl Snapshot) -> Vec<u8> {
+    let mut state = State {
+        output: MAGIC.to_vec(),
+        input: None,
+        overrun: false,
+    };
+
+    state.output.push(VERSION);
+    value.snapshot(&mut state);
+    state.output
+}
+
+// If the data turns out to be invalid, the previous state is put back
+pub fn load(value: &mut impl Snapshot, data: &[u8]) -> Result<(), Error> {
+    let Some(body) = data
+        .strip_prefix(MAGIC)
+        .and_then(|data| data.strip_prefix(&[VERSION]))
+    else {
+        return Err("Unrecognised save state format".into());
+    };
+
+    let backup = save(value);
+
+    let mut state = State {
+        output: Vec::new(),
+        input: Some(body),
+        overrun: false,
+    };
+
+    value.snapshot(&mut state);
+
+    if state.overrun || state.input.is_some_and(|input| !input.is_empty()) {
+        load(value, &backup)?;
+        return Err("Save state does not match the loaded game".into());
+    }
+
+    Ok(())
+}
+
+macro_rules! snapshot_primitive {
+    ($($type:ty),*) => {
+        $(
+            impl Snapshot for $type {
+                fn snapshot(&mut self, state: &mut State) {
+                    let mut bytes = self.to_le_bytes();
+                    state.bytes(&mut bytes);
+                    *self = Self::from_le_bytes(bytes);
+                }
+            }
+        )*
+    };
+}
+
+snapshot_primitive!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+// Sizes are always saved as 64 bits, so states can be shared between platforms
+impl Snapshot for usize {
+    fn snapshot(&mut self, state: &mut State) {
+        let mut value = *self as u64;
+        value.snapshot(state);
+        *self = value as usize;
+    }
+}
+
+impl Snapshot for bool {
+    fn snapshot(&mut self, state: &mut State) {
+        let mut value = *self as u8;
+        value.snapshot(state);
+        *self = value != 0;
+    }
+}
+
+impl<T: Snapshot + Default> Snapshot for Option<T> {
+    fn snapshot(&mut self, state: &mut State) {
+        let mut present = self.is_some();
+        present.snapshot(state);
+
+        if present {
+            self.get_or_insert_with(T::default).snapshot(state);
+        } else {
+            *self = None;
+        }
+    }
+}
+
+impl<T: Snapshot, const N: usize> Snapshot for [T; N] {
+    fn snapshot(&mut self, state: &mut State) {
+        for element in self {
+            element.snapshot(state);
+        }
+    }
+}
+
+// Mirrored memory keeps its size, which is fixed by the cartridge header
+impl<T: MirrorableMut> Snapshot for Mirror<T>
+where
+    T::Output: Snapshot,
+{
+    fn snapshot(&mut self, state: &mut State) {
+        for index in 0..self.len() {
+            self[index].snapshot(state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+    enum Mode {
+        #[default]
+        Off,
+        On,
+    }
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Component {
+        counter: u16,
+        enabled: bool,
+        mode: Mode,
+        buffer: Option<u8>,
+        data: [i32; 3],
+    }
+
+    impl Snapshot for Component {
+        fn snapshot(&mut self, state: &mut State) {
+            self.counter.snapshot(state);
+            self.enabled.snapshot(state);
+            state.variant(&mut self.mode, &[Mode::Off, Mode::On]);
+            self.buffer.snapshot(state);
+            self.data.snapshot(state);
+        }
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut component = Component {
+            counter: 0x1234,
+            enabled: true,
+            mode: Mode::On,
+            buffer: Some(0x56),
+            data: [-1, 2, -3],
+        };
+
+        let data = save(&mut component);
+        assert_eq!(&data[..4], MAGIC);
+
+        let mut restored = Component::default();
+        load(&mut restored, &data).unwrap();
+        assert_eq!(restored, component);
+    }
+
+    #[test]
+    fn invalid_state_is_rolled_back() {
+        let mut component = Component {
+            counter: 0x1234,
+            ..Default::default()
+        };
+
+        let data = save(&mut component);
+        let mut other = Component::default();
+
+        assert!(load(&mut other, &data[..(data.len() - 1)]).is_err());
+        assert_eq!(other, Component::default());
+
+        let mut extended = data.clone();
+        extended.push(0);
+        assert!(load(&mut other, &extended).is_err());
+        assert!(load(&mut other, &data[1..]).is_err());
+        assert_eq!(other, Component::default());
+    }
+}